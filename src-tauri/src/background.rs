@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use tauri::http::{header, Response, StatusCode};
+use tracing::error;
+
+/// Scheme the configured background image is served under, as webviews
+/// can't load files from anywhere on disk. The URL is
+/// `background://localhost/`.
+pub const SCHEME: &str = "background";
+
+/// Responds with the image at `path`, or with 404 if there is none.
+pub fn respond(path: Option<&Path>) -> Response<Vec<u8>> {
+  let image = path.and_then(|path| {
+    std::fs::read(path)
+      .inspect_err(|err| {
+        error!("failed to read background {}: {err}", path.display());
+      })
+      .ok()
+      .map(|image| (image, content_type(path)))
+  });
+
+  let response = match image {
+    Some((image, content_type)) => Response::builder()
+      .header(header::CONTENT_TYPE, content_type)
+      .body(image),
+    None => Response::builder()
+      .status(StatusCode::NOT_FOUND)
+      .body(Vec::new()),
+  };
+
+  response.unwrap_or_default()
+}
+
+fn content_type(path: &Path) -> &'static str {
+  let extension = path
+    .extension()
+    .map(|extension| extension.to_string_lossy().to_lowercase());
+
+  match extension.as_deref() {
+    Some("png") => "image/png",
+    Some("jpg" | "jpeg") => "image/jpeg",
+    Some("webp") => "image/webp",
+    Some("gif") => "image/gif",
+    Some("svg") => "image/svg+xml",
+    Some("avif") => "image/avif",
+    _ => "application/octet-stream",
+  }
+}
@@ -0,0 +1,728 @@
+use std::{collections::BTreeMap, path::Path};
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use toml::Value as TomlValue;
+use toml_edit::{ImDocument, Item, Key, Table, TomlError, Value};
+
+use super::{
+  layers::{self, ConfigError, Includes},
+  legacy::{self, Legacy},
+  schema::schema,
+  ConfigValues,
+};
+
+/// Minimum similarity for a name to be suggested, the same as clap uses
+const SUGGESTION_THRESHOLD: f64 = 0.7;
+
+/// Problems found by [`check`]
+#[derive(Debug, Default)]
+pub struct Report {
+  /// Problems that stop the config from loading
+  pub errors: Vec<ConfigError>,
+  /// Problems the config still loads with, like the deprecated flat format
+  pub warnings: Vec<String>,
+}
+
+/// Checks the files and environment variables that would be loaded from
+/// `path`, see [`layers::load_layers`]. Unlike loading, this reports unknown
+/// keys and keeps going after the first problem.
+pub fn check(path: Option<&Path>) -> Report {
+  let schema = schema();
+  let mut report = Report::default();
+
+  for file in layers::base_files(path) {
+    check_file(&schema, &file, 0, &mut report);
+  }
+
+  for (var, raw) in layers::env_overrides() {
+    check_env(&schema, var, &raw, &mut report);
+  }
+
+  // Anything the layers only break together
+  if report.errors.is_empty() {
+    if let Err(err) = layers::load_layers(path) {
+      report.errors.push(err);
+    }
+  }
+
+  report
+}
+
+fn check_file(schema: &RootSchema, path: &Path, depth: usize, report: &mut Report) {
+  if depth > layers::MAX_INCLUDE_DEPTH {
+    report.errors.push(ConfigError::IncludeDepth {
+      path: path.to_path_buf(),
+    });
+    return;
+  }
+
+  let raw = match std::fs::read_to_string(path) {
+    Ok(raw) => raw,
+    Err(source) => {
+      report.errors.push(ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+      });
+      return;
+    }
+  };
+
+  let mut node = match Node::parse(&raw) {
+    Ok(node) => node,
+    Err(err) => {
+      let offset = err.span().map_or(0, |span| span.start);
+      report.errors.push(layers::invalid_at(
+        path,
+        &raw,
+        offset,
+        err.message().to_string(),
+      ));
+      return;
+    }
+  };
+
+  let deprecations = node.migrate();
+  report.warnings.extend(
+    deprecations
+      .into_iter()
+      .map(|problem| layers::invalid_at(path, &raw, problem.offset, problem.message).to_string()),
+  );
+
+  let mut problems = Validator::new(schema).validate(&node);
+
+  // The schema doesn't cover everything, e.g. the range of integers
+  let typed = [
+    toml::from_str::<ConfigValues>(&raw).err(),
+    toml::from_str::<Legacy>(&raw).err(),
+  ];
+
+  for err in typed.into_iter().flatten() {
+    let offset = err.span().map_or(0, |span| span.start);
+    if !problems.iter().any(|problem| problem.offset == offset) {
+      problems.push(Problem {
+        offset,
+        message: err.message().to_string(),
+      });
+    }
+  }
+
+  problems.sort_by_key(|problem| problem.offset);
+  report.errors.extend(
+    problems
+      .into_iter()
+      .map(|problem| layers::invalid_at(path, &raw, problem.offset, problem.message)),
+  );
+
+  // Broken includes were reported above
+  let Ok(includes) = toml::from_str::<Includes>(&raw) else {
+    return;
+  };
+
+  let dir = path.parent().unwrap_or(Path::new("/"));
+  for include in includes.include {
+    match layers::include_files(&dir.join(include)) {
+      Ok(files) => {
+        for file in files {
+          check_file(schema, &file, depth + 1, report);
+        }
+      }
+      Err(err) => report.errors.push(err),
+    }
+  }
+}
+
+fn check_env(schema: &RootSchema, var: String, raw: &str, report: &mut Report) {
+  let mut overlay = match layers::env_overlay(&var, raw) {
+    Ok(overlay) => overlay,
+    Err(message) => {
+      report.errors.push(ConfigError::Env { var, message });
+      return;
+    }
+  };
+
+  for key in legacy::migrate(&mut overlay) {
+    report
+      .warnings
+      .push(format!("{var}: {}", legacy::deprecation(&key)));
+  }
+
+  // Nodes are only parsed from text, the positions don't matter here
+  let node = toml::to_string(&overlay)
+    .ok()
+    .and_then(|text| Node::parse(&text).ok());
+
+  let problems = node.map_or_else(Vec::new, |node| Validator::new(schema).validate(&node));
+  if problems.is_empty() {
+    if let Err(err) = TomlValue::Table(overlay).try_into::<ConfigValues>() {
+      report.errors.push(ConfigError::Env {
+        var,
+        message: err.message().to_string(),
+      });
+    }
+    return;
+  }
+
+  report
+    .errors
+    .extend(problems.into_iter().map(|problem| ConfigError::Env {
+      var: var.clone(),
+      message: problem.message,
+    }));
+}
+
+/// TOML value and where it starts. Only strings keep their value, for
+/// checking enums.
+#[derive(Clone)]
+struct Node {
+  offset: usize,
+  kind: Kind,
+}
+
+#[derive(Clone)]
+enum Kind {
+  Table(Vec<Entry>),
+  Array(Vec<Node>),
+  String(String),
+  Integer,
+  Float,
+  Boolean,
+  Datetime,
+}
+
+#[derive(Clone)]
+struct Entry {
+  key: String,
+  offset: usize,
+  value: Node,
+}
+
+impl Node {
+  fn parse(raw: &str) -> Result<Self, TomlError> {
+    let document = ImDocument::parse(raw)?;
+    Ok(Self::from_table(document.as_table(), 0))
+  }
+
+  /// Implicit tables, e.g. `a` in `[a.b]`, have no position of their own
+  fn from_table(table: &Table, fallback: usize) -> Self {
+    let offset = table.span().map_or(fallback, |span| span.start);
+    let entries = table
+      .iter()
+      .filter_map(|(key, item)| {
+        let offset = table
+          .key(key)
+          .and_then(Key::span)
+          .map_or(offset, |span| span.start);
+        let value = Self::from_item(item, offset)?;
+
+        Some(Entry {
+          key: key.to_string(),
+          offset,
+          value,
+        })
+      })
+      .collect();
+
+    Self {
+      offset,
+      kind: Kind::Table(entries),
+    }
+  }
+
+  fn from_item(item: &Item, fallback: usize) -> Option<Self> {
+    match item {
+      Item::None => None,
+      Item::Value(value) => Some(Self::from_value(value, fallback)),
+      Item::Table(table) => Some(Self::from_table(table, fallback)),
+      Item::ArrayOfTables(tables) => Some(Self {
+        offset: tables.span().map_or(fallback, |span| span.start),
+        kind: Kind::Array(
+          tables
+            .iter()
+            .map(|table| Self::from_table(table, fallback))
+            .collect(),
+        ),
+      }),
+    }
+  }
+
+  fn from_value(value: &Value, fallback: usize) -> Self {
+    let offset = value.span().map_or(fallback, |span| span.start);
+    let kind = match value {
+      Value::String(value) => Kind::String(value.value().clone()),
+      Value::Integer(_) => Kind::Integer,
+      Value::Float(_) => Kind::Float,
+      Value::Boolean(_) => Kind::Boolean,
+      Value::Datetime(_) => Kind::Datetime,
+      Value::Array(array) => Kind::Array(
+        array
+          .iter()
+          .map(|value| Self::from_value(value, offset))
+          .collect(),
+      ),
+      Value::InlineTable(table) => Kind::Table(
+        table
+          .iter()
+          .map(|(key, value)| {
+            let offset = table
+              .key(key)
+              .and_then(Key::span)
+              .map_or(offset, |span| span.start);
+
+            Entry {
+              key: key.to_string(),
+              offset,
+              value: Self::from_value(value, offset),
+            }
+          })
+          .collect(),
+      ),
+    };
+
+    Self { offset, kind }
+  }
+
+  /// Moves top-level keys of the flat format into their sections, like
+  /// [`legacy::migrate`], and returns a deprecation for each of them
+  fn migrate(&mut self) -> Vec<Problem> {
+    let Kind::Table(entries) = &mut self.kind else {
+      return Vec::new();
+    };
+
+    let (moved, kept): (Vec<_>, Vec<_>) = std::mem::take(entries)
+      .into_iter()
+      .partition(|entry| legacy::sections(&entry.key).is_some());
+    *entries = kept;
+
+    let mut problems = Vec::new();
+    for entry in moved {
+      problems.push(Problem {
+        offset: entry.offset,
+        message: legacy::deprecation(&entry.key),
+      });
+
+      for section in legacy::sections(&entry.key).unwrap_or_default() {
+        let index = match entries.iter().position(|other| other.key == *section) {
+          Some(index) => index,
+          None => {
+            entries.push(Entry {
+              key: section.to_string(),
+              offset: entry.offset,
+              value: Node {
+                offset: entry.offset,
+                kind: Kind::Table(Vec::new()),
+              },
+            });
+            entries.len() - 1
+          }
+        };
+
+        // Keys may repeat here, each entry is checked on its own
+        if let Kind::Table(section) = &mut entries[index].value.kind {
+          section.push(entry.clone());
+        }
+      }
+    }
+
+    problems
+  }
+
+  fn matches(&self, instance_type: &InstanceType) -> bool {
+    matches!(
+      (&self.kind, instance_type),
+      (Kind::Table(_), InstanceType::Object)
+        | (Kind::Array(_), InstanceType::Array)
+        | (Kind::String(_), InstanceType::String)
+        | (Kind::Integer, InstanceType::Integer | InstanceType::Number)
+        | (Kind::Float, InstanceType::Number)
+        | (Kind::Boolean, InstanceType::Boolean)
+    )
+  }
+
+  fn describe(&self) -> &'static str {
+    match self.kind {
+      Kind::Table(_) => "a table",
+      Kind::Array(_) => "a list",
+      Kind::String(_) => "a string",
+      Kind::Integer => "an integer",
+      Kind::Float => "a number",
+      Kind::Boolean => "a boolean",
+      Kind::Datetime => "a date",
+    }
+  }
+}
+
+struct Problem {
+  /// Byte offset into the checked file
+  offset: usize,
+  message: String,
+}
+
+/// Checks nodes against the parts of JSON Schema that `schemars` generates
+/// for the config
+struct Validator<'a> {
+  root: &'a RootSchema,
+  problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+  fn new(root: &'a RootSchema) -> Self {
+    Self {
+      root,
+      problems: Vec::new(),
+    }
+  }
+
+  fn validate(mut self, node: &Node) -> Vec<Problem> {
+    let root = &self.root.schema;
+    self.check_object(std::slice::from_ref(&root), node);
+    self.problems
+  }
+
+  /// Follows references and flattens `allOf`, `anyOf` and `oneOf`. Those
+  /// only combine a reference with a default or list alternatives here, so
+  /// a value has to match any of the returned schemas.
+  fn alternatives(&self, schema: &'a Schema) -> Vec<&'a SchemaObject> {
+    let Schema::Object(object) = schema else {
+      return Vec::new();
+    };
+
+    if let Some(reference) = &object.reference {
+      let name = reference.trim_start_matches("#/definitions/");
+      return self
+        .root
+        .definitions
+        .get(name)
+        .map_or_else(Vec::new, |schema| self.alternatives(schema));
+    }
+
+    let mut alternatives = object
+      .subschemas
+      .iter()
+      .flat_map(|subschemas| [&subschemas.all_of, &subschemas.any_of, &subschemas.one_of])
+      .flatten()
+      .flatten()
+      .flat_map(|schema| self.alternatives(schema))
+      .collect::<Vec<_>>();
+
+    if object.instance_type.is_some() || object.enum_values.is_some() {
+      alternatives.push(object);
+    }
+
+    alternatives
+  }
+
+  fn check(&mut self, schema: &'a Schema, node: &Node) {
+    let alternatives = self.alternatives(schema);
+    self.check_object(&alternatives, node);
+  }
+
+  fn check_object(&mut self, alternatives: &[&'a SchemaObject], node: &Node) {
+    // Anything goes
+    if alternatives.is_empty() {
+      return;
+    }
+
+    let types = alternatives
+      .iter()
+      .filter_map(|object| object.instance_type.as_ref())
+      .flat_map(|types| match types {
+        SingleOrVec::Single(instance_type) => std::slice::from_ref(instance_type.as_ref()),
+        SingleOrVec::Vec(types) => types.as_slice(),
+      })
+      .collect::<Vec<_>>();
+
+    if !types.is_empty()
+      && !types
+        .iter()
+        .any(|instance_type| node.matches(instance_type))
+    {
+      self.problems.push(Problem {
+        offset: node.offset,
+        message: type_mismatch(&types, node),
+      });
+      return;
+    }
+
+    match &node.kind {
+      Kind::Table(entries) => self.check_table(alternatives, entries),
+      Kind::Array(items) => {
+        let item_schema = alternatives
+          .iter()
+          .filter_map(|object| object.array.as_ref()?.items.as_ref())
+          .find_map(|items| match items {
+            SingleOrVec::Single(schema) => Some(schema.as_ref()),
+            SingleOrVec::Vec(_) => None,
+          });
+
+        if let Some(item_schema) = item_schema {
+          for item in items {
+            self.check(item_schema, item);
+          }
+        }
+      }
+      Kind::String(value) => {
+        let allowed = alternatives
+          .iter()
+          .filter_map(|object| object.enum_values.as_ref())
+          .flatten()
+          .filter_map(|value| value.as_str())
+          .collect::<Vec<_>>();
+
+        if !allowed.is_empty() && !allowed.contains(&value.as_str()) {
+          let mut message = format!(
+            "unknown value `{value}`, expected one of {}",
+            allowed
+              .iter()
+              .map(|allowed| format!("`{allowed}`"))
+              .collect::<Vec<_>>()
+              .join(", ")
+          );
+
+          if let Some(suggestion) = suggest(value, allowed) {
+            message.push_str(&format!(", did you mean `{suggestion}`?"));
+          }
+
+          self.problems.push(Problem {
+            offset: node.offset,
+            message,
+          });
+        }
+      }
+      Kind::Integer | Kind::Float | Kind::Boolean | Kind::Datetime => {}
+    }
+  }
+
+  fn check_table(&mut self, alternatives: &[&'a SchemaObject], entries: &[Entry]) {
+    let objects = alternatives
+      .iter()
+      .filter_map(|object| object.object.as_deref())
+      .collect::<Vec<_>>();
+
+    let properties = objects
+      .iter()
+      .flat_map(|object| &object.properties)
+      .collect::<BTreeMap<_, _>>();
+
+    // Maps like `session.env` allow any key
+    let additional = objects
+      .iter()
+      .find_map(|object| object.additional_properties.as_deref());
+
+    for entry in entries {
+      let Some(schema) = properties.get(&entry.key).copied().or(additional) else {
+        let mut message = format!("unknown key `{}`", entry.key);
+
+        if let Some(suggestion) = suggest(&entry.key, properties.keys().map(|key| key.as_str())) {
+          message.push_str(&format!(", did you mean `{suggestion}`?"));
+        }
+
+        self.problems.push(Problem {
+          offset: entry.offset,
+          message,
+        });
+        continue;
+      };
+
+      self.check(schema, &entry.value);
+    }
+  }
+}
+
+fn type_mismatch(types: &[&InstanceType], node: &Node) -> String {
+  let expected = types
+    .iter()
+    .filter_map(|instance_type| match instance_type {
+      InstanceType::Null => None,
+      InstanceType::Boolean => Some("a boolean"),
+      InstanceType::Object => Some("a table"),
+      InstanceType::Array => Some("a list"),
+      InstanceType::Number => Some("a number"),
+      InstanceType::String => Some("a string"),
+      InstanceType::Integer => Some("an integer"),
+    })
+    .collect::<Vec<_>>()
+    .join(" or ");
+
+  let mut message = format!("expected {expected}, found {}", node.describe());
+
+  let expects = |expected: InstanceType| types.contains(&&expected);
+  let hint = match &node.kind {
+    Kind::String(value)
+      if (expects(InstanceType::Integer) && value.trim().parse::<i64>().is_ok())
+        || (expects(InstanceType::Number) && value.trim().parse::<f64>().is_ok())
+        || (expects(InstanceType::Boolean) && value.trim().parse::<bool>().is_ok()) =>
+    {
+      Some("remove the quotes")
+    }
+    Kind::Integer | Kind::Float | Kind::Boolean if expects(InstanceType::String) => {
+      Some("put the value in quotes")
+    }
+    _ if expects(InstanceType::Array) => Some("put the value in a list, e.g. `[value]`"),
+    _ => None,
+  };
+
+  if let Some(hint) = hint {
+    message.push_str(&format!(", {hint}"));
+  }
+
+  message
+}
+
+/// The most similar of `candidates`, if any is similar enough
+fn suggest<'b>(name: &str, candidates: impl IntoIterator<Item = &'b str>) -> Option<&'b str> {
+  candidates
+    .into_iter()
+    .map(|candidate| (strsim::jaro(name, candidate), candidate))
+    .filter(|(similarity, _)| *similarity > SUGGESTION_THRESHOLD)
+    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+    .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  /// Errors and warnings for the files under `dir`, as
+  /// `file:line:column: message` relative to it
+  fn report_in(dir: &TempDir, name: &str) -> (Vec<String>, Vec<String>) {
+    let mut report = Report::default();
+    check_file(&schema(), &dir.path().join(name), 0, &mut report);
+
+    let prefix = format!("{}/", dir.path().display());
+    let relative = |message: String| message.replace(&prefix, "");
+    (
+      report
+        .errors
+        .iter()
+        .map(|err| relative(err.to_string()))
+        .collect(),
+      report.warnings.into_iter().map(relative).collect(),
+    )
+  }
+
+  fn check_in(dir: &TempDir, name: &str) -> Vec<String> {
+    report_in(dir, name).0
+  }
+
+  fn report_str(name: &str, raw: &str) -> (Vec<String>, Vec<String>) {
+    let dir = TempDir::new(&format!("check-{name}"));
+    dir.write("config.toml", raw);
+    report_in(&dir, "config.toml")
+  }
+
+  fn check_str(name: &str, raw: &str) -> Vec<String> {
+    report_str(name, raw).0
+  }
+
+  #[test]
+  fn suggests_similar_keys() {
+    let errors = check_str("keys", "[greeter]\nusr = \"alice\"\n[desktop.idel]\n");
+
+    assert_eq!(
+      errors,
+      [
+        "config.toml:2:1: unknown key `usr`, did you mean `user`?",
+        "config.toml:3:10: unknown key `idel`, did you mean `idle`?",
+      ]
+    );
+  }
+
+  #[test]
+  fn suggests_similar_values() {
+    let errors = check_str("values", "[desktop]\npanels = [\"workspaces\"]\n");
+
+    assert_eq!(
+      errors,
+      [
+        "config.toml:2:11: unknown value `workspaces`, expected one of `workspace`, `control`, \
+        `bars`, did you mean `workspace`?"
+      ]
+    );
+  }
+
+  #[test]
+  fn hints_at_quotes_and_lists() {
+    let errors = check_str(
+      "hints",
+      "[desktop.idle]\nlock_after = \"300\"\n[lock]\nprimary_display = \"DP-1\"\n",
+    );
+
+    assert_eq!(
+      errors,
+      [
+        "config.toml:2:14: expected an integer, found a string, remove the quotes",
+        "config.toml:4:19: expected a list, found a string, put the value in a list, e.g. \
+         `[value]`",
+      ]
+    );
+  }
+
+  #[test]
+  fn points_into_nested_and_inline_tables() {
+    let errors = check_str(
+      "nested",
+      "desktop = { idle = { dim_brightness = \"low\" } }\n\
+       [lock.throttle]\n\n  max_attempts = true\n",
+    );
+
+    assert_eq!(
+      errors,
+      [
+        "config.toml:1:39: expected a number, found a string",
+        "config.toml:4:18: expected an integer, found a boolean",
+      ]
+    );
+  }
+
+  #[test]
+  fn checks_included_files() {
+    let dir = TempDir::new("check-include");
+    dir.write("config.toml", "include = [\"conf.d\"]\n");
+    dir.write("conf.d/10-lock.toml", "[lock.pam]\nservic = \"login\"\n");
+
+    assert_eq!(
+      check_in(&dir, "config.toml"),
+      ["conf.d/10-lock.toml:2:1: unknown key `servic`, did you mean `service`?"]
+    );
+  }
+
+  #[test]
+  fn stops_include_recursion() {
+    let dir = TempDir::new("check-recursion");
+    dir.write("config.toml", "include = [\"config.toml\"]\n");
+
+    assert_eq!(
+      check_in(&dir, "config.toml"),
+      ["config.toml: includes are nested too deeply"]
+    );
+  }
+
+  #[test]
+  fn warns_about_legacy_keys() {
+    let (errors, warnings) = report_str(
+      "legacy",
+      "[idle]\nlock_after = 300\n[pam]\nservic = \"login\"\n",
+    );
+
+    assert_eq!(
+      warnings,
+      [
+        "config.toml:1:2: the flat config format is deprecated, move `idle` to `[desktop]`",
+        "config.toml:3:2: the flat config format is deprecated, move `pam` to `[lock]`",
+      ]
+    );
+    assert_eq!(
+      errors,
+      ["config.toml:4:1: unknown key `servic`, did you mean `service`?"]
+    );
+  }
+
+  #[test]
+  fn accepts_valid_legacy_keys() {
+    let (errors, warnings) = report_str("legacy-valid", "[idle]\nlock_after = 300\n");
+
+    assert!(errors.is_empty());
+    assert_eq!(warnings.len(), 1);
+  }
+}
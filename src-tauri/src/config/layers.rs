@@ -0,0 +1,486 @@
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use thiserror::Error;
+use toml::{Table, Value};
+use tracing::warn;
+
+use super::{
+  legacy::{self, Legacy},
+  ConfigValues,
+};
+
+const SYSTEM_CONFIG: &str = "/etc/dash2/config.toml";
+const USER_CONFIG: &str = "dash2/config.toml";
+
+const ENV_PREFIX: &str = "DASH2_";
+/// Separates nested keys in environment overrides, e.g. `DASH2_IDLE__LOCK_AFTER`
+const ENV_SEPARATOR: &str = "__";
+
+pub const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+  #[error("failed to read {}: {source}", path.display())]
+  Io {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+
+  #[error("{}:{line}:{column}: {message}", path.display())]
+  Invalid {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    message: String,
+  },
+
+  #[error("invalid value in {var}: {message}")]
+  Env { var: String, message: String },
+
+  #[error("{}: includes are nested too deeply", path.display())]
+  IncludeDepth { path: PathBuf },
+
+  #[error("invalid configuration: {0}")]
+  Merged(String),
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct Includes {
+  /// Files or directories of `*.toml` files merged after this file,
+  /// relative to it
+  #[serde(default)]
+  pub include: Vec<PathBuf>,
+}
+
+pub struct Loaded {
+  pub values: ConfigValues,
+  /// Files the values were read from, lowest priority first
+  pub files: Vec<PathBuf>,
+  /// Directories in which changes may affect the values
+  pub dirs: Vec<PathBuf>,
+}
+
+/// Merges the system config, the user config and `DASH2_*` environment
+/// variables, each overriding the previous per key. An explicit path takes
+/// the place of the user config.
+pub fn load_layers(path: Option<&Path>) -> Result<Loaded, ConfigError> {
+  let configs = [Some(PathBuf::from(SYSTEM_CONFIG)), user_config(path)];
+  layer(&base_files(path), configs.iter().flatten(), env_overrides())
+}
+
+/// Merges the `base` files and `env` overrides. The directories of
+/// `configs` are watched even if the files don't exist yet.
+fn layer<'a>(
+  base: &[PathBuf],
+  configs: impl IntoIterator<Item = &'a PathBuf>,
+  env: Vec<(String, String)>,
+) -> Result<Loaded, ConfigError> {
+  let mut merged = Table::new();
+  let mut files = Vec::new();
+  let mut dirs = Vec::new();
+
+  for file in base {
+    merge_file(&mut merged, &mut files, &mut dirs, file, 0)?;
+  }
+
+  // Watching the config dirs notices the files being created
+  for config in configs {
+    if let Some(parent) = config.parent().filter(|parent| parent.is_dir()) {
+      dirs.push(parent.to_path_buf());
+    }
+  }
+
+  for (var, raw) in env {
+    merge_env(&mut merged, var, &raw)?;
+  }
+
+  let values = Value::Table(merged)
+    .try_into()
+    .map_err(|err: toml::de::Error| ConfigError::Merged(err.message().to_string()))?;
+
+  dirs.sort();
+  dirs.dedup();
+
+  Ok(Loaded {
+    values,
+    files,
+    dirs,
+  })
+}
+
+/// The system config and the user config, if they exist. An explicit path
+/// takes the place of the user config and has to exist.
+pub fn base_files(path: Option<&Path>) -> Vec<PathBuf> {
+  let system = PathBuf::from(SYSTEM_CONFIG);
+  let user = user_config(path).filter(|user| user.exists() || path.is_some());
+
+  system
+    .exists()
+    .then_some(system)
+    .into_iter()
+    .chain(user)
+    .collect()
+}
+
+fn user_config(path: Option<&Path>) -> Option<PathBuf> {
+  match path {
+    Some(path) => Some(path.to_path_buf()),
+    None => dirs::config_dir().map(|dir| dir.join(USER_CONFIG)),
+  }
+}
+
+/// `DASH2_*` environment variables, sorted by name
+pub fn env_overrides() -> Vec<(String, String)> {
+  let mut vars = std::env::vars()
+    .filter(|(var, _)| var.starts_with(ENV_PREFIX))
+    .collect::<Vec<_>>();
+  vars.sort();
+
+  vars
+}
+
+fn merge_file(
+  merged: &mut Table,
+  files: &mut Vec<PathBuf>,
+  dirs: &mut Vec<PathBuf>,
+  path: &Path,
+  depth: usize,
+) -> Result<(), ConfigError> {
+  if depth > MAX_INCLUDE_DEPTH {
+    return Err(ConfigError::IncludeDepth {
+      path: path.to_path_buf(),
+    });
+  }
+
+  let raw = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+    path: path.to_path_buf(),
+    source,
+  })?;
+
+  // Every file is checked on its own, so errors point into it
+  toml::from_str::<ConfigValues>(&raw).map_err(|err| invalid(path, &raw, err))?;
+  toml::from_str::<Legacy>(&raw).map_err(|err| invalid(path, &raw, err))?;
+  let includes: Includes = toml::from_str(&raw).map_err(|err| invalid(path, &raw, err))?;
+  let mut table: Table = toml::from_str(&raw).map_err(|err| invalid(path, &raw, err))?;
+
+  table.remove("include");
+  for key in legacy::migrate(&mut table) {
+    warn!("{}: {}", path.display(), legacy::deprecation(&key));
+  }
+
+  merge(merged, table);
+  files.push(path.to_path_buf());
+
+  let dir = path.parent().unwrap_or(Path::new("/"));
+  dirs.push(dir.to_path_buf());
+
+  for include in includes.include {
+    let include = dir.join(include);
+    if include.is_dir() {
+      dirs.push(include.clone());
+    }
+
+    for file in include_files(&include)? {
+      merge_file(merged, files, dirs, &file, depth + 1)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Expands a directory into its `*.toml` files, sorted by name
+pub fn include_files(path: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+  if !path.is_dir() {
+    return Ok(vec![path.to_path_buf()]);
+  }
+
+  let io_error = |source| ConfigError::Io {
+    path: path.to_path_buf(),
+    source,
+  };
+
+  let mut files = std::fs::read_dir(path)
+    .map_err(io_error)?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(io_error)?;
+
+  files.retain(|file| file.is_file() && file.extension().is_some_and(|ext| ext == "toml"));
+  files.sort();
+
+  Ok(files)
+}
+
+/// Applies a single `DASH2_*` override, see [`env_overlay`]
+fn merge_env(merged: &mut Table, var: String, raw: &str) -> Result<(), ConfigError> {
+  let mut overlay = env_overlay(&var, raw).map_err(|message| ConfigError::Env {
+    var: var.clone(),
+    message,
+  })?;
+
+  for key in legacy::migrate(&mut overlay) {
+    warn!("{var}: {}", legacy::deprecation(&key));
+  }
+
+  if let Err(err) = Value::Table(overlay.clone()).try_into::<ConfigValues>() {
+    return Err(ConfigError::Env {
+      var,
+      message: err.message().to_string(),
+    });
+  }
+
+  merge(merged, overlay);
+  Ok(())
+}
+
+/// Turns `DASH2_A__B=value` into the table `{ a = { b = value } }`. Values
+/// are parsed as TOML, so `DASH2_PAM__ADMIN_USERS='["root"]'` sets a list.
+/// Anything that isn't valid TOML, or only fits the config as a string,
+/// like `DASH2_GREETER__USER=1000`, is taken as a string.
+pub fn env_overlay(var: &str, raw: &str) -> Result<Table, String> {
+  let keys = var[ENV_PREFIX.len()..]
+    .split(ENV_SEPARATOR)
+    .map(str::to_lowercase)
+    .collect::<Vec<_>>();
+
+  if keys.iter().any(String::is_empty) {
+    return Err("empty key".to_string());
+  }
+
+  let string = nest(&keys, Value::String(raw.to_string()));
+  let Some(value) = toml::from_str::<Table>(&format!("value = {raw}"))
+    .ok()
+    .and_then(|mut table| table.remove("value"))
+  else {
+    return Ok(string);
+  };
+
+  // If neither fits, errors are about the parsed value
+  let typed = nest(&keys, value);
+  if !fits(&typed) && fits(&string) {
+    return Ok(string);
+  }
+
+  Ok(typed)
+}
+
+fn nest(keys: &[String], value: Value) -> Table {
+  let overlay = keys.iter().rev().fold(value, |value, key| {
+    Value::Table(Table::from_iter([(key.clone(), value)]))
+  });
+
+  let Value::Table(overlay) = overlay else {
+    unreachable!("keys are never empty");
+  };
+
+  overlay
+}
+
+/// Whether an overlay on its own is a valid config
+fn fits(overlay: &Table) -> bool {
+  let mut overlay = overlay.clone();
+  legacy::migrate(&mut overlay);
+  Value::Table(overlay).try_into::<ConfigValues>().is_ok()
+}
+
+/// Merges tables key by key, anything else is replaced
+pub fn merge(base: &mut Table, overlay: Table) {
+  for (key, value) in overlay {
+    match (base.get_mut(&key), value) {
+      (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+      (_, value) => {
+        base.insert(key, value);
+      }
+    }
+  }
+}
+
+pub fn invalid(path: &Path, raw: &str, err: toml::de::Error) -> ConfigError {
+  let offset = err.span().map(|span| span.start).unwrap_or(0);
+  invalid_at(path, raw, offset, err.message().to_string())
+}
+
+/// Error pointing at the line and column of a byte offset into `raw`
+pub fn invalid_at(path: &Path, raw: &str, offset: usize, message: String) -> ConfigError {
+  let before = raw.get(..offset).unwrap_or(raw);
+
+  ConfigError::Invalid {
+    path: path.to_path_buf(),
+    line: before.matches('\n').count() + 1,
+    column: before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1,
+    message,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars
+      .iter()
+      .map(|(var, raw)| (var.to_string(), raw.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn layers_files_and_env_per_key() {
+    let dir = TempDir::new("layers");
+    let system = dir.write(
+      "system.toml",
+      "[greeter]\nuser = \"alice\"\n[lock.pam]\nservice = \"login\"\n\
+       [desktop.idle]\nlock_after = 300\n",
+    );
+    let user = dir.write(
+      "user.toml",
+      "[greeter]\nuser = \"bob\"\n[desktop.idle]\ndim_after = 60\n",
+    );
+
+    let loaded = layer(
+      &[system.clone(), user.clone()],
+      [&system, &user],
+      env(&[("DASH2_LOCK__PAM__SERVICE", "dash2-lock")]),
+    )
+    .unwrap();
+
+    assert_eq!(loaded.values.greeter.user, "bob");
+    assert_eq!(loaded.values.lock.pam.service, "dash2-lock");
+    assert_eq!(loaded.values.desktop.idle.lock_after, 300);
+    assert_eq!(loaded.values.desktop.idle.dim_after, 60);
+    assert_eq!(loaded.files, [system, user]);
+    assert_eq!(loaded.dirs, [dir.path()]);
+  }
+
+  #[test]
+  fn watches_dirs_of_missing_configs() {
+    let dir = TempDir::new("layers-missing");
+    std::fs::create_dir(dir.path().join("etc")).unwrap();
+
+    let configs = [
+      dir.path().join("etc/config.toml"),
+      dir.path().join("home/config.toml"),
+    ];
+    let loaded = layer(&[], &configs, Vec::new()).unwrap();
+
+    assert!(loaded.files.is_empty());
+    assert_eq!(loaded.dirs, [dir.path().join("etc")]);
+  }
+
+  #[test]
+  fn merges_includes_in_order() {
+    let dir = TempDir::new("layers-include");
+    let config = dir.write(
+      "config.toml",
+      "include = [\"conf.d\"]\n[greeter]\nuser = \"alice\"\n",
+    );
+    let second = dir.write("conf.d/20-second.toml", "[greeter]\nuser = \"carol\"\n");
+    let first = dir.write("conf.d/10-first.toml", "[greeter]\nuser = \"bob\"\n");
+    dir.write("conf.d/30-ignored.txt", "[greeter]\nuser = \"dave\"\n");
+
+    let loaded = layer(std::slice::from_ref(&config), &[], Vec::new()).unwrap();
+
+    assert_eq!(loaded.values.greeter.user, "carol");
+    assert_eq!(loaded.files, [config, first, second]);
+    assert_eq!(
+      loaded.dirs,
+      [dir.path().to_path_buf(), dir.path().join("conf.d")]
+    );
+  }
+
+  #[test]
+  fn limits_include_depth() {
+    let dir = TempDir::new("layers-depth");
+    let config = dir.write("config.toml", "include = [\"config.toml\"]\n");
+
+    let err = layer(std::slice::from_ref(&config), &[], Vec::new())
+      .err()
+      .unwrap();
+
+    assert!(matches!(err, ConfigError::IncludeDepth { path } if path == config));
+  }
+
+  #[test]
+  fn points_at_the_invalid_line() {
+    let dir = TempDir::new("layers-invalid");
+    let config = dir.write(
+      "config.toml",
+      "[greeter]\nuser = \"alice\"\n\n[desktop.idle]\nlock_after = -1\n",
+    );
+
+    let err = layer(std::slice::from_ref(&config), &[], Vec::new())
+      .err()
+      .unwrap();
+
+    let ConfigError::Invalid {
+      path, line, column, ..
+    } = err
+    else {
+      panic!("unexpected error: {err}");
+    };
+    assert_eq!((path, line, column), (config, 5, 14));
+  }
+
+  #[test]
+  fn counts_columns_in_bytes_from_the_line_start() {
+    let raw = "a = 1\nb = \"ä\"";
+    let ConfigError::Invalid { line, column, .. } =
+      invalid_at(Path::new("config.toml"), raw, raw.len(), String::new())
+    else {
+      unreachable!();
+    };
+
+    assert_eq!((line, column), (2, 9));
+  }
+
+  #[test]
+  fn parses_env_values_as_toml() {
+    let loaded = layer(
+      &[],
+      &[],
+      env(&[
+        ("DASH2_DESKTOP__IDLE__LOCK_AFTER", "300"),
+        ("DASH2_LOCK__PAM__ADMIN_USERS", "[\"root\"]"),
+        ("DASH2_LOCK__PAM__SERVICE", "dash2-lock"),
+      ]),
+    )
+    .unwrap();
+
+    assert_eq!(loaded.values.desktop.idle.lock_after, 300);
+    assert_eq!(loaded.values.lock.pam.admin_users, ["root"]);
+    assert_eq!(loaded.values.lock.pam.service, "dash2-lock");
+  }
+
+  #[test]
+  fn takes_env_values_as_strings_where_needed() {
+    let loaded = layer(
+      &[],
+      &[],
+      env(&[
+        ("DASH2_GREETER__USER", "1000"),
+        ("DASH2_LOCK__PAM__SERVICE", "true"),
+      ]),
+    )
+    .unwrap();
+
+    assert_eq!(loaded.values.greeter.user, "1000");
+    assert_eq!(loaded.values.lock.pam.service, "true");
+  }
+
+  #[test]
+  fn rejects_invalid_env_values() {
+    let err = layer(
+      &[],
+      &[],
+      env(&[("DASH2_DESKTOP__IDLE__LOCK_AFTER", "soon")]),
+    )
+    .err()
+    .unwrap();
+
+    assert!(
+      matches!(err, ConfigError::Env { ref var, .. } if var == "DASH2_DESKTOP__IDLE__LOCK_AFTER")
+    );
+  }
+}
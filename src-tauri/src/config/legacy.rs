@@ -0,0 +1,87 @@
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use super::{layers, Idle, Pam, Session, Throttle, Users};
+
+/// Top-level keys of the flat format used before the per-mode sections,
+/// and the sections they moved to
+const MOVED: [(&str, &[&str]); 8] = [
+  ("user", &["greeter"]),
+  ("primary_display", &["greeter", "lock", "desktop"]),
+  ("session", &["greeter"]),
+  ("users", &["greeter"]),
+  ("xsession_wrapper", &["greeter"]),
+  ("throttle", &["lock"]),
+  ("pam", &["lock"]),
+  ("idle", &["desktop"]),
+];
+
+/// Values of the flat format, only deserialized to check them
+#[derive(Debug, Deserialize)]
+pub struct Legacy {
+  user: Option<String>,
+  primary_display: Option<Vec<String>>,
+  session: Option<Session>,
+  users: Option<Users>,
+  xsession_wrapper: Option<Vec<String>>,
+  throttle: Option<Throttle>,
+  pam: Option<Pam>,
+  idle: Option<Idle>,
+}
+
+/// Sections a top-level key of the flat format moved to
+pub fn sections(key: &str) -> Option<&'static [&'static str]> {
+  MOVED
+    .iter()
+    .find(|(moved, _)| *moved == key)
+    .map(|(_, sections)| *sections)
+}
+
+/// Moves keys of the flat format into their sections and returns them.
+/// Values set in the section itself take precedence.
+pub fn migrate(table: &mut Table) -> Vec<String> {
+  let mut moved = Vec::new();
+
+  for (key, sections) in MOVED {
+    let Some(value) = table.remove(key) else {
+      continue;
+    };
+
+    for section in sections {
+      let section = table
+        .entry(*section)
+        .or_insert_with(|| Value::Table(Table::new()));
+
+      let Value::Table(section) = section else {
+        continue;
+      };
+
+      let value = match (value.clone(), section.remove(key)) {
+        (Value::Table(mut value), Some(Value::Table(overlay))) => {
+          layers::merge(&mut value, overlay);
+          Value::Table(value)
+        }
+        (_, Some(overlay)) => overlay,
+        (value, None) => value,
+      };
+
+      section.insert(key.to_string(), value);
+    }
+
+    moved.push(key.to_string());
+  }
+
+  moved
+}
+
+/// Tells where a key of the flat format belongs now
+pub fn deprecation(key: &str) -> String {
+  let sections = sections(key)
+    .unwrap_or_default()
+    .iter()
+    .map(|section| format!("`[{section}]`"))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  format!("the flat config format is deprecated, move `{key}` to {sections}")
+}
@@ -0,0 +1,378 @@
+use std::{
+  collections::HashMap,
+  path::PathBuf,
+  sync::{Arc, RwLock},
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+pub use self::{check::check, schema::schema, watch::watch};
+
+mod check;
+mod layers;
+mod legacy;
+mod schema;
+mod watch;
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ConfigValues {
+  #[serde(default)]
+  pub greeter: Greeter,
+
+  #[serde(default)]
+  pub lock: Lock,
+
+  #[serde(default)]
+  pub desktop: Desktop,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Greeter {
+  /// User preselected if no previous choice was remembered
+  #[serde(default)]
+  pub user: String,
+  #[serde(default)]
+  pub primary_display: Vec<String>,
+
+  /// Custom session offered in addition to the installed ones
+  #[serde(default)]
+  pub session: Session,
+
+  #[serde(default)]
+  pub users: Users,
+
+  /// Command used to start X11 sessions, with the session command appended
+  #[serde(default = "default_xsession_wrapper")]
+  pub xsession_wrapper: Vec<String>,
+
+  #[serde(flatten)]
+  pub appearance: Appearance,
+}
+
+impl Default for Greeter {
+  fn default() -> Self {
+    Self {
+      user: String::new(),
+      primary_display: Vec::new(),
+      session: Session::default(),
+      users: Users::default(),
+      xsession_wrapper: default_xsession_wrapper(),
+      appearance: Appearance::default(),
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Lock {
+  #[serde(default)]
+  pub primary_display: Vec<String>,
+
+  #[serde(default)]
+  pub throttle: Throttle,
+
+  #[serde(default)]
+  pub pam: Pam,
+
+  #[serde(flatten)]
+  pub appearance: Appearance,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Desktop {
+  #[serde(default)]
+  pub primary_display: Vec<String>,
+
+  #[serde(default)]
+  pub idle: Idle,
+
+  /// Panels shown on every monitor
+  #[serde(default = "default_panels")]
+  pub panels: Vec<Panel>,
+
+  /// Widgets shown in the control and bars panels
+  #[serde(default = "default_widgets")]
+  pub widgets: Vec<Widget>,
+
+  /// Format of the clock in the control panel, see [`Appearance::clock_format`]
+  #[serde(default = "default_clock_format")]
+  pub clock_format: String,
+}
+
+impl Default for Desktop {
+  fn default() -> Self {
+    Self {
+      primary_display: Vec::new(),
+      idle: Idle::default(),
+      panels: default_panels(),
+      widgets: default_widgets(),
+      clock_format: default_clock_format(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Panel {
+  /// Workspace overview along the top edge
+  Workspace,
+  /// Clock and system menus along the bottom edge
+  Control,
+  /// Brightness and volume in the bottom right corner
+  Bars,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Widget {
+  Clock,
+  Calendar,
+  Wifi,
+  Bluetooth,
+  Audio,
+  Power,
+  Brightness,
+  Volume,
+}
+
+fn default_panels() -> Vec<Panel> {
+  vec![Panel::Workspace, Panel::Control, Panel::Bars]
+}
+
+fn default_widgets() -> Vec<Widget> {
+  vec![
+    Widget::Clock,
+    Widget::Calendar,
+    Widget::Wifi,
+    Widget::Bluetooth,
+    Widget::Audio,
+    Widget::Power,
+    Widget::Brightness,
+    Widget::Volume,
+  ]
+}
+
+/// Look of the greeter and lock screen, sent to their webviews
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Appearance {
+  /// Image shown behind everything else
+  #[serde(default)]
+  pub background: Option<PathBuf>,
+
+  /// Format of the clock, see <https://date-fns.org/docs/format>
+  #[serde(default = "default_clock_format")]
+  pub clock_format: String,
+}
+
+impl Default for Appearance {
+  fn default() -> Self {
+    Self {
+      background: None,
+      clock_format: default_clock_format(),
+    }
+  }
+}
+
+fn default_clock_format() -> String {
+  "p".to_string()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Session {
+  #[serde(default)]
+  pub cmd: Vec<String>,
+
+  #[serde(default)]
+  pub env: HashMap<String, String>,
+}
+
+/// Range of UIDs listed by the greeter
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Users {
+  #[serde(default = "default_min_uid")]
+  pub min_uid: u32,
+  #[serde(default = "default_max_uid")]
+  pub max_uid: u32,
+}
+
+impl Default for Users {
+  fn default() -> Self {
+    Self {
+      min_uid: default_min_uid(),
+      max_uid: default_max_uid(),
+    }
+  }
+}
+
+/// PAM setup for the lock screen
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Pam {
+  #[serde(default = "default_pam_service")]
+  pub service: String,
+
+  /// Service to use if `service` isn't installed
+  #[serde(default = "default_pam_fallback_service")]
+  pub fallback_service: Option<String>,
+
+  /// Users other than the locked one that may unlock the session. The
+  /// service has to be able to check their passwords, which pam_unix can't
+  /// as an unprivileged user. `dash2 check-pam` tries it.
+  #[serde(default)]
+  pub admin_users: Vec<String>,
+}
+
+impl Default for Pam {
+  fn default() -> Self {
+    Self {
+      service: default_pam_service(),
+      fallback_service: default_pam_fallback_service(),
+      admin_users: Vec::new(),
+    }
+  }
+}
+
+fn default_pam_service() -> String {
+  "dash2".to_string()
+}
+
+fn default_pam_fallback_service() -> Option<String> {
+  Some("login".to_string())
+}
+
+/// Automatic dimming and locking of the desktop session after inactivity.
+/// Idle inhibitors, e.g. from video players, are respected.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Idle {
+  /// Seconds of inactivity before the screen is dimmed, 0 to disable
+  #[serde(default)]
+  pub dim_after: u64,
+
+  /// Seconds of inactivity before the session is locked, 0 to disable
+  #[serde(default)]
+  pub lock_after: u64,
+
+  /// Backlight brightness while dimmed, relative to the current brightness
+  #[serde(default = "default_idle_dim_brightness")]
+  pub dim_brightness: f64,
+
+  /// Seconds after an automatic lock in which any input unlocks again
+  /// without a password
+  #[serde(default = "default_idle_grace_period")]
+  pub grace_period: u64,
+}
+
+impl Default for Idle {
+  fn default() -> Self {
+    Self {
+      dim_after: 0,
+      lock_after: 0,
+      dim_brightness: default_idle_dim_brightness(),
+      grace_period: default_idle_grace_period(),
+    }
+  }
+}
+
+fn default_idle_dim_brightness() -> f64 {
+  0.3
+}
+
+fn default_idle_grace_period() -> u64 {
+  5
+}
+
+/// Delays and limits for failed unlock attempts on the lock screen
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Throttle {
+  /// Seconds to wait after each consecutive failure. The last
+  /// entry applies to all further failures.
+  #[serde(default = "default_throttle_delays")]
+  pub delays: Vec<u64>,
+
+  /// Failures after which `on_max_attempts` kicks in, 0 to disable
+  #[serde(default)]
+  pub max_attempts: u32,
+
+  #[serde(default)]
+  pub on_max_attempts: LockoutAction,
+}
+
+impl Default for Throttle {
+  fn default() -> Self {
+    Self {
+      delays: default_throttle_delays(),
+      max_attempts: 0,
+      on_max_attempts: LockoutAction::default(),
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockoutAction {
+  /// Log an alert and notify the UI, but keep accepting attempts
+  #[default]
+  Alert,
+  /// Stop accepting any attempts until the machine is rebooted
+  RequireReboot,
+}
+
+fn default_throttle_delays() -> Vec<u64> {
+  vec![0, 0, 1, 2, 5, 10, 30]
+}
+
+fn default_min_uid() -> u32 {
+  1000
+}
+
+fn default_max_uid() -> u32 {
+  60000
+}
+
+fn default_xsession_wrapper() -> Vec<String> {
+  vec!["startx".to_string(), "/usr/bin/env".to_string()]
+}
+
+pub type Config = Arc<ConfigValues>;
+
+/// Shared handle to a configuration that can be replaced while running.
+/// Readers get a snapshot that stays valid after it was replaced.
+#[derive(Clone)]
+pub struct ConfigHandle(Arc<RwLock<Config>>);
+
+impl ConfigHandle {
+  pub fn new(config: Config) -> Self {
+    Self(Arc::new(RwLock::new(config)))
+  }
+
+  pub fn load(&self) -> Config {
+    match self.0.read() {
+      Ok(config) => config.clone(),
+      Err(poisoned) => poisoned.into_inner().clone(),
+    }
+  }
+
+  pub fn store(&self, config: Config) {
+    match self.0.write() {
+      Ok(mut current) => *current = config,
+      Err(poisoned) => *poisoned.into_inner() = config,
+    }
+  }
+}
+
+/// Loads the layered configuration, see [`layers::load_layers`]
+pub fn load(path: &Option<PathBuf>) -> Result<Config> {
+  let loaded = layers::load_layers(path.as_deref())?;
+
+  if loaded.files.is_empty() {
+    info!("No config file found, using defaults");
+  }
+
+  for file in &loaded.files {
+    info!("Using config file: {}", file.to_string_lossy());
+  }
+
+  Ok(Arc::new(loaded.values))
+}
@@ -0,0 +1,20 @@
+use schemars::{schema::RootSchema, schema_for};
+
+use super::{layers::Includes, ConfigValues};
+
+/// JSON Schema of a config file, e.g. for completion in editors
+pub fn schema() -> RootSchema {
+  let mut schema = schema_for!(ConfigValues);
+  schema.schema.metadata().title = Some("dash2 configuration".to_string());
+
+  // `include` is handled while loading and not part of the values
+  if let Some(includes) = schema_for!(Includes).schema.object {
+    schema
+      .schema
+      .object()
+      .properties
+      .extend(includes.properties);
+  }
+
+  schema
+}
@@ -0,0 +1,146 @@
+use std::{
+  ffi::CString,
+  io,
+  os::{
+    fd::{AsRawFd, FromRawFd, OwnedFd},
+    unix::ffi::OsStrExt,
+  },
+  path::PathBuf,
+  sync::Arc,
+  thread::JoinHandle,
+  time::Duration,
+};
+
+use anyhow::Result;
+use tracing::{error, info, warn};
+
+use super::{layers, Config};
+
+/// Editors often write a new file and rename it over the old one, so
+/// whole directories are watched rather than single files
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE
+  | libc::IN_MOVED_TO
+  | libc::IN_MOVED_FROM
+  | libc::IN_CREATE
+  | libc::IN_DELETE;
+
+/// Saving a file usually causes several events in a row
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Reloads the configuration whenever one of the files it was loaded from
+/// changes, and calls `on_change` with the new values. Invalid changes are
+/// logged and otherwise ignored.
+pub fn watch<F>(path: Option<PathBuf>, current: Config, on_change: F) -> Result<JoinHandle<()>>
+where
+  F: Fn(Config) + Send + 'static,
+{
+  let mut dirs = layers::load_layers(path.as_deref())?.dirs;
+  let mut inotify = Inotify::new(&dirs)?;
+
+  let thread_handle = std::thread::spawn(move || {
+    let mut current = current;
+
+    loop {
+      if let Err(err) = inotify.wait() {
+        error!("failed to watch config files: {err}");
+        return;
+      }
+
+      let loaded = match layers::load_layers(path.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+          error!("failed to reload config: {err}");
+          continue;
+        }
+      };
+
+      // Includes might have been added or removed
+      if loaded.dirs != dirs {
+        match Inotify::new(&loaded.dirs) {
+          Ok(new) => {
+            inotify = new;
+            dirs = loaded.dirs;
+          }
+          Err(err) => error!("failed to update config watches: {err}"),
+        }
+      }
+
+      if *current == loaded.values {
+        continue;
+      }
+
+      info!("config changed, reloading");
+      current = Arc::new(loaded.values);
+      on_change(current.clone());
+    }
+  });
+
+  Ok(thread_handle)
+}
+
+struct Inotify(OwnedFd);
+
+impl Inotify {
+  fn new(dirs: &[PathBuf]) -> io::Result<Self> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    let inotify = Self(unsafe { OwnedFd::from_raw_fd(fd) });
+
+    for dir in dirs {
+      let path = CString::new(dir.as_os_str().as_bytes())?;
+      let wd = unsafe { libc::inotify_add_watch(fd, path.as_ptr(), WATCH_MASK) };
+
+      if wd < 0 {
+        let err = io::Error::last_os_error();
+        warn!("failed to watch {}: {err}", dir.to_string_lossy());
+      }
+    }
+
+    Ok(inotify)
+  }
+
+  /// Blocks until something changed and no more changes follow
+  fn wait(&self) -> io::Result<()> {
+    let mut buf = [0u8; 4096];
+    self.read(&mut buf)?;
+
+    loop {
+      let mut pollfd = libc::pollfd {
+        fd: self.0.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+      };
+
+      match unsafe { libc::poll(&mut pollfd, 1, DEBOUNCE.as_millis() as i32) } {
+        0 => return Ok(()),
+        n if n < 0 => {
+          let err = io::Error::last_os_error();
+          if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+          }
+        }
+        _ => {
+          self.read(&mut buf)?;
+        }
+      }
+    }
+  }
+
+  /// The events themselves don't matter, every change causes a reload
+  fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+      let n = unsafe { libc::read(self.0.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+      if n >= 0 {
+        return Ok(n as usize);
+      }
+
+      let err = io::Error::last_os_error();
+      if err.kind() != io::ErrorKind::Interrupted {
+        return Err(err);
+      }
+    }
+  }
+}
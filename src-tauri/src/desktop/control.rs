@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use tauri::{Emitter, Manager};
+use tracing::info;
+
+use crate::ipc::{self, MonitorState, PanelState, PanelTarget, Request, Response, State};
+
+use super::{
+  panel::{self, PanelKind},
+  TauriState,
+};
+
+/// Answers requests on the control socket, e.g. from `dash2 msg`.
+pub async fn listen(app: tauri::AppHandle) -> Result<()> {
+  let path = ipc::socket_path()?;
+  let listener = ipc::bind(&path).await?;
+  info!("listening for commands on {}", path.display());
+
+  ipc::serve(listener, move |request| {
+    let app = app.clone();
+    async move {
+      handle(&app, request)
+        .await
+        .unwrap_or_else(|err| Response::Error(err.to_string()))
+    }
+  })
+  .await;
+
+  Ok(())
+}
+
+async fn handle(app: &tauri::AppHandle, request: Request) -> Result<Response> {
+  let state = app.state::<TauriState>();
+
+  match request {
+    Request::TogglePanel(target) => {
+      let window = panel_window(app, &target).await?;
+      let event = match state.panels.is_visible(window.label())? {
+        true => "leave",
+        false => "enter",
+      };
+
+      window.emit_to(window.label(), event, ())?;
+    }
+    Request::ShowPanel(target) => {
+      let window = panel_window(app, &target).await?;
+      window.emit_to(window.label(), "enter", ())?;
+    }
+    Request::HidePanel(target) => {
+      let window = panel_window(app, &target).await?;
+      window.emit_to(window.label(), "leave", ())?;
+    }
+    Request::ReloadConfig => super::reload_config(app)?,
+    Request::State => return Ok(Response::State(desktop_state(app).await?)),
+    Request::Lock => {
+      state
+        .locker
+        .lock()
+        .map_err(|_| anyhow::anyhow!("failed to lock locker"))?
+        .lock(0, None)?;
+    }
+  }
+
+  Ok(Response::Ok)
+}
+
+/// Panels show and hide themselves on pointer crossing events, so they are
+/// controlled by sending them the same events.
+async fn panel_window(
+  app: &tauri::AppHandle,
+  target: &PanelTarget,
+) -> Result<tauri::WebviewWindow> {
+  let state = app.state::<TauriState>();
+
+  let monitor = match &target.monitor {
+    Some(monitor) => monitor.clone(),
+    None => state
+      .hyprland
+      .get_monitors()
+      .await?
+      .into_iter()
+      .find(|monitor| monitor.focused)
+      .map(|monitor| monitor.name)
+      .context("no monitor is focused")?,
+  };
+
+  let kind = PanelKind::from(target.panel);
+  app
+    .get_webview_window(&panel::label(kind, &monitor))
+    .with_context(|| format!("there is no {} panel on {monitor}", kind.name()))
+}
+
+async fn desktop_state(app: &tauri::AppHandle) -> Result<State> {
+  let state = app.state::<TauriState>();
+  let hyprland_monitors = state.hyprland.get_monitors().await?;
+  let primary = super::primary_monitor(app, &hyprland_monitors)?;
+  let config = state.config.load();
+
+  let locked = state
+    .locker
+    .lock()
+    .map_err(|_| anyhow::anyhow!("failed to lock locker"))?
+    .is_locked();
+
+  let monitors = hyprland_monitors
+    .into_iter()
+    .map(|monitor| {
+      let panels = config
+        .desktop
+        .panels
+        .iter()
+        .filter_map(|&panel| {
+          let label = panel::label(PanelKind::from(panel), &monitor.name);
+          let visible = state.panels.is_visible(&label).ok()?;
+          Some(PanelState { panel, visible })
+        })
+        .collect();
+
+      MonitorState {
+        primary: primary.as_ref() == Some(&monitor.name),
+        focused: monitor.focused,
+        name: monitor.name,
+        panels,
+      }
+    })
+    .collect();
+
+  Ok(State { locked, monitors })
+}
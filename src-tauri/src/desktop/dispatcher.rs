@@ -0,0 +1,152 @@
+use serde::Deserialize;
+
+use crate::hyprland::{
+  dispatch::{
+    DispatchType, FullscreenType, MonitorIdentifier, WindowIdentifier,
+    WorkspaceIdentifierWithSpecial,
+  },
+  types::{Address, WorkspaceId},
+};
+
+/// Dispatchers the desktop frontends may run. Anything that can start
+/// programs or change hyprland's setup, like `exec` or `exit`, is left out.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Dispatcher {
+  Workspace {
+    id: WorkspaceId,
+  },
+  /// Moves by `offset` through the workspaces of the focused monitor
+  RelativeWorkspace {
+    offset: i32,
+  },
+  PreviousWorkspace,
+  ToggleSpecialWorkspace {
+    #[serde(default)]
+    workspace: Option<String>,
+  },
+  FocusMonitor {
+    monitor: String,
+  },
+  FocusWindow {
+    address: Address,
+  },
+  CloseWindow {
+    address: Address,
+  },
+  /// Moves a window, the focused one if no address is given
+  MoveToWorkspace {
+    id: WorkspaceId,
+    #[serde(default)]
+    address: Option<Address>,
+    #[serde(default)]
+    silent: bool,
+  },
+  ToggleFloating {
+    #[serde(default)]
+    address: Option<Address>,
+  },
+  ToggleFullscreen,
+  KillActiveWindow,
+}
+
+impl Dispatcher {
+  pub fn to_dispatch_type(&self) -> DispatchType<'_> {
+    use WorkspaceIdentifierWithSpecial::{Id, Previous, RelativeMonitor};
+
+    match self {
+      Self::Workspace { id } => DispatchType::Workspace(Id(*id)),
+      Self::RelativeWorkspace { offset } => DispatchType::Workspace(RelativeMonitor(*offset)),
+      Self::PreviousWorkspace => DispatchType::Workspace(Previous),
+      Self::ToggleSpecialWorkspace { workspace } => {
+        DispatchType::ToggleSpecialWorkspace(workspace.clone())
+      }
+      Self::FocusMonitor { monitor } => {
+        DispatchType::FocusMonitor(MonitorIdentifier::Name(monitor))
+      }
+      Self::FocusWindow { address } => {
+        DispatchType::FocusWindow(WindowIdentifier::Address(address.clone()))
+      }
+      Self::CloseWindow { address } => {
+        DispatchType::CloseWindow(WindowIdentifier::Address(address.clone()))
+      }
+      Self::MoveToWorkspace {
+        id,
+        address,
+        silent,
+      } => {
+        let window = address.clone().map(WindowIdentifier::Address);
+        if *silent {
+          DispatchType::MoveToWorkspaceSilent(Id(*id), window)
+        } else {
+          DispatchType::MoveToWorkspace(Id(*id), window)
+        }
+      }
+      Self::ToggleFloating { address } => {
+        DispatchType::ToggleFloating(address.clone().map(WindowIdentifier::Address))
+      }
+      Self::ToggleFullscreen => DispatchType::ToggleFullscreen(FullscreenType::Real),
+      Self::KillActiveWindow => DispatchType::KillActiveWindow,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::hyprland::dispatch::gen_dispatch_str;
+
+  fn dispatch_str(value: serde_json::Value) -> String {
+    let dispatcher: Dispatcher = serde_json::from_value(value).unwrap();
+    gen_dispatch_str(dispatcher.to_dispatch_type(), true)
+      .unwrap()
+      .data
+  }
+
+  #[test]
+  fn generates_dispatch_strings() {
+    assert_eq!(
+      dispatch_str(json!({ "name": "workspace", "id": 3 })),
+      "dispatch workspace 3"
+    );
+    assert_eq!(
+      dispatch_str(json!({ "name": "relative_workspace", "offset": -1 })),
+      "dispatch workspace m-1"
+    );
+    assert_eq!(
+      dispatch_str(json!({ "name": "toggle_special_workspace", "workspace": "scratch" })),
+      "dispatch togglespecialworkspace scratch"
+    );
+    assert_eq!(
+      dispatch_str(json!({ "name": "focus_monitor", "monitor": "DP-1" })),
+      "dispatch focusmonitor DP-1"
+    );
+    assert_eq!(
+      dispatch_str(json!({
+        "name": "move_to_workspace",
+        "id": 2,
+        "address": "0x5678",
+        "silent": true,
+      })),
+      "dispatch movetoworkspacesilent 2,address:0x5678"
+    );
+    assert_eq!(
+      dispatch_str(json!({ "name": "kill_active_window" })),
+      "dispatch killactive"
+    );
+  }
+
+  #[test]
+  fn rejects_other_dispatchers() {
+    for value in [
+      json!({ "name": "exec", "args": "rm -rf ~" }),
+      json!({ "name": "custom", "args": "exec foot" }),
+      json!({ "name": "exit" }),
+      json!({ "name": "workspace", "id": 1, "args": "; exec foot" }),
+    ] {
+      assert!(serde_json::from_value::<Dispatcher>(value).is_err());
+    }
+  }
+}
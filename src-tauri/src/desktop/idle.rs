@@ -0,0 +1,471 @@
+use std::{
+  sync::{Arc, Mutex},
+  thread::JoinHandle,
+};
+
+use anyhow::Result;
+use gdk::{glib::translate::ToGlibPtr, prelude::*};
+use smithay_client_toolkit::reexports::protocols::ext::idle_notify::v1::client::{
+  ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+  ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+use wayland_backend::client::Backend;
+use wayland_client::{
+  globals::{registry_queue_init, GlobalListContents},
+  protocol::{wl_registry, wl_seat::WlSeat},
+  Connection, Dispatch, QueueHandle,
+};
+
+use crate::{
+  config::{Config, ConfigHandle},
+  power::Power,
+};
+
+use super::locker::Locker;
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Timeout {
+  Dim,
+  Lock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdleEvent {
+  Idled(Timeout),
+  Resumed(Timeout),
+}
+
+struct State {
+  events: UnboundedSender<IdleEvent>,
+  running: bool,
+}
+
+/// What going idle does to the session, split out so the state machine in
+/// `handle_events` can be driven without backlights or a lock screen
+trait Session {
+  /// Dims every backlight and returns their previous brightness
+  async fn dim(&self, factor: f64) -> Vec<(String, u32)>;
+  async fn set_brightness(&self, name: &str, brightness: u32);
+  fn is_locked(&self) -> bool;
+  fn lock(&self, grace_period: u64);
+}
+
+struct DesktopSession {
+  locker: Arc<Mutex<Locker>>,
+  power: Power,
+}
+
+impl Session for DesktopSession {
+  async fn dim(&self, factor: f64) -> Vec<(String, u32)> {
+    dim(&self.power, factor).await
+  }
+
+  async fn set_brightness(&self, name: &str, brightness: u32) {
+    self
+      .power
+      .set_brightness(name, brightness)
+      .await
+      .unwrap_or_else(|err| {
+        error!("failed to restore brightness of {name}: {err}");
+      });
+  }
+
+  fn is_locked(&self) -> bool {
+    match self.locker.lock() {
+      Ok(mut locker) => locker.is_locked(),
+      Err(_) => {
+        error!("failed to lock locker");
+        // Don't try locking with a broken locker
+        true
+      }
+    }
+  }
+
+  fn lock(&self, grace_period: u64) {
+    let Ok(mut locker) = self.locker.lock() else {
+      error!("failed to lock locker");
+      return;
+    };
+
+    locker.lock(grace_period, None).unwrap_or_else(|err| {
+      error!("failed to lock session: {err}");
+    });
+  }
+}
+
+/// Dims and locks the session after the configured periods of inactivity.
+/// Does nothing if both timeouts are disabled. The timeouts are fixed at
+/// startup, the other idle settings follow config reloads.
+pub fn start(config: ConfigHandle, locker: Arc<Mutex<Locker>>, power: Power) -> Result<()> {
+  let initial = config.load();
+  if initial.desktop.idle.dim_after == 0 && initial.desktop.idle.lock_after == 0 {
+    return Ok(());
+  }
+
+  let (events_tx, events_rx) = mpsc::unbounded_channel();
+  watch_idle(&initial, events_tx)?;
+  tokio::spawn(handle_events(
+    config,
+    DesktopSession { locker, power },
+    events_rx,
+  ));
+
+  Ok(())
+}
+
+/// Listens for ext_idle_notify_v1 notifications on the gdk connection.
+/// Notifications from version 1 of the protocol are held back by the
+/// compositor while an idle inhibitor is active.
+fn watch_idle(config: &Config, events: UnboundedSender<IdleEvent>) -> Result<JoinHandle<()>> {
+  let display = gdk::Display::default().ok_or(anyhow::anyhow!("failed to get default display"))?;
+  let wl_display = display
+    .downcast_ref::<gdkwayland::WaylandDisplay>()
+    .ok_or(anyhow::anyhow!("display was not a wayland display"))?;
+  let wl_display =
+    unsafe { gdk_wayland_sys::gdk_wayland_display_get_wl_display(wl_display.to_glib_none().0) };
+  let wl_backend = unsafe { Backend::from_foreign_display(wl_display as *mut _) };
+  let wl_conn = Connection::from_backend(wl_backend);
+  let (globals, mut event_queue) = registry_queue_init::<State>(&wl_conn)?;
+
+  let qh = event_queue.handle();
+  let seat: WlSeat = globals.bind(&qh, 1..=1, ())?;
+  let notifier: ExtIdleNotifierV1 = globals
+    .bind(&qh, 1..=1, ())
+    .map_err(|_| anyhow::anyhow!("compositor does not support ext_idle_notify_v1"))?;
+
+  let notifications = [
+    (Timeout::Dim, config.desktop.idle.dim_after),
+    (Timeout::Lock, config.desktop.idle.lock_after),
+  ]
+  .into_iter()
+  .filter(|(_, seconds)| *seconds > 0)
+  .map(|(timeout, seconds)| {
+    let millis = u32::try_from(seconds * 1000).unwrap_or(u32::MAX);
+    notifier.get_idle_notification(millis, &seat, &qh, timeout)
+  })
+  .collect::<Vec<_>>();
+
+  let thread_handle = std::thread::spawn(move || {
+    let mut state = State {
+      events,
+      running: true,
+    };
+
+    while state.running {
+      if let Err(err) = event_queue.blocking_dispatch(&mut state) {
+        error!("failed to dispatch idle events: {err}");
+        break;
+      }
+    }
+
+    for notification in notifications {
+      notification.destroy();
+    }
+  });
+
+  Ok(thread_handle)
+}
+
+async fn handle_events(
+  config: ConfigHandle,
+  session: impl Session,
+  mut events: UnboundedReceiver<IdleEvent>,
+) {
+  // Brightness of each backlight before dimming
+  let mut dimmed = Vec::new();
+
+  while let Some(event) = events.recv().await {
+    match event {
+      IdleEvent::Idled(Timeout::Dim) => {
+        // Dimming twice would restore the dimmed brightness later
+        if dimmed.is_empty() {
+          dimmed = session.dim(config.load().desktop.idle.dim_brightness).await;
+        }
+      }
+      IdleEvent::Resumed(Timeout::Dim) => {
+        for (name, brightness) in std::mem::take(&mut dimmed) {
+          session.set_brightness(&name, brightness).await;
+        }
+      }
+      IdleEvent::Idled(Timeout::Lock) => {
+        // The user might have been idle on the lock screen itself
+        if session.is_locked() {
+          continue;
+        }
+
+        info!("locking session after inactivity");
+        session.lock(config.load().desktop.idle.grace_period);
+      }
+      IdleEvent::Resumed(Timeout::Lock) => {}
+    }
+  }
+}
+
+/// Dims every backlight and returns their previous brightness.
+async fn dim(power: &Power, factor: f64) -> Vec<(String, u32)> {
+  let backlights = match read_backlights() {
+    Ok(backlights) => backlights,
+    Err(err) => {
+      error!("failed to read backlights: {err}");
+      return Vec::new();
+    }
+  };
+
+  let mut previous = Vec::new();
+  for (name, brightness) in backlights {
+    // Some panels turn off entirely at zero
+    let value = ((brightness as f64 * factor).round() as u32)
+      .max(1)
+      .min(brightness);
+
+    match power.set_brightness(&name, value).await {
+      Ok(()) => previous.push((name, brightness)),
+      Err(err) => error!("failed to dim {name}: {err}"),
+    }
+  }
+
+  previous
+}
+
+fn read_backlights() -> Result<Vec<(String, u32)>> {
+  let mut backlights = Vec::new();
+  for entry in std::fs::read_dir(BACKLIGHT_DIR)? {
+    let entry = entry?;
+    let brightness = std::fs::read_to_string(entry.path().join("brightness"))?;
+    backlights.push((
+      entry.file_name().to_string_lossy().into_owned(),
+      brightness.trim().parse()?,
+    ));
+  }
+
+  Ok(backlights)
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+  fn event(
+    _: &mut Self,
+    _: &wl_registry::WlRegistry,
+    _: wl_registry::Event,
+    _: &GlobalListContents,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+  }
+}
+
+impl Dispatch<ExtIdleNotificationV1, Timeout> for State {
+  fn event(
+    state: &mut Self,
+    _: &ExtIdleNotificationV1,
+    event: ext_idle_notification_v1::Event,
+    timeout: &Timeout,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    let event = match event {
+      ext_idle_notification_v1::Event::Idled => IdleEvent::Idled(*timeout),
+      ext_idle_notification_v1::Event::Resumed => IdleEvent::Resumed(*timeout),
+      _ => return,
+    };
+
+    if state.events.send(event).is_err() {
+      state.running = false;
+    }
+  }
+}
+
+wayland_client::delegate_noop!(State: ignore WlSeat);
+wayland_client::delegate_noop!(State: ExtIdleNotifierV1);
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+
+  /// Backlights and a lock screen that only exist in memory
+  #[derive(Default)]
+  struct FakeSession {
+    brightness: Mutex<HashMap<String, u32>>,
+    locked: Mutex<bool>,
+    /// Grace period of every lock
+    locks: Mutex<Vec<u64>>,
+  }
+
+  impl FakeSession {
+    fn with_backlights(backlights: &[(&str, u32)]) -> Self {
+      let brightness = backlights
+        .iter()
+        .map(|(name, brightness)| (name.to_string(), *brightness))
+        .collect();
+
+      Self {
+        brightness: Mutex::new(brightness),
+        ..Default::default()
+      }
+    }
+
+    fn brightness(&self, name: &str) -> u32 {
+      self.brightness.lock().unwrap()[name]
+    }
+  }
+
+  impl Session for &FakeSession {
+    async fn dim(&self, factor: f64) -> Vec<(String, u32)> {
+      let mut brightness = self.brightness.lock().unwrap();
+      let previous = brightness
+        .iter()
+        .map(|(name, brightness)| (name.clone(), *brightness))
+        .collect();
+
+      for value in brightness.values_mut() {
+        *value = (*value as f64 * factor) as u32;
+      }
+
+      previous
+    }
+
+    async fn set_brightness(&self, name: &str, brightness: u32) {
+      self
+        .brightness
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), brightness);
+    }
+
+    fn is_locked(&self) -> bool {
+      *self.locked.lock().unwrap()
+    }
+
+    fn lock(&self, grace_period: u64) {
+      *self.locked.lock().unwrap() = true;
+      self.locks.lock().unwrap().push(grace_period);
+    }
+  }
+
+  fn config(dim_brightness: f64, grace_period: u64) -> ConfigHandle {
+    let mut config = Config::default();
+    config.desktop.idle.dim_brightness = dim_brightness;
+    config.desktop.idle.grace_period = grace_period;
+    ConfigHandle::new(config)
+  }
+
+  /// Runs the state machine over the events until they run out
+  async fn handle(config: &ConfigHandle, session: &FakeSession, events: &[IdleEvent]) {
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    for event in events {
+      events_tx.send(*event).unwrap();
+    }
+
+    drop(events_tx);
+    handle_events(config.clone(), session, events_rx).await;
+  }
+
+  #[tokio::test]
+  async fn dims_backlights() {
+    let config = config(0.5, 0);
+    let session = FakeSession::with_backlights(&[("intel_backlight", 800), ("ddcci1", 60)]);
+
+    handle(&config, &session, &[IdleEvent::Idled(Timeout::Dim)]).await;
+
+    assert_eq!(session.brightness("intel_backlight"), 400);
+    assert_eq!(session.brightness("ddcci1"), 30);
+  }
+
+  #[tokio::test]
+  async fn restores_the_brightness_from_before_dimming() {
+    let config = config(0.5, 0);
+    let session = FakeSession::with_backlights(&[("intel_backlight", 800)]);
+
+    handle(
+      &config,
+      &session,
+      &[
+        IdleEvent::Idled(Timeout::Dim),
+        IdleEvent::Idled(Timeout::Dim),
+        IdleEvent::Resumed(Timeout::Dim),
+      ],
+    )
+    .await;
+
+    assert_eq!(session.brightness("intel_backlight"), 800);
+  }
+
+  #[tokio::test]
+  async fn resuming_without_dimming_keeps_the_brightness() {
+    let config = config(0.5, 0);
+    let session = FakeSession::with_backlights(&[("intel_backlight", 800)]);
+
+    handle(&config, &session, &[IdleEvent::Resumed(Timeout::Dim)]).await;
+
+    assert_eq!(session.brightness("intel_backlight"), 800);
+  }
+
+  #[tokio::test]
+  async fn locks_with_the_grace_period() {
+    let config = config(0.5, 30);
+    let session = FakeSession::default();
+
+    handle(
+      &config,
+      &session,
+      &[
+        IdleEvent::Idled(Timeout::Dim),
+        IdleEvent::Idled(Timeout::Lock),
+        IdleEvent::Resumed(Timeout::Lock),
+        IdleEvent::Resumed(Timeout::Dim),
+      ],
+    )
+    .await;
+
+    assert_eq!(*session.locks.lock().unwrap(), [30]);
+    assert!(*session.locked.lock().unwrap());
+  }
+
+  #[tokio::test]
+  async fn follows_config_reloads() {
+    let config = config(0.5, 30);
+    let session = FakeSession::with_backlights(&[("intel_backlight", 800)]);
+
+    let mut reloaded = config.load();
+    reloaded.desktop.idle.dim_brightness = 0.25;
+    reloaded.desktop.idle.grace_period = 5;
+    config.store(reloaded);
+
+    handle(
+      &config,
+      &session,
+      &[
+        IdleEvent::Idled(Timeout::Dim),
+        IdleEvent::Idled(Timeout::Lock),
+      ],
+    )
+    .await;
+
+    assert_eq!(session.brightness("intel_backlight"), 200);
+    assert_eq!(*session.locks.lock().unwrap(), [5]);
+  }
+
+  #[tokio::test]
+  async fn does_not_lock_twice() {
+    let config = config(0.5, 0);
+    let session = FakeSession::default();
+
+    handle(
+      &config,
+      &session,
+      &[
+        IdleEvent::Idled(Timeout::Lock),
+        IdleEvent::Resumed(Timeout::Lock),
+        IdleEvent::Idled(Timeout::Lock),
+      ],
+    )
+    .await;
+
+    assert_eq!(*session.locks.lock().unwrap(), [0]);
+  }
+}
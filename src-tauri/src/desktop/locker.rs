@@ -0,0 +1,65 @@
+use std::{
+  io,
+  os::fd::{AsRawFd, OwnedFd},
+  path::PathBuf,
+};
+
+use anyhow::Result;
+use tokio::process::Child;
+
+/// Starts the lock screen as a separate process, at most one at a time.
+pub struct Locker {
+  config_path: Option<PathBuf>,
+  child: Option<Child>,
+}
+
+impl Locker {
+  pub fn new(config_path: Option<PathBuf>) -> Self {
+    Self {
+      config_path,
+      child: None,
+    }
+  }
+
+  pub fn is_locked(&mut self) -> bool {
+    self
+      .child
+      .as_mut()
+      .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+  }
+
+  /// Starts the lock screen unless it is already running. The sleep
+  /// inhibitor is handed to the lock screen, which releases it once the
+  /// session is locked.
+  pub fn lock(&mut self, grace_period: u64, inhibitor: Option<OwnedFd>) -> Result<()> {
+    if self.is_locked() {
+      return Ok(());
+    }
+
+    let mut command = tokio::process::Command::new(std::env::current_exe()?);
+    command
+      .args(&self.config_path)
+      .arg("lock")
+      .arg("--grace")
+      .arg(grace_period.to_string());
+
+    if let Some(inhibitor) = &inhibitor {
+      let fd = inhibitor.as_raw_fd();
+      command.arg("--inhibitor-fd").arg(fd.to_string());
+
+      // Descriptors are closed on exec unless we clear the flag
+      unsafe {
+        command.pre_exec(move || {
+          if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+            return Err(io::Error::last_os_error());
+          }
+
+          Ok(())
+        });
+      }
+    }
+
+    self.child = Some(command.spawn()?);
+    Ok(())
+  }
+}
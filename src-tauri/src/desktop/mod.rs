@@ -1,9 +1,23 @@
+use std::{
+  collections::HashMap,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
 use crate::{
   battery::BatterySubscription,
-  config::Config,
-  hyprland::{events::Event, HyprlandClient, HyprlandListener},
+  config::{self, Config, ConfigHandle},
+  hyprland::{
+    data,
+    dispatch::{DispatchType, WorkspaceIdentifierWithSpecial},
+    events::Event,
+    types::WorkspaceId,
+    HyprlandClient, HyprlandListener,
+  },
   layer_shell::LayerShellWindowBuilder,
   power::Power,
+  primary::{resolve_primary, OutputInfo},
+  util::get_hyprland_monitor,
 };
 use anyhow::Result;
 use futures::StreamExt;
@@ -21,14 +35,34 @@ use gtk::prelude::*;
 use tauri::{Emitter, Manager};
 use tracing::error;
 
+use self::{
+  dispatcher::Dispatcher,
+  locker::Locker,
+  panel::{PanelKind, Panels},
+  workspaces::{MonitorWorkspaces, Workspaces},
+};
+
+mod control;
+mod dispatcher;
+mod idle;
+mod locker;
+mod panel;
+mod sleep;
+mod workspaces;
+
 const NAMESPACE: &str = "dash2-desktop";
 
 struct TauriState<'a> {
-  config: Config,
+  config: ConfigHandle,
+  config_path: Option<PathBuf>,
+  locker: Arc<Mutex<Locker>>,
   battery: BatterySubscription<'a>,
   hyprland: HyprlandClient,
   power: Power,
-  desktop_windows: Vec<tauri::WebviewWindow>,
+  /// Desktop windows keyed by the connector name of their monitor
+  desktop_windows: Mutex<HashMap<String, tauri::WebviewWindow>>,
+  panels: Panels,
+  workspaces: Workspaces,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -39,54 +73,137 @@ struct InputRegion {
   height: i32,
 }
 
-pub fn run(config: Config) -> Result<()> {
+pub fn run(config: Config, config_path: Option<PathBuf>) -> Result<()> {
   let rt = tokio::runtime::Runtime::new()?;
   rt.block_on(async {
     tauri::async_runtime::set(tokio::runtime::Handle::current());
 
     let app = tauri::Builder::default()
       .invoke_handler(tauri::generate_handler![
+        get_config,
         log,
         window_ready,
         request_input_regions,
+        request_height,
+        request_width,
+        hide_panel,
+        get_workspaces,
+        set_active_workspace,
+        dispatch,
+        dispatch_many,
       ])
       .build(tauri::generate_context!())?;
 
     let zbus_conn = zbus::Connection::system().await?;
     let battery = BatterySubscription::new(app.handle(), &zbus_conn).await?;
-    let power = Power::new(zbus_conn);
+    let power = Power::new(zbus_conn.clone());
+
+    let config_handle = ConfigHandle::new(config.clone());
+
+    let locker = Arc::new(Mutex::new(Locker::new(config_path.clone())));
+    idle::start(
+      config_handle.clone(),
+      locker.clone(),
+      Power::new(zbus_conn.clone()),
+    )
+    .unwrap_or_else(|err| {
+      error!("failed to start idle service: {err}");
+    });
 
+    let sleep_locker = locker.clone();
     tokio::spawn(async move {
-      let event_listener = HyprlandListener::new().await.unwrap();
-      let mut stream = event_listener.listen().await.unwrap();
-
-      while let Some(event) = stream.next().await {
-        match event {
-          Event::Workspace(workspace) => {
-            println!("workspace changed: {workspace:?}")
-          }
-          event => println!("event: {event:?}"),
-        }
-      }
+      sleep::watch(&zbus_conn, sleep_locker)
+        .await
+        .unwrap_or_else(|err| {
+          error!("failed to watch for sleep: {err}");
+        });
     });
 
     let hyprland_client = HyprlandClient::new().await?;
+    let hyprland_monitors = hyprland_client.get_monitors().await?;
 
-    // TODO: Handle monitor changes
-    let display = gdk::Display::default().unwrap();
-    let mut desktop_windows = Vec::with_capacity(display.n_monitors() as usize);
-
-    for n in 0..display.n_monitors() {
-      let monitor = display.monitor(n).unwrap();
-      desktop_windows.push(create_monitor_window(app.handle(), n, &monitor)?);
-    }
+    let workspaces = Workspaces::default();
+    workspaces.sync(&hyprland_client).await?;
 
     app.manage(TauriState {
-      config: config.clone(),
+      config: config_handle,
+      config_path: config_path.clone(),
+      locker,
       hyprland: hyprland_client,
       battery,
       power,
-      desktop_windows,
+      desktop_windows: Mutex::new(HashMap::new()),
+      panels: Panels::default(),
+      workspaces,
+    });
+
+    update_monitor_windows(app.handle(), &hyprland_monitors)?;
+
+    let app_handle = app.handle().clone();
+    config::watch(config_path, config, move |config| {
+      apply_config(&app_handle, config);
+    })
+    .unwrap_or_else(|err| {
+      error!("failed to watch config files: {err}");
+    });
+
+    let app_handle = app.handle().clone();
+    tokio::spawn(async move {
+      control::listen(app_handle).await.unwrap_or_else(|err| {
+        error!("failed to listen for commands: {err}");
+      });
+    });
+
+    let display = gdk::Display::default().unwrap();
+
+    let app_handle = app.handle().clone();
+    display.connect_monitor_added(move |_, _| {
+      let app_handle = app_handle.clone();
+      tokio::spawn(async move { sync_monitors(&app_handle).await });
+    });
+
+    let app_handle = app.handle().clone();
+    display.connect_monitor_removed(move |_, _| {
+      let app_handle = app_handle.clone();
+      tokio::spawn(async move { sync_monitors(&app_handle).await });
+    });
+
+    let app_handle = app.handle().clone();
+    tokio::spawn(async move {
+      let event_listener = match HyprlandListener::new().await {
+        Ok(listener) => listener,
+        Err(err) => {
+          error!("failed to create hyprland listener: {err}");
+          return;
+        }
+      };
+
+      let mut stream = match event_listener.listen().await {
+        Ok(stream) => stream,
+        Err(err) => {
+          error!("failed to listen to hyprland events: {err}");
+          return;
+        }
+      };
+
+      while let Some(event) = stream.next().await {
+        let state = app_handle.state::<TauriState>();
+
+        match event {
+          Event::Resync => {
+            match state.workspaces.sync(&state.hyprland).await {
+              Ok(_) => state.workspaces.emit_all(&app_handle).await,
+              Err(err) => error!("failed to resync workspaces: {err}"),
+            }
+
+            sync_monitors(&app_handle).await;
+          }
+          Event::MonitorAdded(_) | Event::MonitorRemoved(_) => {
+            sync_monitors(&app_handle).await;
+          }
+          event => state.workspaces.handle_event(&app_handle, &event).await,
+        }
+      }
     });
 
     app.run(|_, _| {});
@@ -95,47 +212,190 @@ pub fn run(config: Config) -> Result<()> {
   })
 }
 
+/// Switches to a newly loaded configuration and applies it to the panels
+/// and windows.
+fn apply_config(app: &tauri::AppHandle, config: Config) {
+  let state = app.state::<TauriState>();
+  state.config.store(config.clone());
+
+  app
+    .emit("config-changed", &config.desktop)
+    .unwrap_or_else(|err| {
+      error!("failed to emit config-changed: {err}");
+    });
+
+  // Applies the panel layout and primary display
+  let app_handle = app.clone();
+  tauri::async_runtime::spawn(async move {
+    sync_monitors(&app_handle).await;
+  });
+}
+
+/// Loads the configuration again without waiting for a file to change.
+fn reload_config(app: &tauri::AppHandle) -> Result<()> {
+  let state = app.state::<TauriState>();
+  let config = config::load(&state.config_path)?;
+  apply_config(app, config);
+
+  Ok(())
+}
+
 async fn assign_primary(app: &tauri::AppHandle) -> Result<()> {
   let state = app.state::<TauriState>();
   let hyprland_monitors = state.hyprland.get_monitors().await?;
+  let primary = primary_monitor(app, &hyprland_monitors)?;
+
+  let desktop_windows = state
+    .desktop_windows
+    .lock()
+    .map_err(|_| anyhow::anyhow!("failed to lock desktop windows"))?
+    .clone();
+
+  for (name, window) in desktop_windows.iter() {
+    window
+      .emit_to(window.label(), "is-primary", Some(name) == primary.as_ref())
+      .unwrap_or_else(|err| {
+        error!("failed to emit is-primary: {err}");
+      });
+  }
+
+  Ok(())
+}
+
+/// Resolves the configured primary display among the monitors that have a
+/// desktop window and returns its connector name.
+fn primary_monitor(
+  app: &tauri::AppHandle,
+  hyprland_monitors: &[data::Monitor],
+) -> Result<Option<String>> {
+  let state = app.state::<TauriState>();
+  let desktop_windows = state
+    .desktop_windows
+    .lock()
+    .map_err(|_| anyhow::anyhow!("failed to lock desktop windows"))?;
+
+  let outputs = hyprland_monitors
+    .iter()
+    .filter(|monitor| desktop_windows.contains_key(&monitor.name))
+    .collect::<Vec<_>>();
+
+  let primary = resolve_primary(
+    &state.config.load().desktop.primary_display,
+    &outputs.iter().map(|m| OutputInfo::from(*m)).collect::<Vec<_>>(),
+  )
+  .map(|index| outputs[index].name.clone());
+
+  Ok(primary)
+}
+
+/// Brings the desktop and panel windows in line with the connected monitors
+/// and reassigns the primary monitor afterwards.
+async fn sync_monitors(app: &tauri::AppHandle) {
+  let state = app.state::<TauriState>();
+  let hyprland_monitors = match state.hyprland.get_monitors().await {
+    Ok(monitors) => monitors,
+    Err(err) => {
+      error!("failed to get monitors: {err}");
+      return;
+    }
+  };
 
   let app_handle = app.clone();
-  app
-    .run_on_main_thread(move || {
-      let state = app_handle.state::<TauriState>();
-      let primary_index = state
-        .config
-        .primary_display
-        .iter()
-        .find_map(|name| {
-          hyprland_monitors
-            .iter()
-            .position(|monitor| &monitor.name == name)
-        })
-        .unwrap_or(0);
-
-      let display = gdk::Display::default().unwrap();
-      for n in 0..display.n_monitors() {
-        let window = state.desktop_windows[n as usize].clone();
-
-        if n == primary_index as i32 {
-          window.emit_to(window.label(), "is-primary", true).unwrap();
-        } else {
-          window.emit_to(window.label(), "is-primary", false).unwrap();
-        }
-      }
+  let (tx, rx) = tokio::sync::oneshot::channel();
+  let result = app.run_on_main_thread(move || {
+    tx.send(update_monitor_windows(&app_handle, &hyprland_monitors))
+      .unwrap_or_else(|_| error!("failed to send monitor update result"));
+  });
+
+  if let Err(err) = result {
+    error!("failed to update monitor windows: {err}");
+    return;
+  }
+
+  match rx.await {
+    Ok(Ok(_)) => {}
+    Ok(Err(err)) => error!("failed to update monitor windows: {err}"),
+    Err(err) => error!("failed to update monitor windows: {err}"),
+  }
+
+  assign_primary(app).await.unwrap_or_else(|err| {
+    error!("failed to assign primary display: {err}");
+  });
+}
+
+/// Creates windows for monitors that don't have any yet and closes the windows
+/// of monitors that are gone. Panels follow the configured layout. Must be
+/// called on the main thread.
+fn update_monitor_windows(
+  app: &tauri::AppHandle,
+  hyprland_monitors: &[data::Monitor],
+) -> Result<()> {
+  let state = app.state::<TauriState>();
+  let display = gdk::Display::default().ok_or(anyhow::anyhow!("failed to get display"))?;
+
+  let monitors = (0..display.n_monitors())
+    .filter_map(|n| display.monitor(n))
+    .filter_map(|monitor| {
+      let name = get_hyprland_monitor(&monitor, hyprland_monitors)?.name.clone();
+      Some((name, monitor))
     })
-    .unwrap();
+    .collect::<HashMap<_, _>>();
+
+  let mut desktop_windows = state
+    .desktop_windows
+    .lock()
+    .map_err(|_| anyhow::anyhow!("failed to lock desktop windows"))?;
+
+  desktop_windows.retain(|name, window| {
+    if monitors.contains_key(name) {
+      return true;
+    }
+
+    window.close().unwrap_or_else(|err| {
+      error!("failed to close desktop window: {err}");
+    });
+    state.panels.close_monitor(app, name);
+
+    false
+  });
+
+  let enabled_panels = state
+    .config
+    .load()
+    .desktop
+    .panels
+    .iter()
+    .map(|&panel| PanelKind::from(panel))
+    .collect::<Vec<_>>();
+
+  for (name, monitor) in monitors {
+    state
+      .panels
+      .sync_monitor(app, &enabled_panels, &name, &monitor)?;
+
+    if desktop_windows.contains_key(&name) {
+      continue;
+    }
+
+    let window = create_monitor_window(app, &name, &monitor)?;
+
+    #[cfg(debug_assertions)]
+    if desktop_windows.is_empty() {
+      window.open_devtools();
+    }
+
+    desktop_windows.insert(name, window);
+  }
 
   Ok(())
 }
 
 fn create_monitor_window(
   app: &tauri::AppHandle,
-  index: i32,
+  name: &str,
   monitor: &Monitor,
 ) -> Result<tauri::WebviewWindow> {
-  let label = format!("{NAMESPACE}-{index}");
+  let label = format!("{NAMESPACE}-{name}");
   let window = LayerShellWindowBuilder::new(&label, "src/desktop/index.html")
     .layer(gtk_layer_shell::Layer::Top)
     .monitor(monitor)
@@ -146,18 +406,21 @@ fn create_monitor_window(
     .background_color(0., 0., 0., 0.)
     .build(app)?;
 
-  if index == 0 {
-    #[cfg(debug_assertions)]
-    window.open_devtools();
-  }
-
   let gtk_window = window.gtk_window()?;
   let empty_region = Region::create_rectangle(&RectangleInt::new(0, 0, 0, 0));
   gtk_window.input_shape_combine_region(Some(&empty_region));
 
+  connect_crossing_events(&window)?;
+
+  Ok(window)
+}
+
+/// Forwards pointer enter and leave events on the window to its webview.
+fn connect_crossing_events(window: &tauri::WebviewWindow) -> Result<()> {
+  let gtk_window = window.gtk_window()?;
+
   let window_handle = window.clone();
   gtk_window.connect_enter_notify_event(move |_, event| {
-    println!("enter at {:?}", event.position());
     window_handle
       .emit_to(window_handle.label(), "enter", event.position())
       .unwrap_or_else(|err| {
@@ -169,7 +432,6 @@ fn create_monitor_window(
 
   let window_handle = window.clone();
   gtk_window.connect_leave_notify_event(move |_, event| {
-    println!("leave at {:?}", event.position());
     window_handle
       .emit_to(window_handle.label(), "leave", event.position())
       .unwrap_or_else(|err| {
@@ -179,7 +441,7 @@ fn create_monitor_window(
     gdk::glib::Propagation::Stop
   });
 
-  Ok(window)
+  Ok(())
 }
 
 #[tauri::command]
@@ -228,6 +490,36 @@ async fn request_input_regions(window: tauri::WebviewWindow, regions: Vec<InputR
   // }
 }
 
+#[tauri::command]
+async fn request_height(app: tauri::AppHandle, window: tauri::WebviewWindow, height: i32) {
+  let state = app.state::<TauriState>();
+  state
+    .panels
+    .request_height(&window, height)
+    .unwrap_or_else(|err| {
+      error!("failed to request panel height: {err}");
+    });
+}
+
+#[tauri::command]
+async fn request_width(app: tauri::AppHandle, window: tauri::WebviewWindow, width: i32) {
+  let state = app.state::<TauriState>();
+  state
+    .panels
+    .request_width(&window, width)
+    .unwrap_or_else(|err| {
+      error!("failed to request panel width: {err}");
+    });
+}
+
+#[tauri::command]
+async fn hide_panel(app: tauri::AppHandle, window: tauri::WebviewWindow) {
+  let state = app.state::<TauriState>();
+  state.panels.hide(&window).unwrap_or_else(|err| {
+    error!("failed to hide panel: {err}");
+  });
+}
+
 // let control_gtk = control.gtk_window().unwrap();
 //
 // let control_is_visible = Arc::new(AtomicBool::new(false));
@@ -288,3 +580,59 @@ async fn request_input_regions(window: tauri::WebviewWindow, regions: Vec<InputR
 // });
 //
 // tokio::spawn(async move {});
+
+#[tauri::command]
+async fn get_config(app: tauri::AppHandle) -> config::Desktop {
+  let state = app.state::<TauriState>();
+  state.config.load().desktop.clone()
+}
+
+#[tauri::command]
+async fn get_workspaces(app: tauri::AppHandle, window: tauri::WebviewWindow) -> MonitorWorkspaces {
+  let state = app.state::<TauriState>();
+  let monitor = window
+    .label()
+    .strip_prefix(&panel::label(PanelKind::Workspace, ""))
+    .unwrap_or_default();
+
+  state.workspaces.get(monitor).await
+}
+
+#[tauri::command]
+async fn set_active_workspace(app: tauri::AppHandle, index: WorkspaceId) {
+  let state = app.state::<TauriState>();
+  state
+    .hyprland
+    .dispatch(DispatchType::Workspace(WorkspaceIdentifierWithSpecial::Id(index)))
+    .await
+    .unwrap_or_else(|err| {
+      error!("failed to set active workspace: {err}");
+    });
+}
+
+#[tauri::command]
+async fn dispatch(app: tauri::AppHandle, dispatcher: Dispatcher) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  state
+    .hyprland
+    .dispatch(dispatcher.to_dispatch_type())
+    .await
+    .map_err(|err| {
+      error!("failed to dispatch {dispatcher:?}: {err}");
+      err.to_string()
+    })
+}
+
+#[tauri::command]
+async fn dispatch_many(app: tauri::AppHandle, dispatchers: Vec<Dispatcher>) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  let cmds = dispatchers
+    .iter()
+    .map(Dispatcher::to_dispatch_type)
+    .collect();
+
+  state.hyprland.dispatch_many(cmds).await.map_err(|err| {
+    error!("failed to dispatch batch: {err}");
+    err.to_string()
+  })
+}
@@ -0,0 +1,303 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use anyhow::Result;
+use gdk::{
+  cairo::{RectangleInt, Region},
+  glib, Monitor,
+};
+use gtk::prelude::*;
+use tauri::Manager;
+use tracing::error;
+
+use crate::{config::Panel, layer_shell::LayerShellWindowBuilder};
+
+const NAMESPACE: &str = "dash2-panel";
+
+/// Size of the strip a hidden panel keeps along its edge, so that
+/// hovering the edge still produces enter events.
+const HIDDEN_HEIGHT: i32 = 2;
+const DEFAULT_WIDTH: i32 = 300;
+
+const ANIMATION_DURATION: Duration = Duration::from_millis(150);
+const ANIMATION_FRAME: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelKind {
+  Workspace,
+  Control,
+  Bars,
+}
+
+impl PanelKind {
+  pub const ALL: [PanelKind; 3] = [PanelKind::Workspace, PanelKind::Control, PanelKind::Bars];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      PanelKind::Workspace => "workspace",
+      PanelKind::Control => "control",
+      PanelKind::Bars => "bars",
+    }
+  }
+
+  fn url(&self) -> &'static str {
+    match self {
+      PanelKind::Workspace => "src/workspace/index.html",
+      PanelKind::Control => "src/control/index.html",
+      PanelKind::Bars => "src/bars/index.html",
+    }
+  }
+
+  /// Anchored edges as (top, right, bottom, left)
+  fn edge(&self) -> (bool, bool, bool, bool) {
+    match self {
+      PanelKind::Workspace => (true, false, false, false),
+      PanelKind::Control => (false, false, true, false),
+      PanelKind::Bars => (false, true, true, false),
+    }
+  }
+}
+
+impl From<Panel> for PanelKind {
+  fn from(panel: Panel) -> Self {
+    match panel {
+      Panel::Workspace => PanelKind::Workspace,
+      Panel::Control => PanelKind::Control,
+      Panel::Bars => PanelKind::Bars,
+    }
+  }
+}
+
+struct PanelState {
+  width: i32,
+  height: i32,
+  animation: Option<glib::SourceId>,
+}
+
+/// Keeps track of all panel windows, keyed by window label.
+#[derive(Default)]
+pub struct Panels {
+  panels: Mutex<HashMap<String, Arc<Mutex<PanelState>>>>,
+}
+
+impl Panels {
+  pub fn create(
+    &self,
+    app: &tauri::AppHandle,
+    kind: PanelKind,
+    monitor_label: &str,
+    monitor: &Monitor,
+  ) -> Result<tauri::WebviewWindow> {
+    let label = label(kind, monitor_label);
+    let (top, right, bottom, left) = kind.edge();
+
+    let window = LayerShellWindowBuilder::new(&label, kind.url())
+      .layer(gtk_layer_shell::Layer::Top)
+      .monitor(monitor)
+      .keyboard_mode(gtk_layer_shell::KeyboardMode::OnDemand)
+      .namespace(NAMESPACE)
+      .edge(top, right, bottom, left)
+      .size(DEFAULT_WIDTH, HIDDEN_HEIGHT)
+      .background_color(0., 0., 0., 0.)
+      .build(app)?;
+
+    apply_size(&window.gtk_window()?, DEFAULT_WIDTH, HIDDEN_HEIGHT);
+    super::connect_crossing_events(&window)?;
+
+    let state = PanelState {
+      width: DEFAULT_WIDTH,
+      height: HIDDEN_HEIGHT,
+      animation: None,
+    };
+
+    self
+      .panels
+      .lock()
+      .map_err(|_| anyhow::anyhow!("failed to lock panels"))?
+      .insert(label, Arc::new(Mutex::new(state)));
+
+    Ok(window)
+  }
+
+  /// Creates the enabled panels that are missing on the given monitor and
+  /// closes the others.
+  pub fn sync_monitor(
+    &self,
+    app: &tauri::AppHandle,
+    enabled: &[PanelKind],
+    monitor_label: &str,
+    monitor: &Monitor,
+  ) -> Result<()> {
+    for kind in PanelKind::ALL {
+      let exists = self
+        .panels
+        .lock()
+        .map_err(|_| anyhow::anyhow!("failed to lock panels"))?
+        .contains_key(&label(kind, monitor_label));
+
+      match (enabled.contains(&kind), exists) {
+        (true, false) => {
+          self.create(app, kind, monitor_label, monitor)?;
+        }
+        (false, true) => self.close(app, kind, monitor_label),
+        _ => {}
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Closes all panels on the given monitor.
+  pub fn close_monitor(&self, app: &tauri::AppHandle, monitor_label: &str) {
+    for kind in PanelKind::ALL {
+      self.close(app, kind, monitor_label);
+    }
+  }
+
+  fn close(&self, app: &tauri::AppHandle, kind: PanelKind, monitor_label: &str) {
+    let label = label(kind, monitor_label);
+
+    match self.panels.lock() {
+      Ok(mut panels) => {
+        panels.remove(&label);
+      }
+      Err(_) => error!("failed to lock panels"),
+    }
+
+    if let Some(window) = app.get_webview_window(&label) {
+      window.close().unwrap_or_else(|err| {
+        error!("failed to close panel: {err}");
+      });
+    }
+  }
+
+  pub fn request_height(&self, window: &tauri::WebviewWindow, height: i32) -> Result<()> {
+    let state = self.get(window.label())?;
+    animate_height(window, state, height.max(HIDDEN_HEIGHT))
+  }
+
+  pub fn request_width(&self, window: &tauri::WebviewWindow, width: i32) -> Result<()> {
+    let state = self.get(window.label())?;
+    let width = width.max(1);
+
+    let window_handle = window.clone();
+    window.run_on_main_thread(move || {
+      let Ok(mut state) = state.lock() else {
+        error!("failed to lock panel state");
+        return;
+      };
+
+      state.width = width;
+      match window_handle.gtk_window() {
+        Ok(gtk_window) => apply_size(&gtk_window, state.width, state.height),
+        Err(err) => error!("failed to get gtk window: {err}"),
+      }
+    })?;
+
+    Ok(())
+  }
+
+  pub fn hide(&self, window: &tauri::WebviewWindow) -> Result<()> {
+    let state = self.get(window.label())?;
+    animate_height(window, state, HIDDEN_HEIGHT)
+  }
+
+  /// Whether the panel is shown, even if only partly while animating.
+  pub fn is_visible(&self, label: &str) -> Result<bool> {
+    let state = self.get(label)?;
+    let state = state
+      .lock()
+      .map_err(|_| anyhow::anyhow!("failed to lock panel state"))?;
+
+    Ok(state.height > HIDDEN_HEIGHT)
+  }
+
+  fn get(&self, label: &str) -> Result<Arc<Mutex<PanelState>>> {
+    self
+      .panels
+      .lock()
+      .map_err(|_| anyhow::anyhow!("failed to lock panels"))?
+      .get(label)
+      .cloned()
+      .ok_or_else(|| anyhow::anyhow!("window {label} is not a panel"))
+  }
+}
+
+pub fn label(kind: PanelKind, monitor_label: &str) -> String {
+  format!("{NAMESPACE}-{}-{monitor_label}", kind.name())
+}
+
+/// Resizes the panel towards `target` over `ANIMATION_DURATION`, cancelling
+/// any animation that is already running for this panel.
+fn animate_height(
+  window: &tauri::WebviewWindow,
+  state: Arc<Mutex<PanelState>>,
+  target: i32,
+) -> Result<()> {
+  let window_handle = window.clone();
+  window.run_on_main_thread(move || {
+    let gtk_window = match window_handle.gtk_window() {
+      Ok(gtk_window) => gtk_window,
+      Err(err) => {
+        error!("failed to get gtk window: {err}");
+        return;
+      }
+    };
+
+    let Ok(mut locked) = state.lock() else {
+      error!("failed to lock panel state");
+      return;
+    };
+
+    if let Some(animation) = locked.animation.take() {
+      animation.remove();
+    }
+
+    if locked.height == target {
+      return;
+    }
+
+    let from = locked.height;
+    let frames = (ANIMATION_DURATION.as_millis() / ANIMATION_FRAME.as_millis()) as i32;
+    let mut frame = 0;
+
+    let animation_state = state.clone();
+    let source = glib::timeout_add_local(ANIMATION_FRAME, move || {
+      let Ok(mut state) = animation_state.lock() else {
+        error!("failed to lock panel state");
+        return glib::ControlFlow::Break;
+      };
+
+      frame += 1;
+      let progress = frame as f64 / frames as f64;
+      let eased = 1. - (1. - progress).powi(3);
+      state.height = from + ((target - from) as f64 * eased).round() as i32;
+      apply_size(&gtk_window, state.width, state.height);
+
+      if frame < frames {
+        return glib::ControlFlow::Continue;
+      }
+
+      // The source is removed by returning `Break`, so it must not be
+      // removed again when the next animation starts
+      state.animation = None;
+      glib::ControlFlow::Break
+    });
+
+    locked.animation = Some(source);
+  })?;
+
+  Ok(())
+}
+
+/// Sets the window size and restricts input to the visible area.
+fn apply_size(gtk_window: &gtk::ApplicationWindow, width: i32, height: i32) {
+  gtk_window.set_size_request(width, height);
+  gtk_window.resize(width, height);
+
+  let region = Region::create_rectangle(&RectangleInt::new(0, 0, width, height));
+  gtk_window.input_shape_combine_region(Some(&region));
+}
@@ -0,0 +1,287 @@
+use std::{
+  os::fd::OwnedFd,
+  sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use futures::StreamExt;
+use tracing::{error, info};
+
+use crate::logind::{self, ManagerProxy};
+
+use super::locker::Locker;
+
+/// Locks the session before the system goes to sleep, and whenever logind
+/// asks for it, e.g. through `loginctl lock-session`.
+pub async fn watch(zbus_conn: &zbus::Connection, locker: Arc<Mutex<Locker>>) -> Result<()> {
+  watch_signals(zbus_conn, |inhibitor| lock_session(&locker, inhibitor)).await
+}
+
+/// Calls `on_lock` for every request to lock the session. Before sleep, it
+/// gets the sleep inhibitor, which it has to hold until the session is
+/// locked.
+async fn watch_signals(
+  zbus_conn: &zbus::Connection,
+  on_lock: impl Fn(Option<OwnedFd>),
+) -> Result<()> {
+  let manager = ManagerProxy::new(zbus_conn).await?;
+  let session = logind::current_session(zbus_conn).await?;
+
+  let mut sleep = manager.receive_prepare_for_sleep().await?;
+  let mut lock = session.receive_lock().await?;
+
+  let mut inhibitor = Some(logind::inhibit_sleep(&manager, "Lock the session").await?);
+
+  loop {
+    tokio::select! {
+      Some(signal) = sleep.next() => {
+        if *signal.args()?.start() {
+          info!("locking session before sleep");
+          on_lock(inhibitor.take());
+        } else {
+          inhibitor = logind::inhibit_sleep(&manager, "Lock the session")
+            .await
+            .map_err(|err| error!("failed to inhibit sleep: {err}"))
+            .ok();
+        }
+      }
+      Some(_) = lock.next() => {
+        info!("locking session on request of logind");
+        on_lock(None);
+      }
+      else => break,
+    }
+  }
+
+  Ok(())
+}
+
+fn lock_session(locker: &Mutex<Locker>, inhibitor: Option<OwnedFd>) {
+  let Ok(mut locker) = locker.lock() else {
+    error!("failed to lock locker");
+    return;
+  };
+
+  // If the session is already locked, the inhibitor is released right away
+  locker.lock(0, inhibitor).unwrap_or_else(|err| {
+    error!("failed to lock session: {err}");
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    os::fd::{AsRawFd, FromRawFd},
+    time::Duration,
+  };
+
+  use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+  use zbus::{dbus_interface, fdo, zvariant, SignalContext};
+
+  use super::*;
+  use crate::testing::PrivateBus;
+
+  const MANAGER: &str = "/org/freedesktop/login1";
+  const SESSION: &str = "/org/freedesktop/login1/session/_32";
+
+  /// An inhibitor handed out by the fake logind, with the end of the pipe
+  /// that logind keeps
+  struct Inhibitor {
+    what: String,
+    who: String,
+    mode: String,
+    fd: OwnedFd,
+  }
+
+  impl Inhibitor {
+    /// Whether every copy of the descriptor handed out was closed
+    fn is_released(&self) -> bool {
+      let mut poll_fd = libc::pollfd {
+        fd: self.fd.as_raw_fd(),
+        events: 0,
+        revents: 0,
+      };
+
+      assert!(unsafe { libc::poll(&mut poll_fd, 1, 0) } >= 0);
+      poll_fd.revents & libc::POLLHUP != 0
+    }
+
+    /// Waits a bit for the inhibitor to be released. zbus keeps a copy of
+    /// the descriptor in the reply until the watcher drains its streams.
+    async fn released(&self) -> bool {
+      for _ in 0..100 {
+        if self.is_released() {
+          return true;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+      }
+
+      false
+    }
+  }
+
+  struct FakeManager {
+    inhibitors: UnboundedSender<Inhibitor>,
+  }
+
+  #[dbus_interface(name = "org.freedesktop.login1.Manager")]
+  impl FakeManager {
+    fn get_session(&self, session_id: &str) -> fdo::Result<zvariant::OwnedObjectPath> {
+      match session_id {
+        "2" => Ok(SESSION.try_into().unwrap()),
+        _ => Err(fdo::Error::Failed(format!("no session {session_id}"))),
+      }
+    }
+
+    /// Like logind, hands out the write end of a pipe and watches the read
+    /// end for the inhibitor being released
+    fn inhibit(&self, what: &str, who: &str, _why: &str, mode: &str) -> zvariant::OwnedFd {
+      let mut fds = [0; 2];
+      assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+
+      self
+        .inhibitors
+        .send(Inhibitor {
+          what: what.to_string(),
+          who: who.to_string(),
+          mode: mode.to_string(),
+          fd: unsafe { OwnedFd::from_raw_fd(fds[0]) },
+        })
+        .unwrap();
+
+      unsafe { zvariant::OwnedFd::from_raw_fd(fds[1]) }
+    }
+
+    #[dbus_interface(signal)]
+    async fn prepare_for_sleep(ctxt: &SignalContext<'_>, start: bool) -> zbus::Result<()>;
+  }
+
+  struct FakeSession;
+
+  #[dbus_interface(name = "org.freedesktop.login1.Session")]
+  impl FakeSession {
+    #[dbus_interface(property)]
+    fn id(&self) -> String {
+      "2".to_string()
+    }
+
+    #[dbus_interface(signal)]
+    async fn lock(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+  }
+
+  struct Logind {
+    conn: zbus::Connection,
+    inhibitors: UnboundedReceiver<Inhibitor>,
+    /// Inhibitors passed along with each lock
+    locks: UnboundedReceiver<Option<OwnedFd>>,
+    _bus: PrivateBus,
+  }
+
+  impl Logind {
+    /// Starts a fake logind and watches it, returning once the watcher
+    /// took its first inhibitor
+    async fn start() -> (Self, Inhibitor) {
+      let bus = PrivateBus::start();
+      let (inhibitors_tx, mut inhibitors) = mpsc::unbounded_channel();
+
+      let conn = bus
+        .connection()
+        .name("org.freedesktop.login1")
+        .unwrap()
+        .serve_at(
+          MANAGER,
+          FakeManager {
+            inhibitors: inhibitors_tx,
+          },
+        )
+        .unwrap()
+        .serve_at("/org/freedesktop/login1/session/auto", FakeSession)
+        .unwrap()
+        .serve_at(SESSION, FakeSession)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+      let (locks_tx, locks) = mpsc::unbounded_channel();
+      let watcher_conn = bus.connect().await;
+      tokio::spawn(async move {
+        watch_signals(&watcher_conn, |inhibitor| locks_tx.send(inhibitor).unwrap())
+          .await
+          .unwrap();
+      });
+
+      let inhibitor = inhibitors.recv().await.unwrap();
+      let logind = Self {
+        conn,
+        inhibitors,
+        locks,
+        _bus: bus,
+      };
+
+      (logind, inhibitor)
+    }
+
+    async fn prepare_for_sleep(&self, start: bool) {
+      let ctxt = SignalContext::new(&self.conn, MANAGER).unwrap();
+      FakeManager::prepare_for_sleep(&ctxt, start).await.unwrap();
+    }
+
+    async fn lock_session(&self) {
+      let ctxt = SignalContext::new(&self.conn, SESSION).unwrap();
+      FakeSession::lock(&ctxt).await.unwrap();
+    }
+  }
+
+  #[tokio::test]
+  async fn takes_a_delay_inhibitor() {
+    let (_logind, inhibitor) = Logind::start().await;
+
+    assert_eq!(inhibitor.what, "sleep");
+    assert_eq!(inhibitor.who, "dash2");
+    assert_eq!(inhibitor.mode, "delay");
+    assert!(!inhibitor.is_released());
+  }
+
+  #[tokio::test]
+  async fn locks_before_sleep_with_the_inhibitor() {
+    let (mut logind, inhibitor) = Logind::start().await;
+
+    logind.prepare_for_sleep(true).await;
+    let fd = logind.locks.recv().await.unwrap().expect("no inhibitor");
+
+    // Held until the lock screen reports the session as locked
+    assert!(!inhibitor.is_released());
+    drop(fd);
+    assert!(inhibitor.released().await);
+  }
+
+  #[tokio::test]
+  async fn inhibits_again_after_waking_up() {
+    let (mut logind, first) = Logind::start().await;
+
+    logind.prepare_for_sleep(true).await;
+    drop(logind.locks.recv().await.unwrap());
+    assert!(first.released().await);
+
+    logind.prepare_for_sleep(false).await;
+    let second = logind.inhibitors.recv().await.unwrap();
+    assert!(!second.is_released());
+
+    logind.prepare_for_sleep(true).await;
+    drop(logind.locks.recv().await.unwrap().expect("no inhibitor"));
+    assert!(second.released().await);
+  }
+
+  #[tokio::test]
+  async fn locks_on_request_of_logind() {
+    let (mut logind, inhibitor) = Logind::start().await;
+
+    logind.lock_session().await;
+
+    assert!(logind.locks.recv().await.unwrap().is_none());
+    // Kept for the next sleep
+    assert!(!inhibitor.is_released());
+  }
+}
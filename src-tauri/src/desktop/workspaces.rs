@@ -0,0 +1,332 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Result;
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::hyprland::{events::Event, types::WorkspaceId, HyprlandClient};
+
+use super::panel::{self, PanelKind};
+
+/// The workspaces shown on a single monitor
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MonitorWorkspaces {
+  pub workspaces: Vec<WorkspaceId>,
+  pub active: Option<WorkspaceId>,
+}
+
+struct WorkspaceEntry {
+  monitor: String,
+}
+
+#[derive(Default)]
+struct WorkspaceModel {
+  workspaces: HashMap<WorkspaceId, WorkspaceEntry>,
+  active: HashMap<String, WorkspaceId>,
+  focused_monitor: Option<String>,
+}
+
+impl WorkspaceModel {
+  /// Applies a hyprland event to the model. Returns true if the event
+  /// changed anything that the workspace panels display.
+  fn apply(&mut self, event: &Event) -> bool {
+    match event {
+      Event::Workspace(data) => {
+        let monitor = match self.workspaces.get(&data.workspace_id) {
+          Some(entry) => entry.monitor.clone(),
+          None => {
+            let Some(monitor) = self.focused_monitor.clone() else {
+              return false;
+            };
+
+            self.workspaces.insert(
+              data.workspace_id,
+              WorkspaceEntry {
+                monitor: monitor.clone(),
+              },
+            );
+
+            monitor
+          }
+        };
+
+        self.focused_monitor = Some(monitor.clone());
+        self.active.insert(monitor, data.workspace_id) != Some(data.workspace_id)
+      }
+      Event::CreateWorkspace(data) => {
+        // Workspaces are always created on the focused monitor
+        let Some(monitor) = self.focused_monitor.clone() else {
+          return false;
+        };
+
+        self
+          .workspaces
+          .insert(data.workspace_id, WorkspaceEntry { monitor })
+          .is_none()
+      }
+      Event::DestroyWorkspace(data) => self.workspaces.remove(&data.workspace_id).is_some(),
+      Event::MoveWorkspace(data) => {
+        let Some(entry) = self.workspaces.get_mut(&data.workspace_id) else {
+          return false;
+        };
+
+        let source = std::mem::replace(&mut entry.monitor, data.monitor_name.clone());
+
+        // The moved workspace is shown on its new monitor, and the old
+        // monitor switches to one of the workspaces it has left
+        self
+          .active
+          .insert(data.monitor_name.clone(), data.workspace_id);
+        if self.active.get(&source) == Some(&data.workspace_id) {
+          match self.monitor_workspaces(&source).workspaces.first() {
+            Some(id) => self.active.insert(source, *id),
+            None => self.active.remove(&source),
+          };
+        }
+
+        true
+      }
+      Event::FocusedMon(data) => {
+        self.focused_monitor = Some(data.monitor_name.clone());
+        false
+      }
+      _ => false,
+    }
+  }
+
+  fn monitor_workspaces(&self, monitor: &str) -> MonitorWorkspaces {
+    // Special workspaces have negative ids and are not shown
+    let workspaces = self
+      .workspaces
+      .iter()
+      .filter(|(id, entry)| **id > 0 && entry.monitor == monitor)
+      .map(|(id, _)| *id)
+      .collect::<BTreeSet<_>>();
+
+    MonitorWorkspaces {
+      workspaces: workspaces.into_iter().collect(),
+      active: self.active.get(monitor).copied(),
+    }
+  }
+
+  fn monitors(&self) -> BTreeSet<String> {
+    self
+      .workspaces
+      .values()
+      .map(|entry| entry.monitor.clone())
+      .chain(self.active.keys().cloned())
+      .collect()
+  }
+}
+
+/// Keeps a per-monitor view of the hyprland workspaces and pushes it
+/// to the workspace panel on the respective monitor.
+#[derive(Default)]
+pub struct Workspaces {
+  model: Mutex<WorkspaceModel>,
+}
+
+impl Workspaces {
+  /// Replaces the model with the current state queried from hyprland.
+  pub async fn sync(&self, hyprland: &HyprlandClient) -> Result<()> {
+    let workspaces = hyprland.get_workspaces().await?;
+    let monitors = hyprland.get_monitors().await?;
+    let active_workspace = hyprland.get_active_workspace().await?;
+
+    let mut model = self.model.lock().await;
+    *model = WorkspaceModel {
+      workspaces: workspaces
+        .into_iter()
+        .map(|workspace| {
+          let entry = WorkspaceEntry {
+            monitor: workspace.monitor,
+          };
+
+          (workspace.id, entry)
+        })
+        .collect(),
+      active: monitors
+        .into_iter()
+        .map(|monitor| (monitor.name, monitor.active_workspace.id))
+        .collect(),
+      focused_monitor: Some(active_workspace.monitor),
+    };
+
+    Ok(())
+  }
+
+  pub async fn handle_event(&self, app: &tauri::AppHandle, event: &Event) {
+    let changed = self.model.lock().await.apply(event);
+    if changed {
+      self.emit_all(app).await;
+    }
+  }
+
+  pub async fn get(&self, monitor: &str) -> MonitorWorkspaces {
+    self.model.lock().await.monitor_workspaces(monitor)
+  }
+
+  pub async fn emit_all(&self, app: &tauri::AppHandle) {
+    let model = self.model.lock().await;
+
+    for monitor in model.monitors() {
+      let state = model.monitor_workspaces(&monitor);
+      let label = panel::label(PanelKind::Workspace, &monitor);
+
+      app
+        .emit_to(&label, "workspace-count", &state.workspaces)
+        .unwrap_or_else(|err| {
+          error!("failed to emit workspace-count: {err}");
+        });
+
+      if let Some(active) = state.active {
+        app
+          .emit_to(&label, "active-workspace", active)
+          .unwrap_or_else(|err| {
+            error!("failed to emit active-workspace: {err}");
+          });
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hyprland::events::{
+    MonitorEventData, WorkspaceEventData, WorkspaceMoveEventData, WorkspaceType,
+  };
+
+  fn workspace(id: WorkspaceId) -> WorkspaceEventData {
+    WorkspaceEventData {
+      workspace_id: id,
+      workspace_name: id.to_string(),
+    }
+  }
+
+  fn focused_mon(monitor: &str) -> Event {
+    Event::FocusedMon(MonitorEventData {
+      monitor_name: monitor.to_string(),
+      workspace: WorkspaceType::Regular("1".to_string()),
+    })
+  }
+
+  fn move_workspace(id: WorkspaceId, monitor: &str) -> Event {
+    Event::MoveWorkspace(WorkspaceMoveEventData {
+      workspace_id: id,
+      workspace_name: id.to_string(),
+      monitor_name: monitor.to_string(),
+    })
+  }
+
+  fn shows(workspaces: &[WorkspaceId], active: Option<WorkspaceId>) -> MonitorWorkspaces {
+    MonitorWorkspaces {
+      workspaces: workspaces.to_vec(),
+      active,
+    }
+  }
+
+  /// Workspaces 1 and 2 on DP-1 and 3 on HDMI-A-1, with 1 and 3 active
+  /// and DP-1 focused
+  fn model() -> WorkspaceModel {
+    let entry = |monitor: &str| WorkspaceEntry {
+      monitor: monitor.to_string(),
+    };
+
+    WorkspaceModel {
+      workspaces: HashMap::from([
+        (1, entry("DP-1")),
+        (2, entry("DP-1")),
+        (3, entry("HDMI-A-1")),
+      ]),
+      active: HashMap::from([("DP-1".to_string(), 1), ("HDMI-A-1".to_string(), 3)]),
+      focused_monitor: Some("DP-1".to_string()),
+    }
+  }
+
+  #[test]
+  fn switches_workspaces() {
+    let mut model = model();
+
+    assert!(model.apply(&Event::Workspace(workspace(2))));
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1, 2], Some(2)));
+
+    // Already active
+    assert!(!model.apply(&Event::Workspace(workspace(2))));
+
+    // Switching to the shown workspace of another monitor only focuses it
+    assert!(!model.apply(&Event::Workspace(workspace(3))));
+    assert_eq!(model.focused_monitor.as_deref(), Some("HDMI-A-1"));
+  }
+
+  #[test]
+  fn creates_workspaces_on_the_focused_monitor() {
+    let mut model = model();
+
+    assert!(!model.apply(&focused_mon("HDMI-A-1")));
+    assert!(model.apply(&Event::CreateWorkspace(workspace(4))));
+    assert!(model.apply(&Event::Workspace(workspace(4))));
+
+    assert_eq!(
+      model.monitor_workspaces("HDMI-A-1"),
+      shows(&[3, 4], Some(4))
+    );
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1, 2], Some(1)));
+  }
+
+  #[test]
+  fn hides_special_workspaces() {
+    let mut model = model();
+
+    assert!(model.apply(&Event::CreateWorkspace(workspace(-98))));
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1, 2], Some(1)));
+  }
+
+  #[test]
+  fn destroys_workspaces() {
+    let mut model = model();
+
+    assert!(model.apply(&Event::DestroyWorkspace(workspace(2))));
+    assert!(!model.apply(&Event::DestroyWorkspace(workspace(2))));
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1], Some(1)));
+  }
+
+  #[test]
+  fn moves_active_workspaces() {
+    let mut model = model();
+
+    assert!(model.apply(&move_workspace(1, "HDMI-A-1")));
+
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[2], Some(2)));
+    assert_eq!(
+      model.monitor_workspaces("HDMI-A-1"),
+      shows(&[1, 3], Some(1))
+    );
+  }
+
+  #[test]
+  fn moves_inactive_workspaces() {
+    let mut model = model();
+
+    assert!(model.apply(&move_workspace(2, "HDMI-A-1")));
+
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1], Some(1)));
+    assert_eq!(
+      model.monitor_workspaces("HDMI-A-1"),
+      shows(&[2, 3], Some(2))
+    );
+  }
+
+  #[test]
+  fn moves_the_last_workspace_of_a_monitor() {
+    let mut model = model();
+
+    assert!(model.apply(&move_workspace(3, "DP-1")));
+
+    assert_eq!(model.monitor_workspaces("HDMI-A-1"), shows(&[], None));
+    assert_eq!(model.monitor_workspaces("DP-1"), shows(&[1, 2, 3], Some(3)));
+    assert!(!model.apply(&move_workspace(7, "DP-1")));
+  }
+}
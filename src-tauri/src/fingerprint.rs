@@ -0,0 +1,441 @@
+use anyhow::{bail, Result};
+use futures::StreamExt;
+use tauri::Emitter;
+use tracing::{error, info};
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath};
+
+/// How many times the user can fail a scan before we stop listening.
+/// fprintd doesn't limit this, but the device would otherwise stay busy forever.
+const MAX_ATTEMPTS: usize = 5;
+
+#[dbus_proxy(
+  interface = "net.reactivated.Fprint.Manager",
+  default_service = "net.reactivated.Fprint",
+  default_path = "/net/reactivated/Fprint/Manager"
+)]
+trait Manager {
+  fn get_default_device(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[dbus_proxy(
+  interface = "net.reactivated.Fprint.Device",
+  default_service = "net.reactivated.Fprint",
+  assume_defaults = false
+)]
+trait Device {
+  fn list_enrolled_fingers(&self, username: &str) -> zbus::Result<Vec<String>>;
+  fn claim(&self, username: &str) -> zbus::Result<()>;
+  fn release(&self) -> zbus::Result<()>;
+  fn verify_start(&self, finger_name: &str) -> zbus::Result<()>;
+  fn verify_stop(&self) -> zbus::Result<()>;
+
+  #[dbus_proxy(signal)]
+  fn verify_status(&self, result: &str, done: bool) -> zbus::Result<()>;
+}
+
+/// Outcome of a single fingerprint scan, see the `VerifyStatus` signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerifyResult {
+  Match,
+  NoMatch,
+  /// The scan was bad and should be repeated
+  Retry,
+  Failed,
+}
+
+impl VerifyResult {
+  fn parse(result: &str) -> Self {
+    match result {
+      "verify-match" => Self::Match,
+      "verify-no-match" => Self::NoMatch,
+      "verify-retry-scan"
+      | "verify-swipe-too-short"
+      | "verify-finger-not-centered"
+      | "verify-remove-and-retry" => Self::Retry,
+      _ => Self::Failed,
+    }
+  }
+}
+
+/// Progress of a verification, shown by the lock screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyEvent {
+  Scanning,
+  /// The scan was bad, with the reason from fprintd
+  Retry(String),
+  Match,
+  Error(String),
+}
+
+impl VerifyEvent {
+  /// Emits the event to the lock screen windows
+  pub fn emit(self, app: &tauri::AppHandle) {
+    let result = match self {
+      Self::Scanning => app.emit("scanning-fingerprint", ()),
+      Self::Retry(reason) => app.emit("fingerprint-retry", reason),
+      Self::Match => app.emit("fingerprint-match", ()),
+      Self::Error(message) => app.emit("fingerprint-error", message),
+    };
+
+    result.unwrap_or_else(|err| {
+      error!("failed to emit fingerprint event: {err}");
+    });
+  }
+}
+
+pub struct Fingerprint<'a> {
+  device: DeviceProxy<'a>,
+}
+
+impl<'a> Fingerprint<'a> {
+  /// Connects to the default fprintd device. Returns `None` if fprintd
+  /// isn't running or there is no fingerprint reader.
+  pub async fn new(zbus_conn: &zbus::Connection) -> Result<Option<Self>> {
+    let manager = ManagerProxy::new(zbus_conn).await?;
+    let path = match manager.get_default_device().await {
+      Ok(path) => path,
+      Err(err) => {
+        info!("no fingerprint device available: {err}");
+        return Ok(None);
+      }
+    };
+
+    let device = DeviceProxy::builder(zbus_conn).path(path)?.build().await?;
+    Ok(Some(Self { device }))
+  }
+
+  /// Waits for the user to scan an enrolled finger and reports the progress
+  /// along the way. Returns true if a finger matched.
+  pub async fn verify(&self, username: &str, on_event: impl Fn(VerifyEvent)) -> Result<bool> {
+    // fprintd reports users without enrolled fingers as an error
+    let enrolled = self
+      .device
+      .list_enrolled_fingers(username)
+      .await
+      .unwrap_or_default();
+
+    if enrolled.is_empty() {
+      info!("no fingers enrolled for {username}");
+      return Ok(false);
+    }
+
+    self.device.claim(username).await?;
+    let result = self.verify_claimed(&on_event).await;
+
+    self.device.release().await.unwrap_or_else(|err| {
+      error!("failed to release fingerprint device: {err}");
+    });
+
+    result
+  }
+
+  async fn verify_claimed(&self, on_event: &impl Fn(VerifyEvent)) -> Result<bool> {
+    // Subscribe before starting, so no status is missed
+    let mut statuses = self.device.receive_verify_status().await?;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+      self.device.verify_start("any").await?;
+      on_event(VerifyEvent::Scanning);
+
+      let mut result = None;
+      while let Some(status) = statuses.next().await {
+        let args = status.args()?;
+        let parsed = VerifyResult::parse(args.result());
+
+        if parsed == VerifyResult::Retry {
+          on_event(VerifyEvent::Retry(args.result().to_string()));
+        }
+
+        if *args.done() {
+          result = Some(parsed);
+          break;
+        }
+      }
+
+      self.device.verify_stop().await.unwrap_or_else(|err| {
+        error!("failed to stop fingerprint verification: {err}");
+      });
+
+      let Some(result) = result else {
+        bail!("fingerprint device stopped reporting verify status");
+      };
+
+      // A failed scan only costs an attempt like one that didn't match.
+      // The device itself failing shows up as an error from the calls above.
+      match result {
+        VerifyResult::Match => {
+          on_event(VerifyEvent::Match);
+          return Ok(true);
+        }
+        VerifyResult::NoMatch => {
+          info!("fingerprint did not match, attempt {attempt}/{MAX_ATTEMPTS}");
+          on_event(VerifyEvent::Error("no match".to_string()));
+        }
+        VerifyResult::Retry | VerifyResult::Failed => {
+          info!("failed to scan fingerprint, attempt {attempt}/{MAX_ATTEMPTS}");
+          on_event(VerifyEvent::Error("failed to scan".to_string()));
+        }
+      }
+    }
+
+    Ok(false)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+  };
+
+  use zbus::{dbus_interface, fdo, SignalContext};
+
+  use super::*;
+  use crate::testing::PrivateBus;
+
+  const DEVICE: &str = "/net/reactivated/Fprint/Device/0";
+
+  struct FakeManager;
+
+  #[dbus_interface(name = "net.reactivated.Fprint.Manager")]
+  impl FakeManager {
+    fn get_default_device(&self) -> OwnedObjectPath {
+      OwnedObjectPath::try_from(DEVICE).unwrap()
+    }
+  }
+
+  /// Answers every scan with the next scripted list of statuses and logs
+  /// the calls it gets. Fails to start once it runs out of scans.
+  struct FakeDevice {
+    enrolled: Vec<String>,
+    scans: VecDeque<Vec<(&'static str, bool)>>,
+    calls: Arc<Mutex<Vec<String>>>,
+  }
+
+  impl FakeDevice {
+    fn log(&self, call: &str) {
+      self.calls.lock().unwrap().push(call.to_string());
+    }
+  }
+
+  #[dbus_interface(name = "net.reactivated.Fprint.Device")]
+  impl FakeDevice {
+    fn list_enrolled_fingers(&self, username: &str) -> fdo::Result<Vec<String>> {
+      self.log(&format!("list_enrolled_fingers {username}"));
+
+      match self.enrolled.is_empty() {
+        true => Err(fdo::Error::Failed(
+          "net.reactivated.Fprint.Error.NoEnrolledPrints".to_string(),
+        )),
+        false => Ok(self.enrolled.clone()),
+      }
+    }
+
+    fn claim(&self, username: &str) {
+      self.log(&format!("claim {username}"));
+    }
+
+    fn release(&self) {
+      self.log("release");
+    }
+
+    async fn verify_start(
+      &mut self,
+      finger_name: &str,
+      #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+      self.log(&format!("verify_start {finger_name}"));
+
+      let Some(scan) = self.scans.pop_front() else {
+        return Err(fdo::Error::Failed(
+          "net.reactivated.Fprint.Error.Internal".to_string(),
+        ));
+      };
+
+      for (result, done) in scan {
+        Self::verify_status(&ctxt, result, done).await?;
+      }
+
+      Ok(())
+    }
+
+    fn verify_stop(&self) {
+      self.log("verify_stop");
+    }
+
+    #[dbus_interface(signal)]
+    async fn verify_status(ctxt: &SignalContext<'_>, result: &str, done: bool) -> zbus::Result<()>;
+  }
+
+  struct Verification {
+    result: Result<bool>,
+    events: Vec<VerifyEvent>,
+    calls: Vec<String>,
+  }
+
+  async fn verify(enrolled: &[&str], scans: Vec<Vec<(&'static str, bool)>>) -> Verification {
+    let bus = PrivateBus::start();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let device = FakeDevice {
+      enrolled: enrolled.iter().map(|finger| finger.to_string()).collect(),
+      scans: scans.into(),
+      calls: calls.clone(),
+    };
+
+    let _fprintd = bus
+      .connection()
+      .name("net.reactivated.Fprint")
+      .unwrap()
+      .serve_at("/net/reactivated/Fprint/Manager", FakeManager)
+      .unwrap()
+      .serve_at(DEVICE, device)
+      .unwrap()
+      .build()
+      .await
+      .unwrap();
+
+    let conn = bus.connect().await;
+    let fingerprint = Fingerprint::new(&conn).await.unwrap().unwrap();
+
+    let events = Mutex::new(Vec::new());
+    let result = fingerprint
+      .verify("alice", |event| events.lock().unwrap().push(event))
+      .await;
+
+    let calls = calls.lock().unwrap().clone();
+    Verification {
+      result,
+      events: events.into_inner().unwrap(),
+      calls,
+    }
+  }
+
+  #[tokio::test]
+  async fn matches() {
+    let verification = verify(&["right-index-finger"], vec![vec![("verify-match", true)]]).await;
+
+    assert!(verification.result.unwrap());
+    assert_eq!(
+      verification.events,
+      [VerifyEvent::Scanning, VerifyEvent::Match]
+    );
+    assert_eq!(
+      verification.calls,
+      [
+        "list_enrolled_fingers alice",
+        "claim alice",
+        "verify_start any",
+        "verify_stop",
+        "release",
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn retries_until_a_match() {
+    let verification = verify(
+      &["right-index-finger"],
+      vec![
+        vec![("verify-no-match", true)],
+        vec![("verify-swipe-too-short", false), ("verify-match", true)],
+      ],
+    )
+    .await;
+
+    assert!(verification.result.unwrap());
+    assert_eq!(
+      verification.events,
+      [
+        VerifyEvent::Scanning,
+        VerifyEvent::Error("no match".to_string()),
+        VerifyEvent::Scanning,
+        VerifyEvent::Retry("verify-swipe-too-short".to_string()),
+        VerifyEvent::Match,
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn gives_up_after_max_attempts() {
+    let scans = vec![vec![("verify-no-match", true)]; MAX_ATTEMPTS + 1];
+    let verification = verify(&["right-index-finger"], scans).await;
+
+    assert!(!verification.result.unwrap());
+
+    let starts = verification
+      .calls
+      .iter()
+      .filter(|call| call.starts_with("verify_start"))
+      .count();
+    assert_eq!(starts, MAX_ATTEMPTS);
+    assert_eq!(verification.calls.last().unwrap(), "release");
+    assert_eq!(
+      verification.events.last(),
+      Some(&VerifyEvent::Error("no match".to_string()))
+    );
+  }
+
+  #[tokio::test]
+  async fn retries_after_failed_scans() {
+    let verification = verify(
+      &["right-index-finger"],
+      vec![
+        vec![("verify-retry-scan", true)],
+        vec![("verify-disconnected", true)],
+        vec![("verify-match", true)],
+      ],
+    )
+    .await;
+
+    assert!(verification.result.unwrap());
+    assert_eq!(
+      verification.events,
+      [
+        VerifyEvent::Scanning,
+        VerifyEvent::Retry("verify-retry-scan".to_string()),
+        VerifyEvent::Error("failed to scan".to_string()),
+        VerifyEvent::Scanning,
+        VerifyEvent::Error("failed to scan".to_string()),
+        VerifyEvent::Scanning,
+        VerifyEvent::Match,
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn stops_when_the_device_fails() {
+    let verification = verify(
+      &["right-index-finger"],
+      vec![vec![("verify-unknown-error", true)]],
+    )
+    .await;
+
+    assert!(verification.result.is_err());
+    assert_eq!(
+      verification.events,
+      [
+        VerifyEvent::Scanning,
+        VerifyEvent::Error("failed to scan".to_string())
+      ]
+    );
+    assert_eq!(verification.calls.last().unwrap(), "release");
+  }
+
+  #[tokio::test]
+  async fn skips_users_without_fingers() {
+    let verification = verify(&[], vec![]).await;
+
+    assert!(!verification.result.unwrap());
+    assert!(verification.events.is_empty());
+    assert_eq!(verification.calls, ["list_enrolled_fingers alice"]);
+  }
+
+  #[tokio::test]
+  async fn no_fprintd() {
+    let bus = PrivateBus::start();
+    let conn = bus.connect().await;
+
+    assert!(Fingerprint::new(&conn).await.unwrap().is_none());
+  }
+}
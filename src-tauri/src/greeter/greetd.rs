@@ -1,75 +1,464 @@
-use anyhow::{Context, Result};
-use greetd_ipc::codec::TokioCodec;
-use tokio::net::UnixStream;
+use anyhow::Context;
+use greetd_ipc::{codec::TokioCodec, AuthMessageType, ErrorType, Request, Response};
+use serde::Serialize;
+use thiserror::Error as ThisError;
+use tokio::{io::AsyncWriteExt, net::UnixStream};
+use tracing::error;
 
-use crate::config::Config;
+use super::sessions::SessionEntry;
+use crate::{scrambler::Scrambler, secret::Secret};
+
+#[derive(Debug, ThisError)]
+pub enum GreetdError {
+  #[error("{0}")]
+  Auth(String),
+  #[error("greetd error: {0}")]
+  Greetd(String),
+  #[error("unexpected response from greetd in state {0:?}")]
+  UnexpectedResponse(ConversationState),
+  #[error("no {0} allowed in state {1:?}")]
+  InvalidState(&'static str, ConversationState),
+  #[error(transparent)]
+  Codec(#[from] greetd_ipc::codec::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMessageKind {
+  Visible,
+  Secret,
+  Info,
+  Error,
+}
+
+impl From<AuthMessageType> for AuthMessageKind {
+  fn from(value: AuthMessageType) -> Self {
+    match value {
+      AuthMessageType::Visible => Self::Visible,
+      AuthMessageType::Secret => Self::Secret,
+      AuthMessageType::Info => Self::Info,
+      AuthMessageType::Error => Self::Error,
+    }
+  }
+}
+
+/// A message from the PAM stack that greetd forwards to the greeter
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthMessage {
+  pub kind: AuthMessageKind,
+  pub message: String,
+}
+
+impl AuthMessage {
+  /// Whether the message expects an answer from the user. Info and
+  /// error messages only need to be acknowledged.
+  pub fn is_prompt(&self) -> bool {
+    matches!(
+      self.kind,
+      AuthMessageKind::Visible | AuthMessageKind::Secret
+    )
+  }
+}
+
+/// Result of a single step in the conversation with greetd
+#[derive(Debug)]
+pub enum AuthStep {
+  Message(AuthMessage),
+  Authenticated,
+}
+
+/// Where the conversation with greetd currently is. Every request is
+/// only valid in some states, see the greetd-ipc man page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversationState {
+  /// No session has been created
+  Idle,
+  /// greetd is waiting for a response to an auth message
+  AwaitingResponse,
+  /// Authentication succeeded, the session can be started
+  Authenticated,
+}
 
 pub struct GreetdClient {
-  config: Config,
   socket: UnixStream,
+  state: ConversationState,
 }
 
 impl GreetdClient {
-  pub async fn new(config: Config) -> Result<Self> {
+  pub async fn new() -> anyhow::Result<Self> {
     let sock_path =
       std::env::var("GREETD_SOCK").context("Missing env var GREETD_SOCK. Is greetd running?")?;
     let socket = UnixStream::connect(sock_path).await?;
 
-    Ok(Self { config, socket })
+    Ok(Self::with_socket(socket))
   }
 
-  pub async fn authenticate(&mut self, password: String) -> Result<()> {
-    let msg = greetd_ipc::Request::CreateSession {
-      username: self.config.user.clone(),
-    };
+  fn with_socket(socket: UnixStream) -> Self {
+    Self {
+      socket,
+      state: ConversationState::Idle,
+    }
+  }
 
-    msg.write_to(&mut self.socket).await?;
-    match greetd_ipc::Response::read_from(&mut self.socket).await? {
-      greetd_ipc::Response::Success | greetd_ipc::Response::AuthMessage { .. } => Ok(()),
-      greetd_ipc::Response::Error {
-        error_type,
-        description,
-      } => Err(anyhow::anyhow!("auth error: {error_type:?}: {description}")),
-    }?;
+  pub fn state(&self) -> ConversationState {
+    self.state
+  }
 
-    let msg = greetd_ipc::Request::PostAuthMessageResponse {
-      response: Some(password),
-    };
+  /// Starts authenticating the given user, cancelling any conversation
+  /// that is still in progress.
+  pub async fn create_session(&mut self, username: String) -> Result<AuthStep, GreetdError> {
+    if self.state != ConversationState::Idle {
+      self.cancel().await?;
+    }
 
-    msg.write_to(&mut self.socket).await?;
-    match greetd_ipc::Response::read_from(&mut self.socket).await? {
-      greetd_ipc::Response::Success | greetd_ipc::Response::AuthMessage { .. } => Ok(()),
-      greetd_ipc::Response::Error {
-        error_type,
-        description,
-      } => Err(anyhow::anyhow!("auth error: {error_type:?}: {description}")),
-    }?;
-
-    let env = self
-      .config
-      .session
-      .env
-      .iter()
-      .map(|(k, v)| format!("{k}={v}"))
-      .collect::<Vec<_>>();
-
-    let msg = greetd_ipc::Request::StartSession {
-      cmd: self.config.session.cmd.clone(),
-      env,
+    self.send(&Request::CreateSession { username }).await
+  }
+
+  /// Answers the last auth message. Info and error messages are
+  /// acknowledged with `None`.
+  pub async fn respond(&mut self, response: Option<Secret>) -> Result<AuthStep, GreetdError> {
+    if self.state != ConversationState::AwaitingResponse {
+      return Err(GreetdError::InvalidState("response", self.state));
+    }
+
+    // greetd_ipc would copy the response into an owned string and a
+    // serialization buffer, neither of which is scrambled
+    let mut frame = auth_response_frame(response.as_ref().map(Secret::expose))?;
+    let result = self.socket.write_all(&frame).await;
+    frame.scramble();
+    result.map_err(greetd_ipc::codec::Error::from)?;
+
+    self.receive().await
+  }
+
+  pub async fn start_session(&mut self, session: &SessionEntry) -> Result<(), GreetdError> {
+    if self.state != ConversationState::Authenticated {
+      return Err(GreetdError::InvalidState("session start", self.state));
+    }
+
+    let request = Request::StartSession {
+      cmd: session.cmd.clone(),
+      env: session.env.clone(),
     };
 
-    msg.write_to(&mut self.socket).await?;
-    match greetd_ipc::Response::read_from(&mut self.socket).await? {
-      greetd_ipc::Response::Success => Ok(()),
-      greetd_ipc::Response::AuthMessage { .. } => {
-        Err(anyhow::anyhow!("got auth message in start_session"))
+    match self.send(&request).await? {
+      AuthStep::Authenticated => {
+        self.state = ConversationState::Idle;
+        Ok(())
       }
-      greetd_ipc::Response::Error {
+      AuthStep::Message(_) => Err(GreetdError::UnexpectedResponse(self.state)),
+    }
+  }
+
+  /// Aborts the current conversation, so a new one can be started.
+  pub async fn cancel(&mut self) -> Result<(), GreetdError> {
+    if self.state == ConversationState::Idle {
+      return Ok(());
+    }
+
+    // Reset first, so a failed cancel doesn't leave us stuck
+    self.state = ConversationState::Idle;
+    Request::CancelSession.write_to(&mut self.socket).await?;
+
+    match Response::read_from(&mut self.socket).await? {
+      Response::Success => Ok(()),
+      Response::Error { description, .. } => Err(GreetdError::Greetd(description)),
+      Response::AuthMessage { .. } => Err(GreetdError::UnexpectedResponse(self.state)),
+    }
+  }
+
+  /// Sends a request and advances the state machine based on the response.
+  /// Errors cancel the session, since greetd doesn't accept anything else
+  /// after an error.
+  async fn send(&mut self, request: &Request) -> Result<AuthStep, GreetdError> {
+    request.write_to(&mut self.socket).await?;
+    self.receive().await
+  }
+
+  async fn receive(&mut self) -> Result<AuthStep, GreetdError> {
+    let response = Response::read_from(&mut self.socket).await?;
+
+    match response {
+      Response::Success => {
+        self.state = ConversationState::Authenticated;
+        Ok(AuthStep::Authenticated)
+      }
+      Response::AuthMessage {
+        auth_message_type,
+        auth_message,
+      } => {
+        self.state = ConversationState::AwaitingResponse;
+        Ok(AuthStep::Message(AuthMessage {
+          kind: auth_message_type.into(),
+          message: auth_message,
+        }))
+      }
+      Response::Error {
         error_type,
         description,
-      } => Err(anyhow::anyhow!("auth error: {error_type:?}: {description}")),
-    }?;
+      } => {
+        // A failed create_session leaves no session behind, but
+        // cancelling then is harmless
+        self.state = ConversationState::AwaitingResponse;
+        self.cancel().await.unwrap_or_else(|err| {
+          error!("failed to cancel session: {err}");
+        });
+
+        match error_type {
+          ErrorType::AuthError => Err(GreetdError::Auth(description)),
+          ErrorType::Error => Err(GreetdError::Greetd(description)),
+        }
+      }
+    }
+  }
+}
+
+/// Frames a `PostAuthMessageResponse` like greetd_ipc does: the length in
+/// native byte order, followed by the JSON request. The buffer has room for
+/// the response with every byte escaped, so it is never reallocated and
+/// scrambling it removes the only copy.
+fn auth_response_frame(response: Option<&str>) -> Result<Vec<u8>, greetd_ipc::codec::Error> {
+  #[derive(Serialize)]
+  #[serde(tag = "type", rename = "post_auth_message_response")]
+  struct AuthResponse<'a> {
+    response: Option<&'a str>,
+  }
+
+  let mut frame = Vec::with_capacity(128 + response.map_or(0, |response| response.len() * 6));
+  frame.extend_from_slice(&[0; 4]);
+
+  if let Err(err) = serde_json::to_writer(&mut frame, &AuthResponse { response }) {
+    frame.scramble();
+    return Err(err.into());
+  }
+
+  let len = (frame.len() - 4) as u32;
+  frame[..4].copy_from_slice(&len.to_ne_bytes());
+
+  Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::task::JoinHandle;
+
+  use super::*;
+  use crate::greeter::sessions::SessionKind;
+
+  /// Answers every request with the next scripted response, like greetd
+  /// would, and returns the requests it received.
+  fn fake_greetd(responses: Vec<Response>) -> (GreetdClient, JoinHandle<Vec<Request>>) {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    let handle = tokio::spawn(async move {
+      let mut requests = Vec::new();
+      for response in responses {
+        requests.push(Request::read_from(&mut server).await.unwrap());
+        response.write_to(&mut server).await.unwrap();
+      }
+
+      requests
+    });
+
+    (GreetdClient::with_socket(client), handle)
+  }
+
+  fn auth_message(kind: AuthMessageType, message: &str) -> Response {
+    Response::AuthMessage {
+      auth_message_type: kind,
+      auth_message: message.to_string(),
+    }
+  }
+
+  fn secret(value: &str) -> Option<Secret> {
+    Some(Secret::new(value.to_string()))
+  }
+
+  fn session() -> SessionEntry {
+    SessionEntry {
+      id: "hyprland".to_string(),
+      name: "Hyprland".to_string(),
+      kind: SessionKind::Wayland,
+      cmd: vec!["Hyprland".to_string()],
+      env: vec!["XDG_SESSION_DESKTOP=Hyprland".to_string()],
+    }
+  }
+
+  fn expect_message(step: AuthStep, kind: AuthMessageKind, message: &str) {
+    match step {
+      AuthStep::Message(auth_message) => {
+        assert_eq!(auth_message.kind, kind);
+        assert_eq!(auth_message.message, message);
+      }
+      AuthStep::Authenticated => panic!("expected {kind:?} message {message:?}"),
+    }
+  }
+
+  #[tokio::test]
+  async fn multi_step_authentication() {
+    let (mut client, server) = fake_greetd(vec![
+      auth_message(AuthMessageType::Secret, "Password: "),
+      auth_message(AuthMessageType::Info, "Your password expires in 3 days"),
+      auth_message(AuthMessageType::Visible, "Verification code: "),
+      Response::Success,
+      Response::Success,
+    ]);
+
+    let step = client.create_session("alice".to_string()).await.unwrap();
+    expect_message(step, AuthMessageKind::Secret, "Password: ");
+    assert_eq!(client.state(), ConversationState::AwaitingResponse);
+
+    let step = client.respond(secret("hunter2")).await.unwrap();
+    expect_message(
+      step,
+      AuthMessageKind::Info,
+      "Your password expires in 3 days",
+    );
+
+    let step = client.respond(None).await.unwrap();
+    expect_message(step, AuthMessageKind::Visible, "Verification code: ");
+
+    let step = client.respond(secret("123456")).await.unwrap();
+    assert!(matches!(step, AuthStep::Authenticated));
+    assert_eq!(client.state(), ConversationState::Authenticated);
+
+    client.start_session(&session()).await.unwrap();
+    assert_eq!(client.state(), ConversationState::Idle);
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 5);
+    assert!(matches!(
+      &requests[0],
+      Request::CreateSession { username } if username == "alice"
+    ));
+    assert!(matches!(
+      &requests[1],
+      Request::PostAuthMessageResponse { response: Some(response) } if response == "hunter2"
+    ));
+    assert!(matches!(
+      &requests[2],
+      Request::PostAuthMessageResponse { response: None }
+    ));
+    assert!(matches!(
+      &requests[3],
+      Request::PostAuthMessageResponse { response: Some(response) } if response == "123456"
+    ));
+    assert!(matches!(
+      &requests[4],
+      Request::StartSession { cmd, env } if *cmd == session().cmd && *env == session().env
+    ));
+  }
+
+  #[tokio::test]
+  async fn auth_errors_cancel_the_session() {
+    let (mut client, server) = fake_greetd(vec![
+      auth_message(AuthMessageType::Secret, "Password: "),
+      Response::Error {
+        error_type: ErrorType::AuthError,
+        description: "pam_authenticate: AUTH_ERR".to_string(),
+      },
+      Response::Success,
+    ]);
+
+    client.create_session("alice".to_string()).await.unwrap();
+    let err = client.respond(secret("wrong")).await.unwrap_err();
+
+    assert!(
+      matches!(&err, GreetdError::Auth(description) if description == "pam_authenticate: AUTH_ERR")
+    );
+    assert_eq!(client.state(), ConversationState::Idle);
+
+    let requests = server.await.unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(matches!(requests[2], Request::CancelSession));
+  }
+
+  #[tokio::test]
+  async fn greetd_errors_cancel_the_session() {
+    let (mut client, server) = fake_greetd(vec![
+      Response::Error {
+        error_type: ErrorType::Error,
+        description: "a session is already being configured".to_string(),
+      },
+      Response::Success,
+    ]);
+
+    let err = client
+      .create_session("alice".to_string())
+      .await
+      .unwrap_err();
+
+    assert!(matches!(err, GreetdError::Greetd(_)));
+    assert_eq!(client.state(), ConversationState::Idle);
+
+    let requests = server.await.unwrap();
+    assert!(matches!(requests[1], Request::CancelSession));
+  }
+
+  #[tokio::test]
+  async fn new_sessions_cancel_the_current_one() {
+    let (mut client, server) = fake_greetd(vec![
+      auth_message(AuthMessageType::Secret, "Password: "),
+      Response::Success,
+      auth_message(AuthMessageType::Secret, "Password: "),
+    ]);
+
+    client.create_session("alice".to_string()).await.unwrap();
+    client.create_session("bob".to_string()).await.unwrap();
+
+    let requests = server.await.unwrap();
+    assert!(matches!(requests[1], Request::CancelSession));
+    assert!(matches!(
+      &requests[2],
+      Request::CreateSession { username } if username == "bob"
+    ));
+  }
+
+  #[tokio::test]
+  async fn auth_responses_are_framed_like_greetd_ipc() {
+    for response in [Some("hunter2"), Some("\"\u{1}☕"), None] {
+      let frame = auth_response_frame(response).unwrap();
+      let request = Request::read_from(&mut frame.as_slice()).await.unwrap();
+
+      match request {
+        Request::PostAuthMessageResponse { response: parsed } => {
+          assert_eq!(parsed.as_deref(), response)
+        }
+        request => panic!("unexpected request {request:?}"),
+      }
+    }
+  }
+
+  #[test]
+  fn auth_response_frames_are_never_reallocated() {
+    // Every byte is escaped as \u00XX
+    let response = "\u{1}".repeat(64);
+    let frame = auth_response_frame(Some(&response)).unwrap();
+
+    assert!(frame.len() <= 128 + response.len() * 6);
+    assert_eq!(frame.capacity(), 128 + response.len() * 6);
+  }
+
+  #[tokio::test]
+  async fn requests_out_of_order_are_refused() {
+    let (mut client, server) = fake_greetd(vec![]);
+
+    assert!(matches!(
+      client.respond(None).await,
+      Err(GreetdError::InvalidState(
+        "response",
+        ConversationState::Idle
+      ))
+    ));
+    assert!(matches!(
+      client.start_session(&session()).await,
+      Err(GreetdError::InvalidState(
+        "session start",
+        ConversationState::Idle
+      ))
+    ));
 
-    Ok(())
+    // Nothing was sent
+    assert!(server.await.unwrap().is_empty());
   }
 }
@@ -7,21 +7,35 @@ use tokio::sync::Mutex;
 use tracing::error;
 
 use crate::{
+  background,
   battery::{BatteryState, BatterySubscription},
-  config::Config,
+  config::{self, Config},
   hyprland::HyprlandClient,
   layer_shell::LayerShellWindowBuilder,
   power::Power,
-  util::rand_string,
+  primary::{resolve_primary, OutputInfo},
+  secret::Secret,
+  util::{get_hyprland_monitor, rand_string},
 };
 
-use self::greetd::GreetdClient;
+use self::{
+  greetd::{AuthMessageKind, AuthStep, ConversationState, GreetdClient},
+  selection::Selection,
+  sessions::SessionEntry,
+  users::User,
+};
 
 mod greetd;
+mod selection;
+mod sessions;
+mod users;
 
 struct TauriState<'a> {
   config: Config,
   greetd: Option<Mutex<GreetdClient>>,
+  users: Vec<User>,
+  sessions: Vec<SessionEntry>,
+  selection: Mutex<Selection>,
   battery: BatterySubscription<'a>,
   hyprland: HyprlandClient,
   power: Power,
@@ -32,14 +46,26 @@ pub fn greet(config: Config, demo: bool) -> Result<()> {
   rt.block_on(async {
     tauri::async_runtime::set(tokio::runtime::Handle::current());
 
+    let background = config.greeter.appearance.background.clone();
     let app = tauri::Builder::default()
+      .register_uri_scheme_protocol(background::SCHEME, move |_, _| {
+        background::respond(background.as_deref())
+      })
       .invoke_handler(tauri::generate_handler![
+        get_config,
         poweroff,
         suspend,
         get_battery_state,
         window_ready,
         quit,
         submit_password,
+        respond_auth_message,
+        cancel_auth,
+        get_users,
+        get_sessions,
+        get_selection,
+        select_user,
+        select_session,
       ])
       .build(tauri::generate_context!())?;
 
@@ -53,14 +79,25 @@ pub fn greet(config: Config, demo: bool) -> Result<()> {
     let greetd_client = if demo {
       None
     } else {
-      Some(Mutex::new(greetd::GreetdClient::new(config.clone()).await?))
+      Some(Mutex::new(greetd::GreetdClient::new().await?))
     };
 
+    let users = users::list_users(&config.greeter.users).unwrap_or_else(|err| {
+      error!("failed to list users: {err}");
+      Vec::new()
+    });
+
+    let sessions = sessions::list_sessions(&config);
+    let selection = initial_selection(&config, &users, &sessions);
+
     let hyprland_client = HyprlandClient::new().await?;
 
     app.manage(TauriState {
       config: config.clone(),
       greetd: greetd_client,
+      users,
+      sessions,
+      selection: Mutex::new(selection),
       hyprland: hyprland_client,
       battery,
       power,
@@ -107,6 +144,25 @@ pub fn greet(config: Config, demo: bool) -> Result<()> {
   })
 }
 
+/// Picks the remembered user and session if they still exist, and
+/// falls back to the configured user and the first session otherwise.
+fn initial_selection(config: &Config, users: &[User], sessions: &[SessionEntry]) -> Selection {
+  let remembered = Selection::load().unwrap_or_default();
+
+  let user = remembered
+    .user
+    .filter(|name| users.iter().any(|user| user.name == *name))
+    .or_else(|| (!config.greeter.user.is_empty()).then(|| config.greeter.user.clone()))
+    .or_else(|| users.first().map(|user| user.name.clone()));
+
+  let session = remembered
+    .session
+    .filter(|id| sessions.iter().any(|session| session.id == *id))
+    .or_else(|| sessions.first().map(|session| session.id.clone()));
+
+  Selection { user, session }
+}
+
 fn create_greeter_window(app: &tauri::AppHandle, monitor: &gdk::Monitor) -> Result<()> {
   let label = format!("greeter-{}", rand_string());
 
@@ -129,39 +185,46 @@ fn create_greeter_window(app: &tauri::AppHandle, monitor: &gdk::Monitor) -> Resu
 async fn assign_primary(config: Config, app: &tauri::AppHandle) -> Result<()> {
   let state = app.state::<TauriState>();
   let hyprland_monitors = state.hyprland.get_monitors().await?;
-  let primary_index = config
-    .primary_display
-    .iter()
-    .find_map(|name| {
-      hyprland_monitors
-        .iter()
-        .position(|monitor| &monitor.name == name)
-    })
-    .unwrap_or(0);
 
   let app_handle = app.clone();
   app.run_on_main_thread(move || {
     let display = gdk::Display::default().unwrap();
 
-    for index in 0..display.n_monitors() {
-      if let Some(window_label) = display
-        .monitor(index)
-        .and_then(|monitor| unsafe { monitor.data::<String>("window-label") })
-      {
+    let windows = (0..display.n_monitors())
+      .filter_map(|index| display.monitor(index))
+      .filter_map(|monitor| {
+        let window_label = unsafe { monitor.data::<String>("window-label") }?;
         let window_label = unsafe { window_label.as_ref() }.clone();
-        let is_primary = index == primary_index as i32;
-        let window = app_handle.get_webview_window(&window_label).unwrap();
-        app_handle
-          .emit_to(&window_label, "is-primary", is_primary)
-          .unwrap();
-
-        if let Ok(gtk_window) = window.gtk_window() {
-          if is_primary {
-            gtk_window.set_keyboard_mode(gtk_layer_shell::KeyboardMode::Exclusive);
-            gtk_window.grab_focus();
-          } else {
-            gtk_window.set_keyboard_mode(gtk_layer_shell::KeyboardMode::None);
-          }
+        let info = get_hyprland_monitor(&monitor, &hyprland_monitors).map(OutputInfo::from);
+
+        Some((info.unwrap_or_default(), window_label))
+      })
+      .collect::<Vec<_>>();
+
+    let outputs = windows
+      .iter()
+      .map(|(info, _)| info.clone())
+      .collect::<Vec<_>>();
+    let primary_index = resolve_primary(&config.greeter.primary_display, &outputs);
+
+    for (index, (_, window_label)) in windows.iter().enumerate() {
+      let is_primary = Some(index) == primary_index;
+      let Some(window) = app_handle.get_webview_window(window_label) else {
+        continue;
+      };
+
+      app_handle
+        .emit_to(window_label, "is-primary", is_primary)
+        .unwrap_or_else(|err| {
+          error!("failed to emit is-primary: {err}");
+        });
+
+      if let Ok(gtk_window) = window.gtk_window() {
+        if is_primary {
+          gtk_window.set_keyboard_mode(gtk_layer_shell::KeyboardMode::Exclusive);
+          gtk_window.grab_focus();
+        } else {
+          gtk_window.set_keyboard_mode(gtk_layer_shell::KeyboardMode::None);
         }
       }
     }
@@ -216,32 +279,217 @@ async fn get_battery_state(app: tauri::AppHandle) -> Option<BatteryState> {
 }
 
 #[tauri::command]
-async fn submit_password(app: tauri::AppHandle, value: String) {
+async fn get_users(app: tauri::AppHandle) -> Vec<User> {
   let state = app.state::<TauriState>();
+  state.users.clone()
+}
 
-  // No greetd client means we're in demo mode
+#[tauri::command]
+async fn get_sessions(app: tauri::AppHandle) -> Vec<SessionEntry> {
+  let state = app.state::<TauriState>();
+  state.sessions.clone()
+}
+
+#[tauri::command]
+async fn get_config(app: tauri::AppHandle) -> config::Greeter {
+  let state = app.state::<TauriState>();
+  state.config.greeter.clone()
+}
+
+#[tauri::command]
+async fn get_selection(app: tauri::AppHandle) -> Selection {
+  let state = app.state::<TauriState>();
+  let selection = state.selection.lock().await;
+  selection.clone()
+}
+
+#[tauri::command]
+async fn select_user(app: tauri::AppHandle, name: String) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  if !state.users.iter().any(|user| user.name == name) {
+    return Err(format!("unknown user: {name}"));
+  }
+
+  // A conversation that is in progress belongs to the previous user
   if let Some(greetd) = &state.greetd {
-    let mut greetd = greetd.lock().await;
-
-    match greetd.authenticate(value).await {
-      Ok(_) => app.exit(0),
-      Err(err) => {
-        error!("failed to authenticate: {err}");
-        app
-          .emit("password-error", err.to_string())
-          .unwrap_or_else(|err| {
-            error!("failed to emit password-error: {err}");
-          });
-      }
+    greetd
+      .lock()
+      .await
+      .cancel()
+      .await
+      .map_err(|err| err.to_string())?;
+  }
+
+  state.selection.lock().await.user = Some(name);
+  Ok(())
+}
+
+#[tauri::command]
+async fn select_session(app: tauri::AppHandle, id: String) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  if !state.sessions.iter().any(|session| session.id == id) {
+    return Err(format!("unknown session: {id}"));
+  }
+
+  state.selection.lock().await.session = Some(id);
+  Ok(())
+}
+
+#[tauri::command]
+async fn submit_password(app: tauri::AppHandle, value: Secret) {
+  let state = app.state::<TauriState>();
+
+  // No greetd client means we're in demo mode
+  let Some(greetd) = &state.greetd else {
+    if value.expose() == "password" {
+      app.exit(0);
+      return;
     }
-  } else if value == "password" {
-    app.exit(0);
-  } else {
+
     tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-    app
-      .emit("password-error", "failed to authenticate".to_string())
-      .unwrap_or_else(|err| {
-        error!("failed to emit password-error: {err}");
-      });
+    emit_auth_error(&app, "failed to authenticate");
+    return;
+  };
+
+  let mut greetd = greetd.lock().await;
+
+  // The password field doubles as the input for follow-up prompts
+  let result = if greetd.state() == ConversationState::AwaitingResponse {
+    match greetd.respond(Some(value)).await {
+      Ok(step) => converse(&app, &mut greetd, step, None).await,
+      Err(err) => Err(err.into()),
+    }
+  } else {
+    start_conversation(&app, &mut greetd, value).await
+  };
+
+  result.unwrap_or_else(|err| {
+    error!("failed to authenticate: {err}");
+    emit_auth_error(&app, &err.to_string());
+  });
+}
+
+/// Answers the prompt that was last sent as an `auth-message` event.
+#[tauri::command]
+async fn respond_auth_message(app: tauri::AppHandle, value: Option<Secret>) {
+  let state = app.state::<TauriState>();
+  let Some(greetd) = &state.greetd else {
+    return;
+  };
+
+  let mut greetd = greetd.lock().await;
+  let result = match greetd.respond(value).await {
+    Ok(step) => converse(&app, &mut greetd, step, None).await,
+    Err(err) => Err(err.into()),
+  };
+
+  result.unwrap_or_else(|err| {
+    error!("failed to authenticate: {err}");
+    emit_auth_error(&app, &err.to_string());
+  });
+}
+
+#[tauri::command]
+async fn cancel_auth(app: tauri::AppHandle) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  let Some(greetd) = &state.greetd else {
+    return Ok(());
+  };
+
+  let mut greetd = greetd.lock().await;
+  greetd.cancel().await.map_err(|err| err.to_string())
+}
+
+async fn start_conversation(
+  app: &tauri::AppHandle,
+  greetd: &mut GreetdClient,
+  password: Secret,
+) -> Result<()> {
+  let state = app.state::<TauriState>();
+  let selection = state.selection.lock().await.clone();
+  let (user, _) = selected(&state, &selection)?;
+
+  let step = greetd.create_session(user).await?;
+  converse(app, greetd, step, Some(password)).await
+}
+
+/// Drives the conversation with greetd until it needs input from the user
+/// or the session was started. The password entered up front answers the
+/// first secret prompt, every other prompt is forwarded to the login window.
+async fn converse(
+  app: &tauri::AppHandle,
+  greetd: &mut GreetdClient,
+  mut step: AuthStep,
+  mut password: Option<Secret>,
+) -> Result<()> {
+  loop {
+    let message = match step {
+      AuthStep::Authenticated => return start_session(app, greetd).await,
+      AuthStep::Message(message) => message,
+    };
+
+    if message.kind == AuthMessageKind::Secret {
+      if let Some(password) = password.take() {
+        step = greetd.respond(Some(password)).await?;
+        continue;
+      }
+    }
+
+    app.emit("auth-message", &message).unwrap_or_else(|err| {
+      error!("failed to emit auth-message: {err}");
+    });
+
+    if message.is_prompt() {
+      return Ok(());
+    }
+
+    step = greetd.respond(None).await?;
   }
 }
+
+async fn start_session(app: &tauri::AppHandle, greetd: &mut GreetdClient) -> Result<()> {
+  let state = app.state::<TauriState>();
+  let selection = state.selection.lock().await.clone();
+  let (_, session) = selected(&state, &selection)?;
+
+  if let Err(err) = greetd.start_session(session).await {
+    greetd.cancel().await.unwrap_or_else(|err| {
+      error!("failed to cancel session: {err}");
+    });
+
+    return Err(err.into());
+  }
+
+  selection.save().unwrap_or_else(|err| {
+    error!("failed to save selection: {err}");
+  });
+
+  app.exit(0);
+  Ok(())
+}
+
+fn emit_auth_error(app: &tauri::AppHandle, message: &str) {
+  app
+    .emit("password-error", message.to_string())
+    .unwrap_or_else(|err| {
+      error!("failed to emit password-error: {err}");
+    });
+}
+
+fn selected<'a>(
+  state: &'a TauriState,
+  selection: &Selection,
+) -> Result<(String, &'a SessionEntry)> {
+  let user = selection
+    .user
+    .clone()
+    .ok_or_else(|| anyhow::anyhow!("no user selected"))?;
+
+  let session = selection
+    .session
+    .as_ref()
+    .and_then(|id| state.sessions.iter().find(|session| session.id == *id))
+    .ok_or_else(|| anyhow::anyhow!("no session selected"))?;
+
+  Ok((user, session))
+}
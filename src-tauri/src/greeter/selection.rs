@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Where the greeter remembers the last login. The greeter user must be
+/// able to write here, so this lives outside of any home directory.
+const SELECTION_PATH: &str = "/var/cache/dash2/greeter.toml";
+
+/// The user and session to log in with
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Selection {
+  pub user: Option<String>,
+  pub session: Option<String>,
+}
+
+impl Selection {
+  /// Loads the last successful selection, if there is one.
+  pub fn load() -> Option<Self> {
+    Self::load_from(Path::new(SELECTION_PATH))
+  }
+
+  pub fn save(&self) -> Result<()> {
+    self.save_to(Path::new(SELECTION_PATH))
+  }
+
+  fn load_from(path: &Path) -> Option<Self> {
+    let raw = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&raw).ok()
+  }
+
+  fn save_to(&self, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, toml::to_string(self)?)?;
+    info!("Saved greeter selection to {}", path.display());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn round_trips() {
+    let dir = TempDir::new("selection");
+    let path = dir.path().join("cache/greeter.toml");
+
+    let selection = Selection {
+      user: Some("alice".to_string()),
+      session: Some("hyprland".to_string()),
+    };
+    selection.save_to(&path).unwrap();
+
+    let loaded = Selection::load_from(&path).unwrap();
+    assert_eq!(loaded.user.as_deref(), Some("alice"));
+    assert_eq!(loaded.session.as_deref(), Some("hyprland"));
+  }
+
+  #[test]
+  fn keeps_missing_fields_empty() {
+    let dir = TempDir::new("selection-partial");
+    let path = dir.path().join("greeter.toml");
+
+    Selection {
+      user: Some("alice".to_string()),
+      session: None,
+    }
+    .save_to(&path)
+    .unwrap();
+
+    let loaded = Selection::load_from(&path).unwrap();
+    assert_eq!(loaded.user.as_deref(), Some("alice"));
+    assert_eq!(loaded.session, None);
+  }
+
+  #[test]
+  fn ignores_missing_or_broken_files() {
+    let dir = TempDir::new("selection-broken");
+    assert!(Selection::load_from(&dir.path().join("missing.toml")).is_none());
+
+    let path = dir.write("greeter.toml", "user = [\n");
+    assert!(Selection::load_from(&path).is_none());
+  }
+}
@@ -0,0 +1,365 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::ConfigValues;
+
+const WAYLAND_SESSIONS_DIR: &str = "/usr/share/wayland-sessions";
+const X11_SESSIONS_DIR: &str = "/usr/share/xsessions";
+
+/// Id of the session defined in the config file
+pub const CONFIG_SESSION_ID: &str = "config";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionKind {
+  Wayland,
+  X11,
+  Config,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEntry {
+  /// File name of the desktop entry, without extension
+  pub id: String,
+  pub name: String,
+  pub kind: SessionKind,
+
+  #[serde(skip)]
+  pub cmd: Vec<String>,
+  #[serde(skip)]
+  pub env: Vec<String>,
+}
+
+/// Lists the configured session, followed by all installed wayland and X11 sessions.
+pub fn list_sessions(config: &ConfigValues) -> Vec<SessionEntry> {
+  let mut sessions = Vec::new();
+
+  if !config.greeter.session.cmd.is_empty() {
+    let env = config
+      .greeter
+      .session
+      .env
+      .iter()
+      .map(|(k, v)| format!("{k}={v}"))
+      .collect();
+
+    sessions.push(SessionEntry {
+      id: CONFIG_SESSION_ID.to_string(),
+      name: config.greeter.session.cmd[0].clone(),
+      kind: SessionKind::Config,
+      cmd: config.greeter.session.cmd.clone(),
+      env,
+    });
+  }
+
+  sessions.extend(read_sessions_dir(
+    WAYLAND_SESSIONS_DIR,
+    SessionKind::Wayland,
+    config,
+  ));
+  sessions.extend(read_sessions_dir(
+    X11_SESSIONS_DIR,
+    SessionKind::X11,
+    config,
+  ));
+  sessions
+}
+
+fn read_sessions_dir(dir: &str, kind: SessionKind, config: &ConfigValues) -> Vec<SessionEntry> {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return Vec::new();
+  };
+
+  let mut sessions = entries
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+    .filter_map(|path| {
+      read_desktop_entry(&path, kind, config).unwrap_or_else(|err| {
+        warn!("skipping session file {}: {err}", path.to_string_lossy());
+        None
+      })
+    })
+    .collect::<Vec<_>>();
+
+  sessions.sort_by(|a, b| a.name.cmp(&b.name));
+  sessions
+}
+
+/// Reads a session desktop entry. Returns `None` for entries that
+/// should not be shown.
+fn read_desktop_entry(
+  path: &Path,
+  kind: SessionKind,
+  config: &ConfigValues,
+) -> Result<Option<SessionEntry>> {
+  let id = path
+    .file_stem()
+    .context("missing file name")?
+    .to_string_lossy()
+    .to_string();
+
+  let raw = std::fs::read_to_string(path)?;
+  let fields = parse_desktop_entry(&raw);
+
+  if fields.get("Hidden") == Some(&"true") || fields.get("NoDisplay") == Some(&"true") {
+    return Ok(None);
+  }
+
+  let name = fields.get("Name").context("missing Name")?.to_string();
+  let exec = fields.get("Exec").context("missing Exec")?;
+  let mut cmd = split_exec(exec).context("invalid Exec")?;
+  if cmd.is_empty() {
+    anyhow::bail!("empty Exec");
+  }
+
+  let mut env = Vec::new();
+  match kind {
+    SessionKind::Wayland => env.push("XDG_SESSION_TYPE=wayland".to_string()),
+    SessionKind::X11 => {
+      env.push("XDG_SESSION_TYPE=x11".to_string());
+      cmd = config
+        .greeter
+        .xsession_wrapper
+        .iter()
+        .cloned()
+        .chain(cmd)
+        .collect();
+    }
+    SessionKind::Config => {}
+  }
+
+  if let Some(desktop_names) = fields.get("DesktopNames") {
+    let desktop_names = desktop_names.trim_end_matches(';').replace(';', ":");
+    env.push(format!("XDG_CURRENT_DESKTOP={desktop_names}"));
+  }
+
+  env.push(format!("XDG_SESSION_DESKTOP={id}"));
+
+  Ok(Some(SessionEntry {
+    id,
+    name,
+    kind,
+    cmd,
+    env,
+  }))
+}
+
+/// Returns the keys of the `[Desktop Entry]` group, ignoring localized keys.
+fn parse_desktop_entry(raw: &str) -> HashMap<&str, &str> {
+  let mut fields = HashMap::new();
+  let mut in_entry_group = false;
+
+  for line in raw.lines().map(str::trim) {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if line.starts_with('[') {
+      in_entry_group = line == "[Desktop Entry]";
+      continue;
+    }
+
+    if !in_entry_group {
+      continue;
+    }
+
+    let Some((key, value)) = line.split_once('=') else {
+      continue;
+    };
+
+    let key = key.trim();
+    if !key.contains('[') {
+      fields.insert(key, value.trim());
+    }
+  }
+
+  fields
+}
+
+/// Splits an `Exec` value into arguments, handling quoting and
+/// dropping field codes like `%f`, which don't apply to sessions.
+fn split_exec(exec: &str) -> Option<Vec<String>> {
+  let mut args = Vec::new();
+  let mut current = String::new();
+  let mut has_arg = false;
+  let mut quoted = false;
+  let mut chars = exec.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => {
+        quoted = !quoted;
+        has_arg = true;
+      }
+      '\\' if quoted => current.push(chars.next()?),
+      '%' if !quoted => {
+        if chars.next()? == '%' {
+          current.push('%');
+        }
+      }
+      c if c.is_whitespace() && !quoted => {
+        if has_arg || !current.is_empty() {
+          args.push(std::mem::take(&mut current));
+        }
+        has_arg = false;
+      }
+      c => current.push(c),
+    }
+  }
+
+  if quoted {
+    return None;
+  }
+
+  if has_arg || !current.is_empty() {
+    args.push(current);
+  }
+
+  Some(args)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn splits_exec() {
+    let cases: &[(&str, Option<&[&str]>)] = &[
+      ("Hyprland", Some(&["Hyprland"])),
+      (
+        "  sway   --unsupported-gpu ",
+        Some(&["sway", "--unsupported-gpu"]),
+      ),
+      (
+        "env \"XDG_CURRENT_DESKTOP=My Desktop\" start",
+        Some(&["env", "XDG_CURRENT_DESKTOP=My Desktop", "start"]),
+      ),
+      (
+        "sh -c \"echo \\\"hi\\\" \\\\ \\$HOME\"",
+        Some(&["sh", "-c", "echo \"hi\" \\ $HOME"]),
+      ),
+      ("run \"\" last", Some(&["run", "", "last"])),
+      (
+        "gnome-session %f --arg %U",
+        Some(&["gnome-session", "--arg"]),
+      ),
+      ("echo 100%%", Some(&["echo", "100%"])),
+      ("echo \"%f\"", Some(&["echo", "%f"])),
+      ("", Some(&[])),
+      ("sh -c \"unterminated", None),
+      ("trailing %", None),
+      ("sh \"trailing \\", None),
+    ];
+
+    for (exec, expected) in cases {
+      let expected =
+        expected.map(|args| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+      assert_eq!(split_exec(exec), expected, "{exec:?}");
+    }
+  }
+
+  #[test]
+  fn parses_the_desktop_entry_group() {
+    let raw = "\
+# A comment
+Name=Outside of any group
+
+[Desktop Entry]
+Name = Hyprland
+Name[de]=Hyprland auf Deutsch
+Comment[fr]=Un compositeur
+Exec=Hyprland --config=a=b
+Type=Application
+
+[Desktop Action Other]
+Name=Other action
+Exec=other
+";
+
+    let fields = parse_desktop_entry(raw);
+
+    assert_eq!(
+      fields,
+      HashMap::from([
+        ("Name", "Hyprland"),
+        ("Exec", "Hyprland --config=a=b"),
+        ("Type", "Application"),
+      ])
+    );
+  }
+
+  fn read(dir: &TempDir, raw: &str, kind: SessionKind) -> Result<Option<SessionEntry>> {
+    let path = dir.write("my-session.desktop", raw);
+    read_desktop_entry(&path, kind, &ConfigValues::default())
+  }
+
+  #[test]
+  fn reads_wayland_sessions() {
+    let dir = TempDir::new("sessions-wayland");
+    let raw = "[Desktop Entry]\nName=Hyprland\nExec=Hyprland %U\nDesktopNames=Hyprland;wlroots;\n";
+
+    let session = read(&dir, raw, SessionKind::Wayland).unwrap().unwrap();
+
+    assert_eq!(session.id, "my-session");
+    assert_eq!(session.name, "Hyprland");
+    assert_eq!(session.cmd, ["Hyprland"]);
+    assert_eq!(
+      session.env,
+      [
+        "XDG_SESSION_TYPE=wayland",
+        "XDG_CURRENT_DESKTOP=Hyprland:wlroots",
+        "XDG_SESSION_DESKTOP=my-session",
+      ]
+    );
+  }
+
+  #[test]
+  fn wraps_x11_sessions() {
+    let dir = TempDir::new("sessions-x11");
+    let raw = "[Desktop Entry]\nName=i3\nExec=i3 --shmlog-size 0\n";
+
+    let session = read(&dir, raw, SessionKind::X11).unwrap().unwrap();
+
+    assert_eq!(
+      session.cmd,
+      ["startx", "/usr/bin/env", "i3", "--shmlog-size", "0"]
+    );
+    assert_eq!(
+      session.env,
+      ["XDG_SESSION_TYPE=x11", "XDG_SESSION_DESKTOP=my-session"]
+    );
+  }
+
+  #[test]
+  fn skips_hidden_sessions() {
+    let dir = TempDir::new("sessions-hidden");
+
+    for hidden in ["Hidden=true", "NoDisplay=true"] {
+      let raw = format!("[Desktop Entry]\nName=Hidden\nExec=hidden\n{hidden}\n");
+      assert!(read(&dir, &raw, SessionKind::Wayland).unwrap().is_none());
+    }
+
+    let raw = "[Desktop Entry]\nName=Shown\nExec=shown\nHidden=false\n";
+    assert!(read(&dir, raw, SessionKind::Wayland).unwrap().is_some());
+  }
+
+  #[test]
+  fn rejects_broken_sessions() {
+    let dir = TempDir::new("sessions-broken");
+
+    for raw in [
+      "[Desktop Entry]\nExec=sway\n",
+      "[Desktop Entry]\nName=Sway\n",
+      "[Desktop Entry]\nName=Sway\nExec=%f\n",
+      "[Desktop Entry]\nName=Sway\nExec=\"sway\n",
+      "Name=Sway\nExec=sway\n",
+    ] {
+      assert!(read(&dir, raw, SessionKind::Wayland).is_err(), "{raw:?}");
+    }
+  }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::Users;
+
+const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Login shells that mark system accounts which can't log in
+const NOLOGIN_SHELLS: &[&str] = &[
+  "/bin/false",
+  "/usr/bin/false",
+  "/sbin/nologin",
+  "/usr/sbin/nologin",
+  "/bin/nologin",
+  "/usr/bin/nologin",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+  pub name: String,
+  pub uid: u32,
+  /// Full name from the GECOS field, falls back to the user name
+  pub display_name: String,
+}
+
+/// Lists all users in `/etc/passwd` within the configured UID range.
+pub fn list_users(range: &Users) -> Result<Vec<User>> {
+  let raw = std::fs::read_to_string(Path::new(PASSWD_PATH))?;
+  Ok(parse_users(&raw, range))
+}
+
+fn parse_users(raw: &str, range: &Users) -> Vec<User> {
+  let mut users = raw
+    .lines()
+    .filter_map(parse_passwd_line)
+    .filter(|(user, shell)| {
+      user.uid >= range.min_uid && user.uid <= range.max_uid && !NOLOGIN_SHELLS.contains(shell)
+    })
+    .map(|(user, _)| user)
+    .collect::<Vec<_>>();
+
+  users.sort_by_key(|user| user.uid);
+  users
+}
+
+/// Parses a `name:password:uid:gid:gecos:home:shell` line, returning
+/// the user and their login shell.
+fn parse_passwd_line(line: &str) -> Option<(User, &str)> {
+  let line = line.trim();
+  if line.is_empty() || line.starts_with('#') {
+    return None;
+  }
+
+  let fields = line.split(':').collect::<Vec<_>>();
+  let [name, _, uid, _, gecos, _, shell] = fields[..] else {
+    return None;
+  };
+
+  let uid = uid.parse().ok()?;
+
+  // The first GECOS entry is the full name, the rest are contact details
+  let display_name = gecos
+    .split(',')
+    .next()
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .unwrap_or(name)
+    .to_string();
+
+  let user = User {
+    name: name.to_string(),
+    uid,
+    display_name,
+  };
+
+  Some((user, shell))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(line: &str) -> Option<(String, u32, String, &str)> {
+    parse_passwd_line(line).map(|(user, shell)| (user.name, user.uid, user.display_name, shell))
+  }
+
+  #[test]
+  fn parses_passwd_lines() {
+    let cases = [
+      (
+        "alice:x:1000:1000:Alice Liddell,Room 1,,:/home/alice:/bin/bash",
+        Some(("alice", 1000, "Alice Liddell", "/bin/bash")),
+      ),
+      (
+        "  bob:x:1001:1001:  Bob  :/home/bob:/usr/bin/zsh  ",
+        Some(("bob", 1001, "Bob", "/usr/bin/zsh")),
+      ),
+      // Without a full name, the user name is shown
+      (
+        "carol:x:1002:1002::/home/carol:/bin/sh",
+        Some(("carol", 1002, "carol", "/bin/sh")),
+      ),
+      (
+        "dave:x:1003:1003:,,,:/home/dave:/bin/sh",
+        Some(("dave", 1003, "dave", "/bin/sh")),
+      ),
+      ("", None),
+      ("# alice:x:1000:1000::/home/alice:/bin/bash", None),
+      ("eve:x:1004:1004::/home/eve", None),
+      ("eve:x:1004:1004::/home/eve:/bin/sh:extra", None),
+      ("eve:x:-1:1004::/home/eve:/bin/sh", None),
+      ("eve:x:eve:1004::/home/eve:/bin/sh", None),
+    ];
+
+    for (line, expected) in cases {
+      let expected = expected.map(|(name, uid, display_name, shell)| {
+        (name.to_string(), uid, display_name.to_string(), shell)
+      });
+      assert_eq!(parse(line), expected, "{line:?}");
+    }
+  }
+
+  #[test]
+  fn lists_users_that_can_log_in() {
+    let raw = "\
+root:x:0:0:root:/root:/bin/bash
+nobody:x:65534:65534:Nobody:/:/usr/sbin/nologin
+bob:x:1001:1001:Bob:/home/bob:/bin/bash
+svc:x:1500:1500:Service:/var/lib/svc:/bin/false
+alice:x:1000:1000:Alice:/home/alice:/bin/zsh
+old:x:999:999:Old:/home/old:/bin/bash
+far:x:2001:2001:Far:/home/far:/bin/bash
+broken line
+";
+
+    let range = Users {
+      min_uid: 1000,
+      max_uid: 2000,
+    };
+    let names = parse_users(raw, &range)
+      .into_iter()
+      .map(|user| user.name)
+      .collect::<Vec<_>>();
+
+    assert_eq!(names, ["alice", "bob"]);
+  }
+}
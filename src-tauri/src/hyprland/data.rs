@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-use super::types::{MonitorId, WorkspaceId};
+use super::types::{Address, MonitorId, WorkspaceId};
 
 /// This struct holds a basic identifier for a workspace often used in other structs
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -43,6 +45,12 @@ pub struct Monitor {
   pub name: String,
   /// The monitor's description
   pub description: String,
+  /// The monitor's make
+  #[serde(default)]
+  pub make: Option<String>,
+  /// The monitor's model
+  #[serde(default)]
+  pub model: Option<String>,
   /// The monitor width (in pixels)
   pub width: u16,
   /// The monitor height (in pixels)
@@ -71,3 +79,603 @@ pub struct Monitor {
   /// VRR state
   pub vrr: bool,
 }
+
+/// This struct holds information for a workspace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+  /// The workspace Id
+  pub id: WorkspaceId,
+  /// The workspace's name
+  pub name: String,
+  /// The monitor the workspace is on
+  pub monitor: String,
+  /// The monitor id
+  #[serde(rename = "monitorID")]
+  pub monitor_id: MonitorId,
+  /// The amount of windows in the workspace
+  pub windows: u16,
+  /// A bool that shows if there is a fullscreen window in the workspace
+  #[serde(rename = "hasfullscreen")]
+  pub fullscreen: bool,
+  /// The last window's [Address]
+  #[serde(rename = "lastwindow")]
+  pub last_window: Address,
+  /// The last window's title
+  #[serde(rename = "lastwindowtitle")]
+  pub last_window_title: String,
+}
+
+/// This enum holds the fullscreen states of a client
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, PartialEq, Eq, Copy, Default)]
+#[repr(u8)]
+pub enum FullscreenMode {
+  /// The client is not fullscreen
+  #[default]
+  None = 0,
+  /// The client is maximized
+  Maximized = 1,
+  /// The client is fullscreen
+  Fullscreen = 2,
+  /// The client is maximized and fullscreen
+  MaximizedFullscreen = 3,
+}
+
+/// Older hyprland versions report fullscreen as a bool, newer ones as a mode
+fn deserialize_fullscreen<'de, D>(deserializer: D) -> Result<FullscreenMode, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Fullscreen {
+    Bool(bool),
+    Mode(FullscreenMode),
+  }
+
+  Ok(match Fullscreen::deserialize(deserializer)? {
+    Fullscreen::Bool(true) => FullscreenMode::Fullscreen,
+    Fullscreen::Bool(false) => FullscreenMode::None,
+    Fullscreen::Mode(mode) => mode,
+  })
+}
+
+/// This struct holds information for a client/window
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+  /// The client's [`Address`][crate::hyprland::types::Address]
+  pub address: Address,
+  /// Whether the client is mapped
+  #[serde(default)]
+  pub mapped: bool,
+  /// Whether the client is hidden
+  #[serde(default)]
+  pub hidden: bool,
+  /// The window location
+  pub at: (i16, i16),
+  /// The window size
+  pub size: (i16, i16),
+  /// The workspace its on
+  pub workspace: WorkspaceBasic,
+  /// Is this window floating?
+  pub floating: bool,
+  /// Is this window pseudo tiled?
+  #[serde(default)]
+  pub pseudo: bool,
+  /// The monitor the window is on
+  pub monitor: MonitorId,
+  /// The window class
+  pub class: String,
+  /// The window title
+  pub title: String,
+  /// The initial window class
+  #[serde(rename = "initialClass")]
+  pub initial_class: String,
+  /// The initial window title
+  #[serde(rename = "initialTitle")]
+  pub initial_title: String,
+  /// The process Id of the client
+  pub pid: i32,
+  /// Is this window running under XWayland?
+  pub xwayland: bool,
+  /// Is this window pinned?
+  pub pinned: bool,
+  /// The fullscreen state of the window
+  #[serde(deserialize_with = "deserialize_fullscreen")]
+  pub fullscreen: FullscreenMode,
+  /// The fullscreen state the client itself requested
+  #[serde(rename = "fullscreenClient", default)]
+  pub fullscreen_client: Option<FullscreenMode>,
+  /// The windows grouped with this one
+  #[serde(default)]
+  pub grouped: Vec<Address>,
+  /// The tags applied to this window
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// The window this window is swallowing
+  #[serde(default)]
+  pub swallowing: Option<Address>,
+  /// How recently the window was focused, 0 being the most recent
+  #[serde(rename = "focusHistoryID", default)]
+  pub focus_history_id: i8,
+}
+
+/// This struct holds information about a layer surface/client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LayerClient {
+  /// The layer's [`Address`][crate::hyprland::types::Address]
+  pub address: Address,
+  /// The layer's x position
+  pub x: i32,
+  /// The layer's y position
+  pub y: i32,
+  /// The layer's width
+  pub w: i32,
+  /// The layer's height
+  pub h: i32,
+  /// The layer's namespace
+  pub namespace: String,
+  /// The process Id of the layer
+  #[serde(default)]
+  pub pid: i32,
+}
+
+/// This struct holds all the layer surfaces of a display, keyed by layer level
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LayerDisplay {
+  /// The layers, keyed by level (0 is background, 3 is overlay)
+  pub levels: HashMap<String, Vec<LayerClient>>,
+}
+
+/// This struct holds all the layer surfaces, keyed by monitor name
+pub type Layers = HashMap<String, LayerDisplay>;
+
+/// This struct holds information about a mouse device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mouse {
+  /// The mouse's address
+  pub address: Address,
+  /// The mouse's name
+  pub name: String,
+  /// The default acceleration of the mouse
+  #[serde(rename = "defaultSpeed", default)]
+  pub default_speed: f64,
+}
+
+/// This struct holds information about a keyboard device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Keyboard {
+  /// The keyboard's address
+  pub address: Address,
+  /// The keyboard's name
+  pub name: String,
+  /// The keyboard rules
+  pub rules: String,
+  /// The keyboard model
+  pub model: String,
+  /// The layout of the keyboard
+  pub layout: String,
+  /// The keyboard variant
+  pub variant: String,
+  /// The keyboard options
+  pub options: String,
+  /// The active keymap
+  pub active_keymap: String,
+  /// Whether this is the main keyboard
+  #[serde(default)]
+  pub main: bool,
+}
+
+/// This struct holds information about a tablet, tablet pad or tablet tool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tablet {
+  /// The tablet's address
+  pub address: Address,
+  /// The tablet type (`tabletPad` or `tabletTool`), if any
+  #[serde(rename = "type", default)]
+  pub tablet_type: Option<String>,
+  /// The tablet this pad or tool belongs to
+  #[serde(rename = "belongsTo", default)]
+  pub belongs_to: Option<TabletBelongsTo>,
+  /// The tablet's name
+  #[serde(default)]
+  pub name: Option<String>,
+}
+
+/// This struct holds the tablet a pad or tool belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TabletBelongsTo {
+  /// The tablet's address
+  pub address: Address,
+  /// The tablet's name
+  pub name: String,
+}
+
+/// This struct holds information about a touch device or switch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputDevice {
+  /// The device's address
+  pub address: Address,
+  /// The device's name
+  pub name: String,
+}
+
+/// This struct holds all the input devices
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Devices {
+  /// All the mice
+  pub mice: Vec<Mouse>,
+  /// All the keyboards
+  pub keyboards: Vec<Keyboard>,
+  /// All the tablets
+  pub tablets: Vec<Tablet>,
+  /// All the touch devices
+  #[serde(default)]
+  pub touch: Vec<InputDevice>,
+  /// All the switches
+  #[serde(default)]
+  pub switches: Vec<InputDevice>,
+}
+
+/// This struct holds version information of the running hyprland instance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+  /// The git branch hyprland was built from
+  pub branch: String,
+  /// The git commit hyprland was built from
+  pub commit: String,
+  /// Whether the working tree had uncommitted changes
+  pub dirty: bool,
+  /// The commit message
+  pub commit_message: String,
+  /// The commit date
+  #[serde(default)]
+  pub commit_date: String,
+  /// The latest tag
+  pub tag: String,
+  /// The number of commits
+  #[serde(default)]
+  pub commits: String,
+  /// The flags hyprland was built with
+  #[serde(default)]
+  pub flags: Vec<String>,
+}
+
+/// This struct holds the cursor position
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorPosition {
+  /// The x position of the cursor
+  pub x: i64,
+  /// The y position of the cursor
+  pub y: i64,
+}
+
+/// This struct holds information about a keybind
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Bind {
+  /// Whether the bind works while the session is locked
+  pub locked: bool,
+  /// Whether this is a mouse bind
+  pub mouse: bool,
+  /// Whether the bind triggers on release
+  pub release: bool,
+  /// Whether the bind repeats while held
+  pub repeat: bool,
+  /// Whether the bind triggers on a long press
+  #[serde(rename = "longPress", default)]
+  pub long_press: bool,
+  /// Whether the key event is passed on to the focused window
+  pub non_consuming: bool,
+  /// Whether the bind has a description
+  #[serde(default)]
+  pub has_description: bool,
+  /// The modifier mask
+  pub modmask: u16,
+  /// The submap the bind belongs to
+  pub submap: String,
+  /// The key of the bind
+  pub key: String,
+  /// The keycode of the bind
+  pub keycode: i16,
+  /// Whether the bind catches all keys
+  #[serde(default)]
+  pub catch_all: bool,
+  /// The description of the bind
+  #[serde(default)]
+  pub description: String,
+  /// The dispatcher the bind runs
+  pub dispatcher: String,
+  /// The argument passed to the dispatcher
+  pub arg: String,
+}
+
+/// This struct holds information about an animation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Animation {
+  /// The animation name
+  pub name: String,
+  /// Whether the animation is overridden in the config
+  pub overridden: bool,
+  /// The bezier curve used by the animation
+  pub bezier: String,
+  /// Whether the animation is enabled
+  pub enabled: bool,
+  /// The animation speed
+  pub speed: f32,
+  /// The animation style
+  pub style: String,
+}
+
+/// This struct holds the name of a bezier curve
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Bezier {
+  /// The name of the bezier curve
+  pub name: String,
+}
+
+/// This struct holds all animations and bezier curves
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Animations(pub Vec<Animation>, pub Vec<Bezier>);
+
+/// This enum holds gaps, which are either uniform or per side
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Gaps {
+  /// The same gap on all sides
+  Uniform(i64),
+  /// Gaps as (top, right, bottom, left)
+  Sides(Vec<i64>),
+}
+
+/// This struct holds a workspace rule
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceRule {
+  /// The workspace selector the rule applies to
+  #[serde(rename = "workspaceString")]
+  pub workspace_string: String,
+  /// The monitor the workspace is bound to
+  #[serde(default)]
+  pub monitor: Option<String>,
+  /// Whether the workspace is the default for its monitor
+  #[serde(default)]
+  pub default: Option<bool>,
+  /// Whether the workspace is persistent
+  #[serde(default)]
+  pub persistent: Option<bool>,
+  /// The inner gaps
+  #[serde(rename = "gapsIn", default)]
+  pub gaps_in: Option<Gaps>,
+  /// The outer gaps
+  #[serde(rename = "gapsOut", default)]
+  pub gaps_out: Option<Gaps>,
+  /// The border size
+  #[serde(rename = "borderSize", default)]
+  pub border_size: Option<i64>,
+  /// Whether borders are drawn
+  #[serde(default)]
+  pub border: Option<bool>,
+  /// Whether shadows are drawn
+  #[serde(default)]
+  pub shadow: Option<bool>,
+  /// Whether corners are rounded
+  #[serde(default)]
+  pub rounding: Option<bool>,
+  /// Whether decorations are drawn
+  #[serde(default)]
+  pub decorate: Option<bool>,
+  /// The default name of the workspace
+  #[serde(rename = "defaultName", default)]
+  pub default_name: Option<String>,
+  /// The command run when the workspace is created empty
+  #[serde(rename = "onCreatedEmptyCmd", default)]
+  pub on_created_empty_cmd: Option<String>,
+}
+
+/// Replies of `hyprctl -j`, from Hyprland 0.42 unless noted otherwise
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse<T: serde::de::DeserializeOwned>(json: &str) -> T {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn monitors() {
+    let monitors: Vec<Monitor> = parse(include_str!("fixtures/monitors.json"));
+
+    assert_eq!(monitors.len(), 2);
+    assert_eq!(monitors[0].name, "eDP-1");
+    assert_eq!(monitors[0].make.as_deref(), Some("BOE"));
+    assert_eq!(monitors[0].reserved, (0, 0, 0, 40));
+    assert!(monitors[0].focused);
+    assert_eq!(monitors[1].description, "Dell Inc. DELL U2720Q 8LQ2NR2");
+    assert_eq!(monitors[1].x, -2560);
+    assert_eq!(monitors[1].transform, Transforms::Normal90);
+    assert_eq!(
+      monitors[1].active_workspace,
+      WorkspaceBasic {
+        id: 4,
+        name: "4".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn workspaces() {
+    let workspaces: Vec<Workspace> = parse(include_str!("fixtures/workspaces.json"));
+
+    assert_eq!(workspaces.len(), 3);
+    assert_eq!(workspaces[0].last_window, Address::new("0x5a4d8b0c7f20"));
+    assert_eq!(workspaces[1].id, -98);
+    assert_eq!(workspaces[1].name, "special:magic");
+    assert_eq!(workspaces[1].monitor_id, 1);
+    assert_eq!(workspaces[2].windows, 0);
+  }
+
+  #[test]
+  fn clients() {
+    let clients: Vec<Client> = parse(include_str!("fixtures/clients.json"));
+
+    assert_eq!(clients.len(), 3);
+    assert_eq!(clients[0].fullscreen, FullscreenMode::None);
+    assert_eq!(clients[0].swallowing, Some(Address::new("0x0")));
+
+    assert_eq!(clients[1].title, "Hyprland Wiki, IPC — Mozilla Firefox");
+    assert_eq!(clients[1].fullscreen, FullscreenMode::MaximizedFullscreen);
+    assert_eq!(
+      clients[1].fullscreen_client,
+      Some(FullscreenMode::Fullscreen)
+    );
+    assert_eq!(clients[1].grouped.len(), 2);
+    assert_eq!(clients[1].tags, ["browser*"]);
+
+    assert_eq!(clients[2].workspace.id, -98);
+    assert_eq!(clients[2].at, (-2160, 300));
+    assert!(clients[2].xwayland && clients[2].pinned && clients[2].floating);
+    assert_eq!(clients[2].focus_history_id, -1);
+  }
+
+  #[test]
+  fn clients_with_fullscreen_bool() {
+    // Hyprland 0.40 and older
+    let clients: Vec<Client> = parse(include_str!("fixtures/clients-0.40.json"));
+
+    assert_eq!(clients[0].fullscreen, FullscreenMode::Fullscreen);
+    assert_eq!(clients[0].fullscreen_client, None);
+    assert!(clients[0].tags.is_empty());
+    assert!(!clients[0].pseudo);
+  }
+
+  #[test]
+  fn layers() {
+    let layers: Layers = parse(include_str!("fixtures/layers.json"));
+
+    let edp = &layers["eDP-1"].levels;
+    assert_eq!(edp["0"][0].namespace, "hyprpaper");
+    assert!(edp["1"].is_empty());
+    assert_eq!(
+      edp["2"]
+        .iter()
+        .map(|layer| layer.namespace.as_str())
+        .collect::<Vec<_>>(),
+      ["dash2-control", "dash2-workspace"]
+    );
+    assert_eq!(edp["2"][0].y, 963);
+
+    let dp = &layers["DP-3"].levels;
+    assert_eq!(dp["3"][0].x, -2560);
+    assert_eq!(dp["3"][0].pid, 1311);
+  }
+
+  #[test]
+  fn devices() {
+    let devices: Devices = parse(include_str!("fixtures/devices.json"));
+
+    assert_eq!(devices.mice.len(), 2);
+    assert_eq!(devices.mice[1].default_speed, -0.5);
+
+    assert_eq!(devices.keyboards[0].layout, "us,de");
+    assert_eq!(devices.keyboards[0].active_keymap, "English (US)");
+    assert!(devices.keyboards[0].main);
+    assert!(!devices.keyboards[1].main);
+
+    let pad = &devices.tablets[0];
+    assert_eq!(pad.tablet_type.as_deref(), Some("tabletPad"));
+    assert_eq!(pad.name, None);
+    assert_eq!(
+      pad.belongs_to.as_ref().map(|tablet| tablet.name.as_str()),
+      Some("wacom-intuos-s-pen")
+    );
+
+    let tablet = &devices.tablets[2];
+    assert_eq!(tablet.tablet_type, None);
+    assert_eq!(tablet.name.as_deref(), Some("wacom-intuos-s-pen"));
+
+    assert_eq!(devices.touch[0].name, "elan9008:00-04f3:2c82");
+    assert_eq!(devices.switches[0].name, "Lid Switch");
+  }
+
+  #[test]
+  fn version() {
+    let version: Version = parse(include_str!("fixtures/version.json"));
+
+    assert_eq!(version.tag, "v0.42.0");
+    assert_eq!(version.commit, "9a09eac79b85c846e3a865a9078a3f8ff65a9259");
+    assert_eq!(version.commits, "5082");
+    assert!(!version.dirty);
+    assert!(version.flags.is_empty());
+  }
+
+  #[test]
+  fn binds() {
+    let binds: Vec<Bind> = parse(include_str!("fixtures/binds.json"));
+
+    assert_eq!(binds.len(), 4);
+    assert_eq!(binds[0].modmask, 64);
+    assert_eq!(binds[0].dispatcher, "exec");
+    assert_eq!(binds[0].arg, "kitty");
+
+    assert!(binds[1].locked && binds[1].repeat && binds[1].has_description);
+    assert_eq!(binds[1].description, "Raise volume");
+
+    assert!(binds[2].mouse);
+    assert_eq!(binds[2].key, "mouse:272");
+
+    assert_eq!(binds[3].submap, "resize");
+    assert_eq!(binds[3].keycode, 9);
+  }
+
+  #[test]
+  fn cursor_position() {
+    let position: CursorPosition = parse(include_str!("fixtures/cursorpos.json"));
+
+    assert_eq!(position, CursorPosition { x: 1128, y: 752 });
+  }
+
+  #[test]
+  fn animations() {
+    let Animations(animations, beziers) = parse(include_str!("fixtures/animations.json"));
+
+    assert_eq!(animations.len(), 3);
+    assert_eq!(animations[1].name, "windows");
+    assert!(animations[1].overridden);
+    assert_eq!(animations[1].style, "popin 80%");
+    assert_eq!(animations[1].speed, 4.0);
+    assert_eq!(
+      beziers,
+      [
+        Bezier {
+          name: "overshot".to_string()
+        },
+        Bezier {
+          name: "linear".to_string()
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn workspace_rules() {
+    let rules: Vec<WorkspaceRule> = parse(include_str!("fixtures/workspacerules.json"));
+
+    assert_eq!(rules.len(), 4);
+    assert_eq!(rules[0].monitor.as_deref(), Some("eDP-1"));
+    assert_eq!(rules[0].default, Some(true));
+    assert_eq!(rules[0].gaps_in, None);
+
+    assert_eq!(rules[1].gaps_out, Some(Gaps::Sides(vec![40, 80, 40, 80])));
+    assert_eq!(rules[1].on_created_empty_cmd.as_deref(), Some("btop"));
+
+    assert_eq!(rules[2].border, Some(false));
+    assert_eq!(rules[2].rounding, Some(false));
+
+    assert_eq!(rules[3].persistent, Some(true));
+    assert_eq!(rules[3].border_size, Some(3));
+    assert_eq!(rules[3].default_name.as_deref(), Some("web"));
+  }
+
+  #[test]
+  fn uniform_gaps() {
+    assert_eq!(parse::<Gaps>("5"), Gaps::Uniform(5));
+  }
+}
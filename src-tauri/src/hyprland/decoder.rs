@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use tracing::{error, warn};
+
+use super::events::{parse_event, Event, EventParseError};
+
+/// Splits the raw event socket stream into lines and parses them. Bytes
+/// after the last newline are kept until the rest of the line arrives.
+#[derive(Debug, Default)]
+pub struct EventDecoder {
+  buf: Vec<u8>,
+  unknown_events: HashSet<String>,
+}
+
+impl EventDecoder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feeds newly read bytes into the decoder and returns all events from
+  /// lines that are complete now. Lines that can't be parsed are skipped.
+  pub fn decode(&mut self, bytes: &[u8]) -> Vec<Event> {
+    self.buf.extend_from_slice(bytes);
+
+    let Some(last_newline) = self.buf.iter().rposition(|b| *b == b'\n') else {
+      return Vec::new();
+    };
+
+    let rest = self.buf.split_off(last_newline + 1);
+    let complete = std::mem::replace(&mut self.buf, rest);
+
+    complete
+      .split(|b| *b == b'\n')
+      .filter(|line| !line.is_empty())
+      .filter_map(|line| self.decode_line(line))
+      .collect()
+  }
+
+  fn decode_line(&mut self, line: &[u8]) -> Option<Event> {
+    let line = match std::str::from_utf8(line) {
+      Ok(line) => line,
+      Err(err) => {
+        error!("Skipping invalid utf-8 in event: {err}");
+        return None;
+      }
+    };
+
+    match parse_event(line) {
+      Ok(event) => event,
+      Err(EventParseError::UnknownEvent(name)) => {
+        // Only warn once per event, newer hyprland versions might send
+        // a lot of events we don't know about
+        if self.unknown_events.insert(name.clone()) {
+          warn!("Skipping unknown event: {name}");
+        }
+
+        None
+      }
+      Err(err) => {
+        error!("Skipping event: {err}");
+        None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+  };
+
+  use super::*;
+  use crate::hyprland::{
+    events::{WindowTitleEventData, WorkspaceEventData},
+    types::Address,
+  };
+
+  /// Writes each chunk to one end of a socket pair and decodes what the
+  /// other end reads, so every chunk arrives as a read of its own.
+  async fn decode_chunks(decoder: &mut EventDecoder, chunks: &[&[u8]]) -> Vec<Vec<Event>> {
+    let (mut writer, mut reader) = UnixStream::pair().unwrap();
+    let mut buf = [0; 4096];
+    let mut decoded = Vec::new();
+
+    for chunk in chunks {
+      writer.write_all(chunk).await.unwrap();
+
+      let mut read = 0;
+      while read < chunk.len() {
+        read += reader.read(&mut buf[read..]).await.unwrap();
+      }
+
+      decoded.push(decoder.decode(&buf[..read]));
+    }
+
+    decoded
+  }
+
+  fn title(address: &str, title: &str) -> Event {
+    Event::WindowTitle(WindowTitleEventData {
+      window_address: Address::fmt_new(address),
+      window_title: title.to_string(),
+    })
+  }
+
+  #[tokio::test]
+  async fn joins_events_split_across_reads() {
+    let mut decoder = EventDecoder::new();
+    let decoded = decode_chunks(
+      &mut decoder,
+      &[
+        b"workspacev2>>3,3\nactivewin",
+        b"dowv2>>5a4d8b0c7f20",
+        b"\nsubmap>>resize\nsubmap>>",
+        b"\n",
+      ],
+    )
+    .await;
+
+    assert_eq!(
+      decoded,
+      [
+        vec![Event::Workspace(WorkspaceEventData {
+          workspace_id: 3,
+          workspace_name: "3".to_string(),
+        })],
+        vec![],
+        vec![
+          Event::ActiveWindow(Some(Address::fmt_new("5a4d8b0c7f20"))),
+          Event::SubMap("resize".to_string()),
+        ],
+        vec![Event::SubMap(String::new())],
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn joins_characters_split_across_reads() {
+    let line = "windowtitlev2>>5a4d8b0c7f20,café ☕, second\n".as_bytes();
+    // Inside the three bytes of the cup
+    let split = line.len() - " second\n".len() - 3;
+
+    let mut decoder = EventDecoder::new();
+    let decoded = decode_chunks(&mut decoder, &[&line[..split], &line[split..]]).await;
+
+    assert_eq!(
+      decoded,
+      [vec![], vec![title("5a4d8b0c7f20", "café ☕, second")]]
+    );
+  }
+
+  #[tokio::test]
+  async fn skips_invalid_lines() {
+    let mut decoder = EventDecoder::new();
+    let decoded = decode_chunks(
+      &mut decoder,
+      &[
+        b"windowtitlev2>>5a4d8b0c7f20,\xff\xfe\n",
+        b"no separator\nfullscreen>>2\nfutureevent>>1\nfutureevent>>2\n",
+        b"windowtitlev2>>5a4d8b0c7f20,fine\n",
+      ],
+    )
+    .await;
+
+    assert_eq!(
+      decoded,
+      [vec![], vec![], vec![title("5a4d8b0c7f20", "fine")]]
+    );
+    assert_eq!(
+      decoder.unknown_events,
+      HashSet::from(["futureevent".to_string()])
+    );
+  }
+
+  #[tokio::test]
+  async fn keeps_incomplete_lines() {
+    let mut decoder = EventDecoder::new();
+    let decoded = decode_chunks(&mut decoder, &[b"submap>>resize"]).await;
+
+    assert_eq!(decoded, [vec![]]);
+    assert_eq!(decoder.buf, b"submap>>resize");
+  }
+}
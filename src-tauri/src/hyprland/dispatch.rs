@@ -172,16 +172,16 @@ pub enum WorkspaceIdentifierWithSpecial<'a> {
   /// The workspace Id
   Id(WorkspaceId),
   /// The workspace relative to the current workspace
-  #[display("{}", "format_relative(*_0, \"\")")]
+  #[display("{}", format_relative(*_0, ""))]
   Relative(i32),
   /// The workspace on the monitor relative to the current workspace
-  #[display("{}", "format_relative(*_0, \"m\")")]
+  #[display("{}", format_relative(*_0, "m"))]
   RelativeMonitor(i32),
   /// The workspace on the monitor relative to the current workspace, including empty workspaces
-  #[display("{}", "format_relative(*_0, \"r\")")]
+  #[display("{}", format_relative(*_0, "r"))]
   RelativeMonitorIncludingEmpty(i32),
   /// The open workspace relative to the current workspace
-  #[display("{}", "format_relative(*_0, \"e\")")]
+  #[display("{}", format_relative(*_0, "e"))]
   RelativeOpen(i32),
   /// The previous Workspace
   #[display("previous")]
@@ -193,7 +193,7 @@ pub enum WorkspaceIdentifierWithSpecial<'a> {
   #[display("name:{_0}")]
   Name(&'a str),
   /// The special workspace
-  #[display("special{}", "format_special_workspace_ident(_0)")]
+  #[display("special{}", format_special_workspace_ident(_0))]
   Special(Option<&'a str>),
 }
 
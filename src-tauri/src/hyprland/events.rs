@@ -1,10 +1,7 @@
-use std::{collections::HashSet, sync::Mutex};
+use std::str::FromStr;
 
-use anyhow::{anyhow, bail};
-use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use thiserror::Error as ThisError;
 
 use super::types::{Address, WorkspaceId};
 
@@ -94,12 +91,43 @@ pub struct ScreencastEventData {
 pub struct WindowMoveEvent {
   /// Window address
   pub window_address: Address,
+  /// The workspace Id
+  pub workspace_id: WorkspaceId,
   /// The workspace name
   pub workspace_name: String,
 }
 
+/// Event data for moving a workspace to another monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceMoveEventData {
+  /// Workspace Id
+  pub workspace_id: WorkspaceId,
+  /// Workspace name
+  pub workspace_name: String,
+  /// The monitor the workspace was moved to
+  pub monitor_name: String,
+}
+
+/// Event data for changing the special workspace of a monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveSpecialEventData {
+  /// The special workspace, or `None` if it was closed
+  pub workspace: Option<WorkspaceType>,
+  /// The monitor name
+  pub monitor_name: String,
+}
+
+/// Event data for pinning or unpinning a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowPinEventData {
+  /// The window address
+  pub window_address: Address,
+  /// The pin state
+  pub is_pinned: bool,
+}
+
 /// The data for the event executed when opening a new window
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct WindowOpenEvent {
   /// Window address
   pub window_address: Address,
@@ -131,13 +159,14 @@ pub struct State {
   pub fullscreen_state: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Event {
   Workspace(WorkspaceEventData),
   DestroyWorkspace(WorkspaceEventData),
   CreateWorkspace(WorkspaceEventData),
-  MoveWorkspace(MonitorEventData),
+  MoveWorkspace(WorkspaceMoveEventData),
   RenameWorkspace(WorkspaceEventData),
+  ActiveSpecial(ActiveSpecialEventData),
   ActiveWindow(Option<Address>),
   FocusedMon(MonitorEventData),
   Fullscreen(bool),
@@ -155,356 +184,435 @@ pub enum Event {
   Minimize(MinimizeEventData),
   WindowTitle(WindowTitleEventData),
   Screencast(ScreencastEventData),
+  Pin(WindowPinEventData),
+  ConfigReloaded,
+  /// Emitted after reconnecting to the event socket. Events may have been
+  /// missed while disconnected, so any derived state should be queried again.
+  Resync,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
-enum ParsedEventType {
-  Workspace,
-  WorkspaceV2,
-  DestroyWorkspace,
-  DestroyWorkspaceV2,
-  CreateWorkspace,
-  CreateWorkspaceV2,
-  MoveWorkspace,
-  RenameWorkspace,
-  ActiveWindow,
-  ActiveWindowV2,
-  FocusedMon,
-  Fullscreen,
-  MonitorAdded,
-  MonitorRemoved,
-  OpenWindow,
-  CloseWindow,
-  MoveWindow,
-  ActiveLayout,
-  SubMap,
-  OpenLayer,
-  CloseLayer,
-  ChangeFloatingMode,
-  Urgent,
-  Minimize,
-  WindowTitle,
-  WindowTitleV2,
-  Screencast,
-  Unknown,
+#[derive(Debug, ThisError)]
+pub enum EventParseError {
+  #[error("missing `>>` separator in event: {0}")]
+  MissingSeparator(String),
+  #[error("unknown event: {0}")]
+  UnknownEvent(String),
+  #[error("{event}: missing argument {index}")]
+  MissingArgument { event: String, index: usize },
+  #[error("{event}: invalid argument `{value}`")]
+  InvalidArgument { event: String, value: String },
 }
 
-/// All the recognized events
-static EVENT_SET: Lazy<Box<[(ParsedEventType, Regex)]>> = Lazy::new(|| {
-  [
-    (
-      ParsedEventType::Workspace,
-      r"\bworkspace>>(?P<workspace>.*)",
-    ),
-    (
-      ParsedEventType::WorkspaceV2,
-      r"\bworkspacev2>>(?P<id>.*),(?P<name>.*)",
-    ),
-    (
-      ParsedEventType::DestroyWorkspace,
-      r"destroyworkspace>>(?P<id>.*)",
-    ),
-    (
-      ParsedEventType::DestroyWorkspaceV2,
-      r"destroyworkspacev2>>(?P<id>.*),(?P<name>.*)",
-    ),
-    (
-      ParsedEventType::CreateWorkspace,
-      r"createworkspace>>(?P<workspace>.*)",
-    ),
-    (
-      ParsedEventType::CreateWorkspaceV2,
-      r"createworkspacev2>>(?P<id>.*),(?P<name>.*)",
-    ),
-    (
-      ParsedEventType::MoveWorkspace,
-      r"moveworkspace>>(?P<workspace>.*),(?P<monitor>.*)",
-    ),
-    (
-      ParsedEventType::RenameWorkspace,
-      r"renameworkspace>>(?P<id>.*),(?P<name>.*)",
-    ),
-    (
-      ParsedEventType::FocusedMon,
-      r"focusedmon>>(?P<monitor>.*),(?P<workspace>.*)",
-    ),
-    (
-      ParsedEventType::ActiveWindow,
-      r"activewindow>>(?P<class>.*?),(?P<title>.*)",
-    ),
-    (
-      ParsedEventType::ActiveWindowV2,
-      r"activewindowv2>>(?P<address>.*)",
-    ),
-    (ParsedEventType::Fullscreen, r"fullscreen>>(?P<state>0|1)"),
-    (
-      ParsedEventType::MonitorRemoved,
-      r"monitorremoved>>(?P<monitor>.*)",
-    ),
-    (
-      ParsedEventType::MonitorAdded,
-      r"monitoradded>>(?P<monitor>.*)",
-    ),
-    (
-      ParsedEventType::OpenWindow,
-      r"openwindow>>(?P<address>.*),(?P<workspace>.*),(?P<class>.*),(?P<title>.*)",
-    ),
-    (
-      ParsedEventType::CloseWindow,
-      r"closewindow>>(?P<address>.*)",
-    ),
-    (
-      ParsedEventType::MoveWindow,
-      r"movewindow>>(?P<address>.*),(?P<workspace>.*)",
-    ),
-    (
-      ParsedEventType::ActiveLayout,
-      r"activelayout>>(?P<keyboard>.*)(?P<layout>.*)",
-    ),
-    (ParsedEventType::SubMap, r"submap>>(?P<submap>.*)"),
-    (ParsedEventType::OpenLayer, r"openlayer>>(?P<namespace>.*)"),
-    (
-      ParsedEventType::CloseLayer,
-      r"closelayer>>(?P<namespace>.*)",
-    ),
-    (
-      ParsedEventType::ChangeFloatingMode,
-      r"changefloatingmode>>(?P<address>.*),(?P<floatstate>[0-1])",
-    ),
-    (
-      ParsedEventType::Minimize,
-      r"minimize>>(?P<address>.*),(?P<state>[0-1])",
-    ),
-    (
-      ParsedEventType::Screencast,
-      r"screencast>>(?P<state>[0-1]),(?P<owner>[0-1])",
-    ),
-    (ParsedEventType::Urgent, r"urgent>>(?P<address>.*)"),
-    (
-      ParsedEventType::WindowTitle,
-      r"windowtitle>>(?P<address>.*)",
-    ),
-    (
-      ParsedEventType::WindowTitleV2,
-      r"windowtitlev2>>(?P<address>.*),(?P<title>.*)",
-    ),
-    (ParsedEventType::Unknown, r"(?P<Event>^[^>]*)"),
-  ]
-  .into_iter()
-  .map(|(e, r)| {
-    (
-      e,
-      match Regex::new(r) {
-        Ok(value) => value,
-        Err(e) => match e {
-          regex::Error::Syntax(str) => panic!("Regex syntax error: {str}"),
-          regex::Error::CompiledTooBig(size) => {
-            panic!("The compiled regex size is too big! ({size})")
-          }
-          _ => panic!("Error compiling regex: {e}"),
-        },
-      },
-    )
-  })
-  .collect()
-});
-
-static CHECK_TABLE: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
-
 fn parse_workspace(str: String) -> WorkspaceType {
   if str == "special" {
     WorkspaceType::Special(None)
-  } else if str.starts_with("special:") {
-    {
-      let mut iter = str.split(':');
-      iter.next();
-      match iter.next() {
-        Some(name) => WorkspaceType::Special(Some(name.to_string())),
-        None => WorkspaceType::Special(None),
-      }
-    }
+  } else if let Some(name) = str.strip_prefix("special:") {
+    WorkspaceType::Special(Some(name.to_string()))
   } else {
     WorkspaceType::Regular(str)
   }
 }
 
-/// This internal function parses event strings
-pub fn parse_event(event: String) -> crate::Result<Vec<Event>> {
-  let event_iter = event
-    .trim()
-    .lines()
-    .map(|event_line| {
-      let type_matches = EVENT_SET
-        .iter()
-        .filter_map(|(event_type, regex)| Some((event_type, regex.captures(event_line)?)))
-        .collect::<Vec<_>>();
-
-      (event_line, type_matches)
+/// Splits the event data into exactly `N` comma-separated arguments. The last
+/// argument receives the rest of the data, since it can contain commas itself
+/// (e.g. window titles).
+fn split_args<'a, const N: usize>(
+  event: &str,
+  data: &'a str,
+) -> Result<[&'a str; N], EventParseError> {
+  let mut parts = data.splitn(N, ',');
+  let mut args = [""; N];
+
+  for (index, arg) in args.iter_mut().enumerate() {
+    *arg = parts
+      .next()
+      .ok_or_else(|| EventParseError::MissingArgument {
+        event: event.to_string(),
+        index,
+      })?;
+  }
+
+  Ok(args)
+}
+
+fn parse_arg<T: FromStr>(event: &str, value: &str) -> Result<T, EventParseError> {
+  value
+    .parse::<T>()
+    .map_err(|_| EventParseError::InvalidArgument {
+      event: event.to_string(),
+      value: value.to_string(),
     })
-    .filter(|(_, b)| !b.is_empty());
-
-  let mut temp_event_holder = Vec::new();
-
-  for (event_str, matches) in event_iter {
-    match matches.len() {
-      0 => error!("No regex matches found for event: {event_str}"),
-      1 => {
-        error!("Unknown event: {event_str}");
-        continue;
-      }
-      2 => {
-        let (event_type, captures) = match matches
-          .into_iter()
-          .find(|(e, _)| **e != ParsedEventType::Unknown)
-        {
-          Some(t) => t,
-          None => {
-            error!("Only unknown events captured");
-            continue;
-          }
-        };
-
-        temp_event_holder.push((event_str, event_type, captures));
-      }
-      _ => error!("Event matched more than one regex"),
+}
+
+fn parse_bool(event: &str, value: &str) -> Result<bool, EventParseError> {
+  match value {
+    "0" => Ok(false),
+    "1" => Ok(true),
+    _ => Err(EventParseError::InvalidArgument {
+      event: event.to_string(),
+      value: value.to_string(),
+    }),
+  }
+}
+
+fn parse_workspace_event(event: &str, data: &str) -> Result<WorkspaceEventData, EventParseError> {
+  let [id, name] = split_args(event, data)?;
+  Ok(WorkspaceEventData {
+    workspace_id: parse_arg(event, id)?,
+    workspace_name: name.to_string(),
+  })
+}
+
+/// Parses a single line from the event socket. Returns `None` for events
+/// that are known but not forwarded, e.g. because a v2 variant of the
+/// event carries the same information.
+pub fn parse_event(line: &str) -> Result<Option<Event>, EventParseError> {
+  let (name, data) = line
+    .trim_end_matches(['\r', '\n'])
+    .split_once(">>")
+    .ok_or_else(|| EventParseError::MissingSeparator(line.to_string()))?;
+
+  let event = match name {
+    "workspacev2" => Event::Workspace(parse_workspace_event(name, data)?),
+    "createworkspacev2" => Event::CreateWorkspace(parse_workspace_event(name, data)?),
+    "destroyworkspacev2" => Event::DestroyWorkspace(parse_workspace_event(name, data)?),
+    "renameworkspace" => Event::RenameWorkspace(parse_workspace_event(name, data)?),
+    "moveworkspacev2" => {
+      let [id, workspace, monitor] = split_args(name, data)?;
+      Event::MoveWorkspace(WorkspaceMoveEventData {
+        workspace_id: parse_arg(name, id)?,
+        workspace_name: workspace.to_string(),
+        monitor_name: monitor.to_string(),
+      })
+    }
+    "activespecial" => {
+      let [workspace, monitor] = split_args(name, data)?;
+      Event::ActiveSpecial(ActiveSpecialEventData {
+        workspace: (!workspace.is_empty()).then(|| parse_workspace(workspace.to_string())),
+        monitor_name: monitor.to_string(),
+      })
+    }
+    "focusedmon" => {
+      let [monitor, workspace] = split_args(name, data)?;
+      Event::FocusedMon(MonitorEventData {
+        monitor_name: monitor.to_string(),
+        workspace: parse_workspace(workspace.to_string()),
+      })
+    }
+    "activewindowv2" => match data {
+      "" | "," => Event::ActiveWindow(None),
+      address => Event::ActiveWindow(Some(Address::fmt_new(address))),
+    },
+    "fullscreen" => Event::Fullscreen(parse_bool(name, data)?),
+    "monitoradded" => Event::MonitorAdded(data.to_string()),
+    "monitorremoved" => Event::MonitorRemoved(data.to_string()),
+    "openwindow" => {
+      let [address, workspace, class, title] = split_args(name, data)?;
+      Event::OpenWindow(WindowOpenEvent {
+        window_address: Address::fmt_new(address),
+        workspace_name: workspace.to_string(),
+        window_class: class.to_string(),
+        window_title: title.to_string(),
+      })
+    }
+    "closewindow" => Event::CloseWindow(Address::fmt_new(data)),
+    "movewindowv2" => {
+      let [address, id, workspace] = split_args(name, data)?;
+      Event::MoveWindow(WindowMoveEvent {
+        window_address: Address::fmt_new(address),
+        workspace_id: parse_arg(name, id)?,
+        workspace_name: workspace.to_string(),
+      })
+    }
+    "activelayout" => {
+      let [keyboard, layout] = split_args(name, data)?;
+      Event::ActiveLayout(LayoutEvent {
+        keyboard_name: keyboard.to_string(),
+        layout_name: layout.to_string(),
+      })
+    }
+    "submap" => Event::SubMap(data.to_string()),
+    "openlayer" => Event::OpenLayer(data.to_string()),
+    "closelayer" => Event::CloseLayer(data.to_string()),
+    "changefloatingmode" => {
+      let [address, floating] = split_args(name, data)?;
+      Event::ChangeFloatingMode(WindowFloatEventData {
+        window_address: Address::fmt_new(address),
+        is_floating: parse_bool(name, floating)?,
+      })
+    }
+    "urgent" => Event::Urgent(Address::fmt_new(data)),
+    "minimize" => {
+      let [address, minimized] = split_args(name, data)?;
+      Event::Minimize(MinimizeEventData {
+        window_address: Address::fmt_new(address),
+        is_minimized: parse_bool(name, minimized)?,
+      })
+    }
+    "windowtitlev2" => {
+      let [address, title] = split_args(name, data)?;
+      Event::WindowTitle(WindowTitleEventData {
+        window_address: Address::fmt_new(address),
+        window_title: title.to_string(),
+      })
+    }
+    "screencast" => {
+      let [state, owner] = split_args(name, data)?;
+      Event::Screencast(ScreencastEventData {
+        is_turning_on: parse_bool(name, state)?,
+        is_monitor: parse_bool(name, owner)?,
+      })
+    }
+    "pin" => {
+      let [address, pinned] = split_args(name, data)?;
+      Event::Pin(WindowPinEventData {
+        window_address: Address::fmt_new(address),
+        is_pinned: parse_bool(name, pinned)?,
+      })
+    }
+    "configreloaded" => Event::ConfigReloaded,
+
+    // Ignored events, v2 exists for these
+    "workspace" | "createworkspace" | "destroyworkspace" | "moveworkspace" | "activewindow"
+    | "movewindow" | "windowtitle" | "focusedmonv2" | "monitoraddedv2" | "monitorremovedv2" => {
+      return Ok(None)
+    }
+
+    // Known events that we don't handle
+    "ignoregrouplock" | "lockgroups" | "togglegroup" | "moveintogroup" | "moveoutofgroup"
+    | "bell" => return Ok(None),
+
+    name => return Err(EventParseError::UnknownEvent(name.to_string())),
+  };
+
+  Ok(Some(event))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn workspace(id: WorkspaceId, name: &str) -> WorkspaceEventData {
+    WorkspaceEventData {
+      workspace_id: id,
+      workspace_name: name.to_string(),
+    }
+  }
+
+  fn address() -> Address {
+    Address::fmt_new("5a4d8b0c7f20")
+  }
+
+  #[test]
+  fn parses_events() {
+    let cases = [
+      ("workspacev2>>3,3", Event::Workspace(workspace(3, "3"))),
+      (
+        "createworkspacev2>>-98,special:magic",
+        Event::CreateWorkspace(workspace(-98, "special:magic")),
+      ),
+      (
+        "destroyworkspacev2>>7,name:web",
+        Event::DestroyWorkspace(workspace(7, "name:web")),
+      ),
+      (
+        "renameworkspace>>2,web, mail",
+        Event::RenameWorkspace(workspace(2, "web, mail")),
+      ),
+      (
+        "moveworkspacev2>>4,4,DP-3",
+        Event::MoveWorkspace(WorkspaceMoveEventData {
+          workspace_id: 4,
+          workspace_name: "4".to_string(),
+          monitor_name: "DP-3".to_string(),
+        }),
+      ),
+      (
+        "activespecial>>special:magic,eDP-1",
+        Event::ActiveSpecial(ActiveSpecialEventData {
+          workspace: Some(WorkspaceType::Special(Some("magic".to_string()))),
+          monitor_name: "eDP-1".to_string(),
+        }),
+      ),
+      (
+        "activespecial>>,eDP-1",
+        Event::ActiveSpecial(ActiveSpecialEventData {
+          workspace: None,
+          monitor_name: "eDP-1".to_string(),
+        }),
+      ),
+      (
+        "focusedmon>>DP-3,special",
+        Event::FocusedMon(MonitorEventData {
+          monitor_name: "DP-3".to_string(),
+          workspace: WorkspaceType::Special(None),
+        }),
+      ),
+      (
+        "focusedmon>>eDP-1,1",
+        Event::FocusedMon(MonitorEventData {
+          monitor_name: "eDP-1".to_string(),
+          workspace: WorkspaceType::Regular("1".to_string()),
+        }),
+      ),
+      (
+        "activewindowv2>>5a4d8b0c7f20",
+        Event::ActiveWindow(Some(address())),
+      ),
+      ("activewindowv2>>", Event::ActiveWindow(None)),
+      ("activewindowv2>>,", Event::ActiveWindow(None)),
+      ("fullscreen>>1", Event::Fullscreen(true)),
+      ("fullscreen>>0", Event::Fullscreen(false)),
+      (
+        "monitoradded>>HDMI-A-1",
+        Event::MonitorAdded("HDMI-A-1".to_string()),
+      ),
+      (
+        "monitorremoved>>HDMI-A-1",
+        Event::MonitorRemoved("HDMI-A-1".to_string()),
+      ),
+      (
+        "openwindow>>5a4d8b0c7f20,2,firefox,Hyprland Wiki, IPC — Mozilla Firefox",
+        Event::OpenWindow(WindowOpenEvent {
+          window_address: address(),
+          workspace_name: "2".to_string(),
+          window_class: "firefox".to_string(),
+          window_title: "Hyprland Wiki, IPC — Mozilla Firefox".to_string(),
+        }),
+      ),
+      ("closewindow>>5a4d8b0c7f20", Event::CloseWindow(address())),
+      (
+        "movewindowv2>>5a4d8b0c7f20,-98,special:magic",
+        Event::MoveWindow(WindowMoveEvent {
+          window_address: address(),
+          workspace_id: -98,
+          workspace_name: "special:magic".to_string(),
+        }),
+      ),
+      (
+        "activelayout>>at-translated-set-2-keyboard,German (no dead keys)",
+        Event::ActiveLayout(LayoutEvent {
+          keyboard_name: "at-translated-set-2-keyboard".to_string(),
+          layout_name: "German (no dead keys)".to_string(),
+        }),
+      ),
+      ("submap>>resize", Event::SubMap("resize".to_string())),
+      ("submap>>", Event::SubMap(String::new())),
+      (
+        "openlayer>>dash2-control",
+        Event::OpenLayer("dash2-control".to_string()),
+      ),
+      (
+        "closelayer>>notifications",
+        Event::CloseLayer("notifications".to_string()),
+      ),
+      (
+        "changefloatingmode>>5a4d8b0c7f20,1",
+        Event::ChangeFloatingMode(WindowFloatEventData {
+          window_address: address(),
+          is_floating: true,
+        }),
+      ),
+      ("urgent>>5a4d8b0c7f20", Event::Urgent(address())),
+      (
+        "minimize>>5a4d8b0c7f20,0",
+        Event::Minimize(MinimizeEventData {
+          window_address: address(),
+          is_minimized: false,
+        }),
+      ),
+      (
+        "windowtitlev2>>5a4d8b0c7f20,nvim a.rs, b.rs,,",
+        Event::WindowTitle(WindowTitleEventData {
+          window_address: address(),
+          window_title: "nvim a.rs, b.rs,,".to_string(),
+        }),
+      ),
+      (
+        "screencast>>1,0",
+        Event::Screencast(ScreencastEventData {
+          is_turning_on: true,
+          is_monitor: false,
+        }),
+      ),
+      (
+        "pin>>5a4d8b0c7f20,1",
+        Event::Pin(WindowPinEventData {
+          window_address: address(),
+          is_pinned: true,
+        }),
+      ),
+      ("configreloaded>>", Event::ConfigReloaded),
+      ("configreloaded>>\r\n", Event::ConfigReloaded),
+    ];
+
+    for (line, event) in cases {
+      assert_eq!(parse_event(line).unwrap(), Some(event), "{line}");
     }
   }
 
-  let mut events: Vec<Event> = Vec::with_capacity(temp_event_holder.len());
-  for (event_str, event_type, captures) in temp_event_holder.iter() {
-    match event_type {
-      // Ignored events, v2 exists for these
-      ParsedEventType::Workspace
-      | ParsedEventType::DestroyWorkspace
-      | ParsedEventType::CreateWorkspace
-      | ParsedEventType::ActiveWindow
-      | ParsedEventType::WindowTitle => continue,
-
-      ParsedEventType::WorkspaceV2 => events.push(Event::Workspace(WorkspaceEventData {
-        workspace_id: captures["id"]
-          .parse::<WorkspaceId>()
-          .map_err(|e| anyhow!("workspacev2: invalid integer error: {e}"))?,
-        workspace_name: captures["name"].to_string(),
-      })),
-      ParsedEventType::DestroyWorkspaceV2 => {
-        events.push(Event::DestroyWorkspace(WorkspaceEventData {
-          workspace_id: captures["id"]
-            .parse::<WorkspaceId>()
-            .map_err(|e| anyhow!("destroyworkspacev2: invalid integer error: {e}"))?,
-          workspace_name: captures["name"].to_string(),
-        }))
-      }
-      ParsedEventType::CreateWorkspaceV2 => {
-        events.push(Event::CreateWorkspace(WorkspaceEventData {
-          workspace_id: captures["id"]
-            .parse::<WorkspaceId>()
-            .map_err(|e| anyhow!("createworkspacev2 v2: invalid integer error: {e}"))?,
-          workspace_name: captures["name"].to_string(),
-        }))
-      }
-      ParsedEventType::MoveWorkspace => events.push(Event::MoveWorkspace(MonitorEventData {
-        monitor_name: captures["monitor"].to_string(),
-        workspace: parse_workspace(captures["workspace"].to_string()),
-      })),
-      ParsedEventType::RenameWorkspace => events.push(Event::RenameWorkspace(WorkspaceEventData {
-        workspace_id: captures["id"]
-          .parse::<WorkspaceId>()
-          .map_err(|e| anyhow!("Workspace rename: invalid integer error: {e}"))?,
-        workspace_name: captures["name"].to_string(),
-      })),
-      ParsedEventType::FocusedMon => events.push(Event::FocusedMon(MonitorEventData {
-        monitor_name: captures["monitor"].to_string(),
-        workspace: WorkspaceType::Regular(captures["workspace"].to_string()),
-      })),
-      ParsedEventType::ActiveWindowV2 => {
-        let addr = &captures["address"];
-        let event = if addr != "," {
-          Event::ActiveWindow(Some(Address::fmt_new(addr)))
-        } else {
-          Event::ActiveWindow(None)
-        };
-        events.push(event);
-      }
-      ParsedEventType::Fullscreen => {
-        let state = &captures["state"] != "0";
-        events.push(Event::Fullscreen(state))
-      }
-      ParsedEventType::MonitorRemoved => {
-        events.push(Event::MonitorRemoved(captures["monitor"].to_string()))
-      }
-      ParsedEventType::MonitorAdded => {
-        events.push(Event::MonitorAdded(captures["monitor"].to_string()))
-      }
-      ParsedEventType::OpenWindow => events.push(Event::OpenWindow(WindowOpenEvent {
-        window_address: Address::fmt_new(&captures["address"]),
-        workspace_name: captures["workspace"].to_string(),
-        window_class: captures["class"].to_string(),
-        window_title: captures["title"].to_string(),
-      })),
-      ParsedEventType::CloseWindow => {
-        events.push(Event::CloseWindow(Address::fmt_new(&captures["address"])))
-      }
-      ParsedEventType::MoveWindow => events.push(Event::MoveWindow(WindowMoveEvent {
-        window_address: Address::fmt_new(&captures["address"]),
-        workspace_name: captures["workspace"].to_string(),
-      })),
-      ParsedEventType::ActiveLayout => events.push(Event::ActiveLayout(LayoutEvent {
-        keyboard_name: captures["keyboard"].to_string(),
-        layout_name: captures["layout"].to_string(),
-      })),
-      ParsedEventType::SubMap => events.push(Event::SubMap(captures["submap"].to_string())),
-      ParsedEventType::OpenLayer => {
-        events.push(Event::OpenLayer(captures["namespace"].to_string()))
-      }
-      ParsedEventType::CloseLayer => {
-        events.push(Event::CloseLayer(captures["namespace"].to_string()))
-      }
-      ParsedEventType::ChangeFloatingMode => {
-        let state = &captures["floatstate"] == "0";
-        events.push(Event::ChangeFloatingMode(WindowFloatEventData {
-          window_address: Address::fmt_new(&captures["address"]),
-          is_floating: state,
-        }))
-      }
-      ParsedEventType::Minimize => {
-        let state = &captures["state"] == "1";
-        events.push(Event::Minimize(MinimizeEventData {
-          window_address: Address::fmt_new(&captures["address"]),
-          is_minimized: state,
-        }))
-      }
-      ParsedEventType::Screencast => {
-        let state = &captures["state"] == "1";
-        let owner = &captures["owner"] == "1";
-        events.push(Event::Screencast(ScreencastEventData {
-          is_turning_on: state,
-          is_monitor: owner,
-        }))
-      }
-      ParsedEventType::Urgent => events.push(Event::Urgent(Address::fmt_new(&captures["address"]))),
-      ParsedEventType::WindowTitleV2 => events.push(Event::WindowTitle(WindowTitleEventData {
-        window_title: captures["title"].to_string(),
-        window_address: Address::fmt_new(&captures["address"]),
-      })),
-      ParsedEventType::Unknown => {
-        let table = CHECK_TABLE.lock();
-
-        if let Ok(mut tbl) = table {
-          let (event_string, print_str) = match captures.name("event").map(|s| s.as_str()) {
-            Some(s) => (s.to_string(), s),
-            None => ("Unknown".to_owned(), *event_str),
-          };
-
-          let should_run = tbl.insert(event_string);
-          if should_run {
-            error!("Unknown event: {print_str}");
-          }
-        }
-
-        bail!("unknown event: {event_str}")
-      }
+  #[test]
+  fn ignores_events_with_v2_variants_and_unhandled_events() {
+    let lines = [
+      "workspace>>3",
+      "createworkspace>>special:magic",
+      "destroyworkspace>>7",
+      "moveworkspace>>4,DP-3",
+      "activewindow>>firefox,Hyprland Wiki, IPC — Mozilla Firefox",
+      "movewindow>>5a4d8b0c7f20,special:magic",
+      "windowtitle>>5a4d8b0c7f20",
+      "focusedmonv2>>DP-3,4",
+      "monitoraddedv2>>1,HDMI-A-1,Dell Inc. DELL U2720Q",
+      "monitorremovedv2>>1,HDMI-A-1,Dell Inc. DELL U2720Q",
+      "ignoregrouplock>>1",
+      "lockgroups>>0",
+      "togglegroup>>1,5a4d8b0c7f20,5a4d8b3f0e80",
+      "moveintogroup>>5a4d8b0c7f20",
+      "moveoutofgroup>>5a4d8b0c7f20",
+      "bell>>5a4d8b0c7f20",
+    ];
+
+    for line in lines {
+      assert_eq!(parse_event(line).unwrap(), None, "{line}");
     }
   }
 
-  Ok(events)
+  #[test]
+  fn rejects_invalid_events() {
+    assert!(matches!(
+      parse_event("configreloaded"),
+      Err(EventParseError::MissingSeparator(line)) if line == "configreloaded"
+    ));
+
+    assert!(matches!(
+      parse_event("futureevent>>1"),
+      Err(EventParseError::UnknownEvent(name)) if name == "futureevent"
+    ));
+
+    assert!(matches!(
+      parse_event("moveworkspacev2>>4,4"),
+      Err(EventParseError::MissingArgument { event, index: 2 }) if event == "moveworkspacev2"
+    ));
+
+    assert!(matches!(
+      parse_event("openwindow>>5a4d8b0c7f20"),
+      Err(EventParseError::MissingArgument { index: 1, .. })
+    ));
+
+    assert!(matches!(
+      parse_event("workspacev2>>three,3"),
+      Err(EventParseError::InvalidArgument { event, value })
+        if event == "workspacev2" && value == "three"
+    ));
+
+    assert!(matches!(
+      parse_event("pin>>5a4d8b0c7f20,yes"),
+      Err(EventParseError::InvalidArgument { value, .. }) if value == "yes"
+    ));
+
+    assert!(matches!(
+      parse_event("fullscreen>>2"),
+      Err(EventParseError::InvalidArgument { value, .. }) if value == "2"
+    ));
+  }
 }
@@ -1,73 +1,302 @@
+use std::{io::ErrorKind, path::Path, sync::RwLock, time::Duration};
+
 use anyhow::Result;
-use futures::{channel::mpsc::channel, SinkExt};
+use futures::{
+  channel::mpsc::{channel, Sender},
+  SinkExt,
+};
 use serde::de::DeserializeOwned;
+use thiserror::Error as ThisError;
 use tokio::{
   io::{AsyncReadExt, AsyncWriteExt},
   net::UnixStream,
 };
-use tracing::error;
+use tracing::{error, info, warn};
 
 use self::{
-  data::Monitor,
-  dispatch::DataCommand,
-  events::{parse_event, Event},
+  data::{
+    Animations, Bind, Client, CursorPosition, Devices, Layers, Monitor, Version, Workspace,
+    WorkspaceRule,
+  },
+  decoder::EventDecoder,
+  dispatch::{gen_dispatch_str, DataCommand, DispatchType},
+  events::Event,
   types::{CommandContent, CommandFlag},
 };
 
 pub mod data;
+mod decoder;
 pub mod dispatch;
 pub mod events;
 pub mod types;
 
+#[derive(Debug, ThisError)]
+pub enum DispatchError {
+  #[error("invalid dispatcher")]
+  InvalidDispatcher,
+  #[error("dispatch failed: {0}")]
+  Failed(String),
+  #[error("expected {expected} replies to batch, got {actual}")]
+  BatchLength { expected: usize, actual: usize },
+  #[error("batch command {index} contains `;`, which separates commands: {command}")]
+  BatchSeparator { index: usize, command: String },
+  #[error("batch command {index} failed: {error}")]
+  Batch {
+    index: usize,
+    error: Box<DispatchError>,
+  },
+  #[error(transparent)]
+  Other(#[from] anyhow::Error),
+}
+
+impl DispatchError {
+  /// Parses a single reply to a dispatch command
+  fn from_reply(reply: &str) -> Result<(), DispatchError> {
+    match reply.trim() {
+      "ok" => Ok(()),
+      "Invalid dispatcher" => Err(DispatchError::InvalidDispatcher),
+      "" => Err(DispatchError::Failed("empty reply".to_string())),
+      reply => Err(DispatchError::Failed(reply.to_string())),
+    }
+  }
+}
+
+const CLIENT_SOCKET: &str = ".socket.sock";
+const EVENT_SOCKET: &str = ".socket2.sock";
+
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(100);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5);
+
+/// Finds the path of the given hyprland socket. If hyprland was restarted,
+/// the instance signature in our environment is stale, so we fall back to
+/// the most recently created instance.
 fn get_socket_path(socket_name: &str) -> Result<String> {
-  let his = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
-  let rt_dir = std::env::var("XDG_RUNTIME_DIR")?;
-  Ok(format!("{rt_dir}/hypr/{his}/{socket_name}"))
+  let hypr_dir = Path::new(&std::env::var("XDG_RUNTIME_DIR")?).join("hypr");
+  let his = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+
+  find_socket(&hypr_dir, his.as_deref(), socket_name)
+}
+
+fn find_socket(hypr_dir: &Path, his: Option<&str>, socket_name: &str) -> Result<String> {
+  if let Some(his) = his {
+    let path = hypr_dir.join(his).join(socket_name);
+    if accepts_connections(&path) {
+      return Ok(path.to_string_lossy().to_string());
+    }
+  }
+
+  let newest = std::fs::read_dir(hypr_dir)?
+    .filter_map(|entry| {
+      let path = entry.ok()?.path().join(socket_name);
+      let modified = path.metadata().ok()?.modified().ok()?;
+      accepts_connections(&path).then_some((modified, path))
+    })
+    .max_by_key(|(modified, _)| *modified);
+
+  match newest {
+    Some((_, path)) => Ok(path.to_string_lossy().to_string()),
+    None => anyhow::bail!("no hyprland instance found for {socket_name}"),
+  }
+}
+
+/// Whether hyprland is listening on the socket. A crashed instance leaves
+/// its socket behind, which still exists but refuses connections.
+fn accepts_connections(path: &Path) -> bool {
+  match std::os::unix::net::UnixStream::connect(path) {
+    Ok(_) => true,
+    Err(err) => !matches!(
+      err.kind(),
+      ErrorKind::ConnectionRefused | ErrorKind::NotFound
+    ),
+  }
 }
 
 pub struct HyprlandClient {
-  path: String,
+  path: RwLock<String>,
 }
 
 impl HyprlandClient {
   pub async fn new() -> Result<Self> {
-    let path = get_socket_path(".socket.sock")?;
-    if !std::path::Path::new(&path).exists() {
-      return Err(anyhow::anyhow!("hyprland client socket not found"));
-    }
+    let path = get_socket_path(CLIENT_SOCKET)?;
 
-    Ok(Self { path })
+    Ok(Self {
+      path: RwLock::new(path),
+    })
   }
 
   pub async fn get_monitors(&self) -> Result<Vec<Monitor>> {
     self.call_data_command(DataCommand::Monitors).await
   }
 
+  pub async fn get_workspaces(&self) -> Result<Vec<Workspace>> {
+    self.call_data_command(DataCommand::Workspaces).await
+  }
+
+  pub async fn get_active_workspace(&self) -> Result<Workspace> {
+    self.call_data_command(DataCommand::ActiveWorkspace).await
+  }
+
+  pub async fn get_clients(&self) -> Result<Vec<Client>> {
+    self.call_data_command(DataCommand::Clients).await
+  }
+
+  /// Returns the focused client, or `None` if no window is focused
+  pub async fn get_active_window(&self) -> Result<Option<Client>> {
+    let value: serde_json::Value = self.call_data_command(DataCommand::ActiveWindow).await?;
+
+    // Hyprland replies with an empty object if no window is focused
+    if value.as_object().is_some_and(|object| object.is_empty()) {
+      return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_value(value)?))
+  }
+
+  pub async fn get_layers(&self) -> Result<Layers> {
+    self.call_data_command(DataCommand::Layers).await
+  }
+
+  pub async fn get_devices(&self) -> Result<Devices> {
+    self.call_data_command(DataCommand::Devices).await
+  }
+
+  pub async fn get_version(&self) -> Result<Version> {
+    self.call_data_command(DataCommand::Version).await
+  }
+
+  pub async fn get_cursor_position(&self) -> Result<CursorPosition> {
+    self.call_data_command(DataCommand::CursorPosition).await
+  }
+
+  pub async fn get_binds(&self) -> Result<Vec<Bind>> {
+    self.call_data_command(DataCommand::Binds).await
+  }
+
+  pub async fn get_animations(&self) -> Result<Animations> {
+    self.call_data_command(DataCommand::Animations).await
+  }
+
+  pub async fn get_workspace_rules(&self) -> Result<Vec<WorkspaceRule>> {
+    self.call_data_command(DataCommand::WorkspaceRules).await
+  }
+
+  pub async fn dispatch(&self, cmd: DispatchType<'_>) -> Result<(), DispatchError> {
+    let response = self.send_command(gen_dispatch_str(cmd, true)?).await?;
+    DispatchError::from_reply(&response)
+  }
+
+  /// Runs several dispatchers with a single request. Commands containing `;`
+  /// are rejected, since hyprland uses it to separate the batched commands.
+  pub async fn dispatch_many(&self, cmds: Vec<DispatchType<'_>>) -> Result<(), DispatchError> {
+    if cmds.is_empty() {
+      return Ok(());
+    }
+
+    let expected = cmds.len();
+    let commands = cmds
+      .into_iter()
+      .map(|cmd| gen_dispatch_str(cmd, true).map(|content| content.data))
+      .collect::<Result<Vec<_>>>()?;
+
+    if let Some(index) = commands.iter().position(|command| command.contains(';')) {
+      return Err(DispatchError::BatchSeparator {
+        index,
+        command: commands[index].clone(),
+      });
+    }
+
+    let batch = commands.join(";");
+
+    let cmd = CommandContent {
+      flag: CommandFlag::Empty,
+      data: format!("[[BATCH]]{batch}"),
+    };
+
+    let response = self.send_command(cmd).await?;
+    let replies = split_batch_reply(&response, expected);
+    if replies.len() != expected {
+      return Err(DispatchError::BatchLength {
+        expected,
+        actual: replies.len(),
+      });
+    }
+
+    for (index, reply) in replies.into_iter().enumerate() {
+      DispatchError::from_reply(reply).map_err(|error| DispatchError::Batch {
+        index,
+        error: Box::new(error),
+      })?;
+    }
+
+    Ok(())
+  }
+
   async fn call_data_command<T: DeserializeOwned>(&self, cmd: DataCommand) -> Result<T> {
-    let mut socket = UnixStream::connect(&self.path).await?;
     let cmd = CommandContent {
       flag: CommandFlag::JSON,
       data: cmd.to_string(),
     };
 
+    let response = self.send_command(cmd).await?;
+    Ok(serde_json::from_str::<T>(&response)?)
+  }
+
+  async fn send_command(&self, cmd: CommandContent) -> Result<String> {
+    let mut socket = self.connect().await?;
     socket.write_all(&cmd.as_bytes()).await?;
 
+    // Hyprland closes the connection after replying
     let mut response = Vec::new();
+    socket.read_to_end(&mut response).await?;
 
-    const BUF_SIZE: usize = 8192;
-    let mut buf = [0; BUF_SIZE];
-    loop {
-      let num_read = socket.read(&mut buf).await?;
-      let buf = &buf[..num_read];
-      response.append(&mut buf.to_vec());
-      if num_read == 0 || num_read != BUF_SIZE {
-        break;
-      }
+    Ok(String::from_utf8(response)?)
+  }
+
+  /// Connects to the client socket, looking up the socket path again if
+  /// hyprland has been restarted since the last command.
+  async fn connect(&self) -> Result<UnixStream> {
+    let path = self
+      .path
+      .read()
+      .map_err(|_| anyhow::anyhow!("failed to lock socket path"))?
+      .clone();
+
+    let err = match UnixStream::connect(&path).await {
+      Ok(socket) => return Ok(socket),
+      Err(err) => err,
+    };
+
+    let new_path = get_socket_path(CLIENT_SOCKET)?;
+    if new_path == path {
+      return Err(err.into());
     }
 
-    let response = String::from_utf8(response)?;
-    Ok(serde_json::from_str::<T>(&response)?)
+    info!("hyprland client socket moved to {new_path}");
+    let socket = UnixStream::connect(&new_path).await?;
+    *self
+      .path
+      .write()
+      .map_err(|_| anyhow::anyhow!("failed to lock socket path"))? = new_path;
+
+    Ok(socket)
+  }
+}
+
+/// Splits the reply to a batch request into the replies of the single
+/// commands. Older hyprland versions concatenate replies without a separator,
+/// which we can only split if every command succeeded.
+fn split_batch_reply(response: &str, expected: usize) -> Vec<&str> {
+  let replies = response
+    .trim()
+    .split("\n\n")
+    .map(str::trim)
+    .collect::<Vec<_>>();
+
+  if replies.len() == 1 && expected > 1 && response.trim() == "ok".repeat(expected) {
+    return vec!["ok"; expected];
   }
+
+  replies
 }
 
 pub struct HyprlandListener {
@@ -76,38 +305,30 @@ pub struct HyprlandListener {
 
 impl HyprlandListener {
   pub async fn new() -> Result<Self> {
-    let path = get_socket_path(".socket2.sock")?;
-    if !std::path::Path::new(&path).exists() {
-      return Err(anyhow::anyhow!("hyprland event socket not found"));
-    }
-
+    let path = get_socket_path(EVENT_SOCKET)?;
     Ok(Self { path })
   }
 
+  /// Listens to hyprland events. If the connection is lost, it is
+  /// reestablished in the background and an [`Event::Resync`] is emitted,
+  /// since any events in between were missed.
   pub async fn listen(&self) -> Result<impl futures::Stream<Item = Event>> {
     let mut socket = UnixStream::connect(&self.path).await?;
     let (mut tx, rx) = channel::<Event>(5);
 
     tokio::spawn(async move {
       loop {
-        let mut buf = [0; 4096];
-        let num_read = match socket.read(&mut buf).await {
-          Ok(n) => n,
-          Err(e) => {
-            error!("Error reading from socket: {e}");
-            break;
-          }
-        };
-
-        if num_read == 0 {
-          break;
+        if !forward_events(&mut socket, &mut tx).await {
+          // The receiving stream was dropped
+          return;
         }
-        let buf = &buf[..num_read];
-        let string = String::from_utf8(buf.to_vec()).unwrap();
-        let parsed: Vec<Event> = parse_event(string).unwrap();
 
-        for event in parsed {
-          tx.send(event).await.unwrap();
+        warn!("hyprland event socket disconnected, reconnecting");
+        socket = reconnect().await;
+        info!("reconnected to hyprland event socket");
+
+        if tx.send(Event::Resync).await.is_err() {
+          return;
         }
       }
     });
@@ -115,3 +336,160 @@ impl HyprlandListener {
     Ok(rx)
   }
 }
+
+/// Forwards events until the socket is closed. Returns false if the
+/// receiver was dropped and listening should stop.
+async fn forward_events(socket: &mut UnixStream, tx: &mut Sender<Event>) -> bool {
+  let mut decoder = EventDecoder::new();
+  let mut buf = [0; 4096];
+
+  loop {
+    let num_read = match socket.read(&mut buf).await {
+      Ok(n) => n,
+      Err(e) => {
+        error!("Error reading from socket: {e}");
+        return true;
+      }
+    };
+
+    if num_read == 0 {
+      return true;
+    }
+
+    for event in decoder.decode(&buf[..num_read]) {
+      if tx.send(event).await.is_err() {
+        return false;
+      }
+    }
+  }
+}
+
+/// Connects to the event socket of the current hyprland instance, retrying
+/// with exponential backoff until it succeeds.
+async fn reconnect() -> UnixStream {
+  let mut delay = RECONNECT_DELAY_MIN;
+
+  loop {
+    tokio::time::sleep(delay).await;
+
+    let result = match get_socket_path(EVENT_SOCKET) {
+      Ok(path) => UnixStream::connect(path).await.map_err(Into::into),
+      Err(err) => Err(err),
+    };
+
+    match result {
+      Ok(socket) => return socket,
+      Err(err) => warn!("failed to reconnect to hyprland: {err}"),
+    }
+
+    delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{os::unix::net::UnixListener, path::PathBuf};
+
+  use super::*;
+
+  struct HyprDir(PathBuf);
+
+  impl HyprDir {
+    /// A fake `$XDG_RUNTIME_DIR/hypr` under `$TMPDIR`
+    fn new(name: &str) -> Self {
+      let dir = std::env::temp_dir().join(format!("dash2-hypr-{}-{name}", std::process::id()));
+      let _ = std::fs::remove_dir_all(&dir);
+      Self(dir)
+    }
+
+    /// Starts an instance listening on its client socket
+    fn live(&self, his: &str) -> UnixListener {
+      std::fs::create_dir_all(self.0.join(his)).unwrap();
+      UnixListener::bind(self.socket(his)).unwrap()
+    }
+
+    /// Leaves behind the socket of an instance that crashed
+    fn stale(&self, his: &str) {
+      drop(self.live(his));
+    }
+
+    fn socket(&self, his: &str) -> String {
+      self
+        .0
+        .join(his)
+        .join(CLIENT_SOCKET)
+        .to_string_lossy()
+        .to_string()
+    }
+  }
+
+  impl Drop for HyprDir {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[tokio::test]
+  async fn rejects_separators_in_batches() {
+    // Never connected to, the batch is rejected before
+    let client = HyprlandClient {
+      path: RwLock::new("/nonexistent/.socket.sock".to_string()),
+    };
+
+    let err = client
+      .dispatch_many(vec![
+        DispatchType::KillActiveWindow,
+        DispatchType::Custom("workspace", "1; exec foot"),
+      ])
+      .await
+      .unwrap_err();
+
+    assert!(matches!(
+      err,
+      DispatchError::BatchSeparator { index: 1, ref command } if command == "dispatch workspace 1; exec foot"
+    ));
+  }
+
+  #[test]
+  fn prefers_the_instance_in_the_environment() {
+    let dir = HyprDir::new("env");
+    let _old = dir.live("old");
+    std::thread::sleep(Duration::from_millis(10));
+    let _new = dir.live("new");
+
+    let path = find_socket(&dir.0, Some("old"), CLIENT_SOCKET).unwrap();
+    assert_eq!(path, dir.socket("old"));
+  }
+
+  #[test]
+  fn falls_back_from_a_stale_socket() {
+    let dir = HyprDir::new("stale-env");
+    dir.stale("crashed");
+    let _restarted = dir.live("restarted");
+
+    let path = find_socket(&dir.0, Some("crashed"), CLIENT_SOCKET).unwrap();
+    assert_eq!(path, dir.socket("restarted"));
+
+    let path = find_socket(&dir.0, Some("gone"), CLIENT_SOCKET).unwrap();
+    assert_eq!(path, dir.socket("restarted"));
+  }
+
+  #[test]
+  fn skips_newer_stale_sockets() {
+    let dir = HyprDir::new("stale-newest");
+    let _live = dir.live("live");
+    std::thread::sleep(Duration::from_millis(10));
+    dir.stale("crashed");
+
+    let path = find_socket(&dir.0, None, CLIENT_SOCKET).unwrap();
+    assert_eq!(path, dir.socket("live"));
+  }
+
+  #[test]
+  fn fails_without_a_live_instance() {
+    let dir = HyprDir::new("none");
+    dir.stale("crashed");
+
+    assert!(find_socket(&dir.0, Some("crashed"), CLIENT_SOCKET).is_err());
+  }
+}
@@ -1,7 +1,7 @@
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Display)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Display, Serialize, Deserialize)]
 pub struct Address(String);
 
 impl Address {
@@ -0,0 +1,320 @@
+use std::{
+  future::Future,
+  io,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+  net::{UnixListener, UnixStream},
+};
+use tracing::error;
+
+use crate::config::Panel;
+
+/// Relative to `XDG_RUNTIME_DIR`
+const SOCKET: &str = "dash2/control.sock";
+
+/// A command for the running desktop. Requests and responses are sent as
+/// JSON, one per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, clap::Subcommand)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+  /// Show a panel if it is hidden and hide it otherwise
+  TogglePanel(PanelTarget),
+  /// Show a panel as if the pointer entered it
+  ShowPanel(PanelTarget),
+  /// Hide a panel as if the pointer left it
+  HidePanel(PanelTarget),
+  /// Reload the configuration files
+  ReloadConfig,
+  /// Print the state of the desktop as JSON
+  State,
+  /// Lock the session
+  Lock,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, clap::Args)]
+pub struct PanelTarget {
+  #[arg(value_enum)]
+  pub panel: Panel,
+
+  /// Connector name of the monitor, defaults to the focused one
+  #[arg(long)]
+  pub monitor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Response {
+  Ok,
+  State(State),
+  Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+  pub locked: bool,
+  pub monitors: Vec<MonitorState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorState {
+  /// Connector name, e.g. `DP-1`
+  pub name: String,
+  pub focused: bool,
+  pub primary: bool,
+  pub panels: Vec<PanelState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PanelState {
+  pub panel: Panel,
+  pub visible: bool,
+}
+
+/// Path of the control socket of the running desktop
+pub fn socket_path() -> Result<PathBuf> {
+  dirs::runtime_dir()
+    .map(|dir| dir.join(SOCKET))
+    .context("XDG_RUNTIME_DIR is not set")
+}
+
+/// Binds the control socket at `path`. A socket left behind by a desktop
+/// that didn't exit cleanly is replaced, one that is still in use is not.
+pub async fn bind(path: &Path) -> Result<UnixListener> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+
+  if path.exists() {
+    if UnixStream::connect(path).await.is_ok() {
+      anyhow::bail!("{} is already in use", path.display());
+    }
+
+    std::fs::remove_file(path)?;
+  }
+
+  Ok(UnixListener::bind(path)?)
+}
+
+/// Answers the requests of every client with `handle`, until accepting
+/// connections fails.
+pub async fn serve<F, Fut>(listener: UnixListener, handle: F)
+where
+  F: Fn(Request) -> Fut + Clone + Send + 'static,
+  Fut: Future<Output = Response> + Send + 'static,
+{
+  loop {
+    let stream = match listener.accept().await {
+      Ok((stream, _)) => stream,
+      Err(err) => {
+        error!("failed to accept control connection: {err}");
+        return;
+      }
+    };
+
+    let handle = handle.clone();
+    tokio::spawn(async move {
+      serve_connection(stream, handle)
+        .await
+        .unwrap_or_else(|err| {
+          error!("failed to serve control connection: {err}");
+        });
+    });
+  }
+}
+
+async fn serve_connection<F, Fut>(stream: UnixStream, handle: F) -> io::Result<()>
+where
+  F: Fn(Request) -> Fut,
+  Fut: Future<Output = Response>,
+{
+  let (reader, mut writer) = stream.into_split();
+  let mut lines = BufReader::new(reader).lines();
+
+  while let Some(line) = lines.next_line().await? {
+    let response = match serde_json::from_str(&line) {
+      Ok(request) => handle(request).await,
+      Err(err) => Response::Error(format!("invalid request: {err}")),
+    };
+
+    write_line(&mut writer, &response).await?;
+  }
+
+  Ok(())
+}
+
+/// Sends a single request to the socket at `path` and waits for the response
+pub async fn send(path: &Path, request: &Request) -> Result<Response> {
+  let stream = UnixStream::connect(path).await.with_context(|| {
+    format!(
+      "failed to connect to {}, is the desktop running?",
+      path.display()
+    )
+  })?;
+
+  let (reader, mut writer) = stream.into_split();
+  write_line(&mut writer, request).await?;
+
+  let line = BufReader::new(reader)
+    .lines()
+    .next_line()
+    .await?
+    .context("the desktop closed the connection without responding")?;
+
+  Ok(serde_json::from_str(&line)?)
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> io::Result<()>
+where
+  W: AsyncWrite + Unpin,
+  T: Serialize,
+{
+  let mut line = serde_json::to_vec(value)?;
+  line.push(b'\n');
+  writer.write_all(&line).await
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::{io::AsyncReadExt, sync::mpsc};
+
+  use super::*;
+
+  /// A socket path in a directory of its own under `$TMPDIR`
+  fn socket_in_tmp(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dash2-ipc-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("control.sock")
+  }
+
+  fn remove_socket_dir(path: &Path) {
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+  }
+
+  fn panel(panel: Panel, monitor: Option<&str>) -> PanelTarget {
+    PanelTarget {
+      panel,
+      monitor: monitor.map(str::to_string),
+    }
+  }
+
+  #[tokio::test]
+  async fn requests_round_trip() {
+    let path = socket_in_tmp("round-trip");
+    let listener = bind(&path).await.unwrap();
+
+    let state = State {
+      locked: false,
+      monitors: vec![MonitorState {
+        name: "DP-1".to_string(),
+        focused: true,
+        primary: true,
+        panels: vec![PanelState {
+          panel: Panel::Control,
+          visible: false,
+        }],
+      }],
+    };
+
+    let (received_tx, mut received_rx) = mpsc::unbounded_channel();
+    let served_state = state.clone();
+    tokio::spawn(serve(listener, move |request| {
+      let received_tx = received_tx.clone();
+      let state = served_state.clone();
+      async move {
+        let response = match request {
+          Request::State => Response::State(state),
+          _ => Response::Ok,
+        };
+
+        received_tx.send(request).unwrap();
+        response
+      }
+    }));
+
+    let requests = [
+      Request::TogglePanel(panel(Panel::Workspace, None)),
+      Request::ShowPanel(panel(Panel::Control, Some("DP-1"))),
+      Request::HidePanel(panel(Panel::Bars, Some("HDMI-A-1"))),
+      Request::ReloadConfig,
+      Request::State,
+      Request::Lock,
+    ];
+
+    for request in requests {
+      let expected = match request {
+        Request::State => Response::State(state.clone()),
+        _ => Response::Ok,
+      };
+
+      assert_eq!(send(&path, &request).await.unwrap(), expected);
+      assert_eq!(received_rx.recv().await.unwrap(), request);
+    }
+
+    remove_socket_dir(&path);
+  }
+
+  #[tokio::test]
+  async fn invalid_requests_get_an_error() {
+    let path = socket_in_tmp("invalid");
+    let listener = bind(&path).await.unwrap();
+    tokio::spawn(serve(listener, |_| async { Response::Ok }));
+
+    let mut stream = UnixStream::connect(&path).await.unwrap();
+    stream
+      .write_all(b"not json\n{\"command\":\"explode\"}\n")
+      .await
+      .unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut output = String::new();
+    stream.read_to_string(&mut output).await.unwrap();
+
+    let responses = output
+      .lines()
+      .map(|line| serde_json::from_str::<Response>(line).unwrap())
+      .collect::<Vec<_>>();
+
+    assert_eq!(responses.len(), 2);
+    for response in responses {
+      assert!(
+        matches!(&response, Response::Error(message) if message.starts_with("invalid request")),
+        "{response:?}"
+      );
+    }
+
+    remove_socket_dir(&path);
+  }
+
+  #[tokio::test]
+  async fn bind_replaces_stale_sockets() {
+    let path = socket_in_tmp("stale");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    // Dropping a listener leaves the socket file behind, like a crash
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let listener = bind(&path).await.unwrap();
+    tokio::spawn(serve(listener, |_| async { Response::Ok }));
+    assert_eq!(send(&path, &Request::Lock).await.unwrap(), Response::Ok);
+
+    remove_socket_dir(&path);
+  }
+
+  #[tokio::test]
+  async fn bind_refuses_sockets_in_use() {
+    let path = socket_in_tmp("in-use");
+    let _listener = bind(&path).await.unwrap();
+
+    let err = bind(&path).await.unwrap_err();
+    assert!(err.to_string().ends_with("is already in use"), "{err}");
+    assert!(path.exists());
+
+    remove_socket_dir(&path);
+  }
+}
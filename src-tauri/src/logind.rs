@@ -0,0 +1,57 @@
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+
+use anyhow::Result;
+use zbus::{dbus_proxy, zvariant::OwnedObjectPath};
+
+#[dbus_proxy(
+  interface = "org.freedesktop.login1.Manager",
+  default_service = "org.freedesktop.login1",
+  default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+  fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+  fn inhibit(
+    &self,
+    what: &str,
+    who: &str,
+    why: &str,
+    mode: &str,
+  ) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+  #[dbus_proxy(signal)]
+  fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+  interface = "org.freedesktop.login1.Session",
+  default_service = "org.freedesktop.login1",
+  default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+  #[dbus_proxy(property)]
+  fn id(&self) -> zbus::Result<String>;
+
+  #[dbus_proxy(signal)]
+  fn lock(&self) -> zbus::Result<()>;
+
+  #[dbus_proxy(signal)]
+  fn unlock(&self) -> zbus::Result<()>;
+}
+
+/// Proxy for the session this process belongs to. Signals are sent from
+/// the real object path of the session, not from `session/auto`.
+pub async fn current_session(zbus_conn: &zbus::Connection) -> Result<SessionProxy<'static>> {
+  let id = SessionProxy::new(zbus_conn).await?.id().await?;
+  let path = ManagerProxy::new(zbus_conn).await?.get_session(&id).await?;
+
+  let session = SessionProxy::builder(zbus_conn).path(path)?.build().await?;
+
+  Ok(session)
+}
+
+/// Takes a delay inhibitor for sleep. Sleep is held back until the
+/// returned descriptor is closed, or logind's `InhibitDelayMaxSec` passes.
+pub async fn inhibit_sleep(manager: &ManagerProxy<'_>, why: &str) -> Result<OwnedFd> {
+  let fd = manager.inhibit("sleep", "dash2", why, "delay").await?;
+  Ok(unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) })
+}
@@ -1,22 +1,33 @@
 #![allow(dead_code)]
 
-use std::path::PathBuf;
+use std::{
+  os::fd::{FromRawFd, OwnedFd, RawFd},
+  path::PathBuf,
+};
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
+pub mod background;
 pub mod battery;
 pub mod config;
+pub mod fingerprint;
 pub mod hyprland;
+pub mod ipc;
 pub mod layer_shell;
+pub mod logind;
 pub mod pam;
 pub mod power;
+pub mod primary;
 pub mod scrambler;
+pub mod secret;
 pub mod util;
 
 mod greeter;
 mod desktop;
 mod session_lock;
+#[cfg(test)]
+mod testing;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -27,14 +38,49 @@ struct Args {
   command: Command,
 }
 
+#[derive(Debug, clap::Args)]
+struct LockArgs {
+  /// Suspend the session after locking
+  #[arg(short, long)]
+  suspend: bool,
+
+  /// Seconds after locking in which any input unlocks again
+  #[arg(long, default_value_t = 0)]
+  grace: u64,
+
+  /// Sleep inhibitor to release once the session is locked, passed on
+  /// by the desktop
+  #[arg(long, hide = true)]
+  inhibitor_fd: Option<RawFd>,
+}
+
+impl LockArgs {
+  /// Takes ownership of the inherited inhibitor. Logind hands out inhibitors
+  /// as the write end of a pipe, so anything else, like stdout, is refused.
+  fn inhibitor(&self) -> Result<Option<OwnedFd>> {
+    let Some(fd) = self.inhibitor_fd else {
+      return Ok(None);
+    };
+
+    let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+    let is_pipe = fd > libc::STDERR_FILENO
+      && unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1
+      && unsafe { libc::fstat(fd, &mut stat) } == 0
+      && stat.st_mode & libc::S_IFMT == libc::S_IFIFO;
+
+    if !is_pipe {
+      anyhow::bail!("inhibitor fd {fd} is not an inherited pipe");
+    }
+
+    // The descriptor was inherited from our parent, which gave it up
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd) }))
+  }
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
   /// Lock the session
-  Lock {
-    /// Suspend the session after locking
-    #[arg(short, long)]
-    suspend: bool,
-  },
+  Lock(LockArgs),
 
   /// Start the greeter
   Greet {
@@ -46,8 +92,36 @@ enum Command {
   /// Start the desktop environment
   Desktop,
 
-  /// Print the configuration
-  PrintConfig,
+  /// Send a command to the running desktop
+  Msg {
+    #[command(subcommand)]
+    request: ipc::Request,
+  },
+
+  /// Print the merged configuration
+  PrintConfig {
+    #[arg(long, value_enum, default_value_t = Format::Toml)]
+    format: Format,
+  },
+
+  /// Check a configuration file for unknown keys and invalid values,
+  /// together with the other files and variables it is merged with
+  CheckConfig {
+    /// Defaults to the user's configuration file
+    path: Option<PathBuf>,
+  },
+
+  /// Print the JSON Schema of the configuration file
+  PrintSchema,
+
+  /// Check the PAM setup of the lock screen without locking
+  CheckPam,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+  Toml,
+  Json,
 }
 
 // TODO: ctrl-c handler
@@ -55,15 +129,98 @@ fn main() -> Result<()> {
   tracing_subscriber::fmt::init();
 
   let args = Args::parse();
-  let config = config::load(&args.config)?;
+  // Checking and printing the schema have to work with a broken config
+  let config = || config::load(&args.config);
 
   match args.command {
-    Command::Lock { suspend } => session_lock::run(config, suspend),
-    Command::Greet { demo } => greeter::greet(config, demo),
-    Command::Desktop => desktop::run(config),
-    Command::PrintConfig => {
-      println!("{:#?}", config);
+    Command::Lock(lock) => {
+      session_lock::run(config()?, lock.suspend, lock.grace, lock.inhibitor()?)
+    }
+    Command::Greet { demo } => greeter::greet(config()?, demo),
+    Command::Desktop => desktop::run(config()?, args.config),
+    Command::Msg { request } => msg(&request),
+    Command::CheckPam => session_lock::check_pam(config()?),
+    Command::PrintConfig { format } => {
+      let config = config()?;
+      match format {
+        Format::Toml => print!("{}", toml::to_string(&*config)?),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&*config)?),
+      }
+      Ok(())
+    }
+    Command::CheckConfig { path } => {
+      let report = config::check(path.or(args.config).as_deref());
+      for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+      }
+
+      for err in &report.errors {
+        eprintln!("{err}");
+      }
+
+      if !report.errors.is_empty() {
+        let count = report.errors.len();
+        return Err(anyhow::anyhow!("found {count} problems in the config"));
+      }
+
+      println!("config is valid");
+      Ok(())
+    }
+    Command::PrintSchema => {
+      println!("{}", serde_json::to_string_pretty(&config::schema())?);
+      Ok(())
+    }
+  }
+}
+
+fn msg(request: &ipc::Request) -> Result<()> {
+  let rt = tokio::runtime::Runtime::new()?;
+  let response = rt.block_on(ipc::send(&ipc::socket_path()?, request))?;
+
+  match response {
+    ipc::Response::Ok => Ok(()),
+    ipc::Response::State(state) => {
+      println!("{}", serde_json::to_string_pretty(&state)?);
       Ok(())
     }
+    ipc::Response::Error(message) => Err(anyhow::anyhow!(message)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::fd::{AsRawFd, IntoRawFd};
+
+  use super::*;
+
+  fn lock_args(inhibitor_fd: RawFd) -> LockArgs {
+    LockArgs {
+      suspend: false,
+      grace: 0,
+      inhibitor_fd: Some(inhibitor_fd),
+    }
+  }
+
+  #[test]
+  fn inhibitor_takes_inherited_pipes() {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let read = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+
+    let inhibitor = lock_args(fds[1]).inhibitor().unwrap().unwrap();
+    assert_eq!(inhibitor.as_raw_fd(), fds[1]);
+    drop(read);
+  }
+
+  #[test]
+  fn inhibitor_refuses_other_descriptors() {
+    assert!(lock_args(libc::STDOUT_FILENO).inhibitor().is_err());
+
+    let file = std::fs::File::open("/dev/null").unwrap();
+    assert!(lock_args(file.as_raw_fd()).inhibitor().is_err());
+
+    let closed = std::fs::File::open("/dev/null").unwrap().into_raw_fd();
+    unsafe { libc::close(closed) };
+    assert!(lock_args(closed).inhibitor().is_err());
   }
 }
@@ -0,0 +1,107 @@
+use std::{
+  cell::RefCell,
+  sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+  time::Duration,
+};
+
+use serde::Serialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+
+use super::converse::Converse;
+use crate::secret::Secret;
+
+/// How long to wait for the user to answer a prompt before giving up,
+/// so an abandoned conversation doesn't block the PAM thread forever.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageKind {
+  Visible,
+  Secret,
+  Info,
+  Error,
+}
+
+/// A message from PAM that should be shown to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct ConvMessage {
+  pub kind: MessageKind,
+  pub message: String,
+}
+
+/// Forwards the PAM conversation over channels, so it can be shown in a
+/// UI while PAM runs on a blocking thread. The password entered up front
+/// answers the first blind prompt, every other prompt is sent out and
+/// blocks until a response arrives. A `None` response aborts the conversation.
+pub struct ChannelConv {
+  password: RefCell<Option<Secret>>,
+  messages: UnboundedSender<ConvMessage>,
+  responses: Receiver<Option<Secret>>,
+}
+
+impl ChannelConv {
+  /// Returns the conversation, the receiving end for messages and the
+  /// sending end for responses.
+  pub fn new(password: Secret) -> (Self, UnboundedReceiver<ConvMessage>, Sender<Option<Secret>>) {
+    let (messages_tx, messages_rx) = unbounded_channel();
+    let (responses_tx, responses_rx) = mpsc::channel();
+
+    let conv = Self {
+      password: RefCell::new(Some(password)),
+      messages: messages_tx,
+      responses: responses_rx,
+    };
+
+    (conv, messages_rx, responses_tx)
+  }
+
+  fn send(&self, kind: MessageKind, msg: &str) -> Result<(), ()> {
+    let message = ConvMessage {
+      kind,
+      message: msg.trim().to_string(),
+    };
+
+    self.messages.send(message).map_err(|_| {
+      error!("pam message receiver was dropped");
+    })
+  }
+
+  fn prompt(&self, kind: MessageKind, msg: &str) -> Result<Secret, ()> {
+    self.send(kind, msg)?;
+
+    match self.responses.recv_timeout(RESPONSE_TIMEOUT) {
+      Ok(Some(response)) => Ok(response),
+      Ok(None) | Err(RecvTimeoutError::Disconnected) => Err(()),
+      Err(RecvTimeoutError::Timeout) => {
+        info!("timed out waiting for pam response");
+        Err(())
+      }
+    }
+  }
+}
+
+impl Converse for ChannelConv {
+  fn prompt_echo(&self, msg: &str) -> Result<Secret, ()> {
+    self.prompt(MessageKind::Visible, msg)
+  }
+
+  fn prompt_blind(&self, msg: &str) -> Result<Secret, ()> {
+    if let Some(password) = self.password.borrow_mut().take() {
+      return Ok(password);
+    }
+
+    self.prompt(MessageKind::Secret, msg)
+  }
+
+  fn info(&self, msg: &str) -> Result<(), ()> {
+    info!("pam info: {msg}");
+    self.send(MessageKind::Info, msg)
+  }
+
+  fn error(&self, msg: &str) -> Result<(), ()> {
+    info!("pam error: {msg}");
+    self.send(MessageKind::Error, msg)
+  }
+}
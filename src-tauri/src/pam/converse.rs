@@ -1,3 +1,5 @@
+use crate::secret::Secret;
+
 /// A trait representing the PAM authentification conversation
 ///
 /// PAM authentification is done as a conversation mechanism, in which PAM
@@ -14,13 +16,13 @@ pub trait Converse {
   /// This would typically be the username. The exact question is provided as the
   /// `msg` argument if you wish to display it to your user.
   #[allow(clippy::result_unit_err)]
-  fn prompt_echo(&self, msg: &str) -> ::std::result::Result<String, ()>;
+  fn prompt_echo(&self, msg: &str) -> ::std::result::Result<Secret, ()>;
   /// PAM requests a value that should be typed blindly by the user
   ///
   /// This would typically be the password. The exact question is provided as the
   /// `msg` argument if you wish to display it to your user.
   #[allow(clippy::result_unit_err)]
-  fn prompt_blind(&self, msg: &str) -> ::std::result::Result<String, ()>;
+  fn prompt_blind(&self, msg: &str) -> ::std::result::Result<Secret, ()>;
   /// This is an informational message from PAM
   #[allow(clippy::result_unit_err)]
   fn info(&self, msg: &str) -> Result<(), ()>;
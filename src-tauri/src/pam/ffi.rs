@@ -5,7 +5,7 @@ use pam_sys::{PamConversation, PamMessage, PamMessageStyle, PamResponse, PamRetu
 
 use super::converse::Converse;
 
-use crate::scrambler::Scrambler;
+use crate::secret::Secret;
 
 pub struct PamConvHandlerWrapper<'a> {
   pub handler: Pin<Box<dyn Converse + 'a>>,
@@ -18,16 +18,26 @@ pub fn make_conversation(conv: &mut PamConvHandlerWrapper) -> PamConversation {
   }
 }
 
-unsafe fn to_cstr(mut s: String) -> *mut c_char {
+/// Copies the secret into a buffer that PAM takes ownership of.
+/// The secret itself is scrambled when it's dropped afterwards.
+unsafe fn to_cstr(s: &Secret) -> *mut c_char {
+  let s = s.expose();
   let a = calloc(1, s.len() + 1) as *mut c_char;
   if a.is_null() {
     panic!("unable to allocate C string");
   }
   memcpy(a as *mut c_void, s.as_ptr() as *const c_void, s.len());
-  s.scramble();
   a
 }
 
+/// Zeroes a response we allocated before freeing it.
+unsafe fn scramble_cstr(s: *mut c_char) {
+  let len = libc::strlen(s);
+  for offset in 0..len {
+    std::ptr::write_volatile(s.add(offset), 0);
+  }
+}
+
 pub extern "C" fn converse(
   num_msg: c_int,
   msg: *mut *mut PamMessage,
@@ -61,14 +71,14 @@ pub extern "C" fn converse(
     match PamMessageStyle::from(m.msg_style) {
       PamMessageStyle::PROMPT_ECHO_ON => {
         if let Ok(handler_response) = wrapper.handler.prompt_echo(msg) {
-          r.resp = unsafe { to_cstr(handler_response) };
+          r.resp = unsafe { to_cstr(&handler_response) };
         } else {
           result = PamReturnCode::CONV_ERR;
         }
       }
       PamMessageStyle::PROMPT_ECHO_OFF => {
         if let Ok(handler_response) = wrapper.handler.prompt_blind(msg) {
-          r.resp = unsafe { to_cstr(handler_response) };
+          r.resp = unsafe { to_cstr(&handler_response) };
         } else {
           result = PamReturnCode::CONV_ERR;
         }
@@ -95,7 +105,10 @@ pub extern "C" fn converse(
     for i in 0..num_msg as isize {
       let r: &mut PamResponse = unsafe { &mut *(resp.offset(i)) };
       if !r.resp.is_null() {
-        unsafe { free(r.resp as *mut c_void) };
+        unsafe {
+          scramble_cstr(r.resp);
+          free(r.resp as *mut c_void);
+        }
       }
     }
 
@@ -1,14 +1,17 @@
+pub mod channel;
 pub mod converse;
 mod env;
 mod ffi;
 pub mod session;
 
-use std::cell::RefCell;
+use std::{cell::RefCell, path::Path};
 
 use thiserror::Error as ThisError;
 
 use pam_sys::PamReturnCode;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::secret::Secret;
 
 #[derive(Debug, ThisError)]
 pub enum PamError {
@@ -18,12 +21,17 @@ pub enum PamError {
   AuthError(String),
   #[error("abort error: {0}")]
   AbortError(String),
+  #[error("password expired: {0}")]
+  NewAuthTokRequired(String),
 }
 
 impl PamError {
   pub fn from_rc(prefix: &str, rc: PamReturnCode) -> PamError {
     match rc {
       PamReturnCode::ABORT => PamError::AbortError(format!("{}: {:?}", prefix, rc)),
+      PamReturnCode::NEW_AUTHTOK_REQD => {
+        PamError::NewAuthTokRequired(format!("{}: {:?}", prefix, rc))
+      }
       PamReturnCode::AUTH_ERR
       | PamReturnCode::MAXTRIES
       | PamReturnCode::CRED_EXPIRED
@@ -37,21 +45,52 @@ impl PamError {
   }
 }
 
-pub struct PasswordConv(RefCell<Option<String>>);
+/// Directories PAM loads service files from, in order of precedence
+const SERVICE_DIRS: &[&str] = &["/etc/pam.d", "/usr/lib/pam.d"];
+
+pub fn service_exists(service: &str) -> bool {
+  SERVICE_DIRS
+    .iter()
+    .any(|dir| Path::new(dir).join(service).exists())
+}
+
+/// Returns the service to authenticate with, falling back to
+/// `fallback` if `service` isn't installed.
+pub fn resolve_service(service: &str, fallback: Option<&str>) -> Result<String, PamError> {
+  if service_exists(service) {
+    return Ok(service.to_string());
+  }
+
+  match fallback {
+    Some(fallback) if service_exists(fallback) => {
+      warn!("pam service {service} not found, falling back to {fallback}");
+      Ok(fallback.to_string())
+    }
+    _ => Err(PamError::Error(format!("pam service {service} not found"))),
+  }
+}
+
+pub struct PasswordConv(RefCell<Option<Secret>>);
 
 impl PasswordConv {
-  pub fn new(password: String) -> Self {
+  pub fn new(password: Secret) -> Self {
     Self(RefCell::new(Some(password)))
   }
 }
 
 impl converse::Converse for PasswordConv {
-  fn prompt_echo(&self, _msg: &str) -> Result<String, ()> {
-    Ok(String::new())
+  fn prompt_echo(&self, _msg: &str) -> Result<Secret, ()> {
+    Ok(Secret::new(String::new()))
   }
 
-  fn prompt_blind(&self, _msg: &str) -> Result<String, ()> {
-    Ok(self.0.borrow_mut().take().unwrap_or_default())
+  fn prompt_blind(&self, _msg: &str) -> Result<Secret, ()> {
+    Ok(
+      self
+        .0
+        .borrow_mut()
+        .take()
+        .unwrap_or_else(|| Secret::new(String::new())),
+    )
   }
 
   fn info(&self, msg: &str) -> Result<(), ()> {
@@ -7,6 +7,7 @@ use std::{
 
 use libc::c_void;
 use pam_sys::{PamFlag, PamHandle, PamItemType, PamReturnCode};
+use tracing::error;
 
 use super::{
   converse::Converse,
@@ -58,6 +59,14 @@ impl<'a> PamSession<'a> {
     }
   }
 
+  pub fn chauthtok(&mut self, flags: PamFlag) -> Result<(), PamError> {
+    self.last_code = pam_sys::chauthtok(self.handle, flags);
+    match self.last_code {
+      PamReturnCode::SUCCESS => Ok(()),
+      rc => Err(PamError::from_rc("pam_chauthtok", rc)),
+    }
+  }
+
   pub fn setcred(&mut self, flags: PamFlag) -> Result<(), PamError> {
     self.last_code = pam_sys::setcred(self.handle, flags);
     match self.last_code {
@@ -121,10 +130,19 @@ impl<'a> PamSession<'a> {
     }
   }
 
-  pub fn end(&mut self) -> Result<(), PamError> {
+  /// Only called on drop, since the handle is invalid afterwards
+  fn end(&mut self) -> Result<(), PamError> {
     match pam_sys::end(self.handle, self.last_code) {
       PamReturnCode::SUCCESS => Ok(()),
       rc => Err(PamError::from_rc("pam_end", rc)),
     }
   }
 }
+
+impl Drop for PamSession<'_> {
+  fn drop(&mut self) {
+    self.end().unwrap_or_else(|err| {
+      error!("failed to end pam session: {err}");
+    });
+  }
+}
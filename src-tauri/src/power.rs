@@ -45,6 +45,20 @@ impl Power {
       .await
   }
 
+  /// Sets the brightness of a backlight device through logind, which
+  /// works without write access to sysfs.
+  pub async fn set_brightness(&self, name: &str, value: u32) -> Result<()> {
+    self
+      .send(
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/session/auto",
+        "org.freedesktop.login1.Session",
+        "SetBrightness",
+        &("backlight", name, value),
+      )
+      .await
+  }
+
   async fn has_owner(&self, name: &str) -> Result<bool> {
     self
       .zbus
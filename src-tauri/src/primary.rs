@@ -0,0 +1,282 @@
+//! Resolves the primary output from the `primary_display` config option.
+//!
+//! Each entry of `primary_display` is a pattern, and the first pattern that
+//! matches any output wins. Patterns match against the output's connector
+//! name (e.g. `DP-1`), or against its description if prefixed with `desc:`
+//! (e.g. `desc:Dell Inc. DELL U2723QE`). Description patterns only need to
+//! match the start of the description. Both support `*` and `?` wildcards.
+//! If a pattern matches several outputs, or no pattern matches at all, the
+//! output with the lowest connector name is used, so the result doesn't
+//! depend on the order the outputs are listed in.
+
+use crate::hyprland::data;
+
+/// Identifying information about an output, as reported by `wl_output`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputInfo {
+  pub name: Option<String>,
+  pub description: Option<String>,
+  pub make: Option<String>,
+  pub model: Option<String>,
+}
+
+impl OutputInfo {
+  /// The output description, falling back to make and model if the
+  /// compositor doesn't provide one.
+  fn description(&self) -> Option<String> {
+    if let Some(description) = &self.description {
+      return Some(description.clone());
+    }
+
+    match (&self.make, &self.model) {
+      (Some(make), Some(model)) => Some(format!("{make} {model}")),
+      (Some(make), None) => Some(make.clone()),
+      (None, Some(model)) => Some(model.clone()),
+      (None, None) => None,
+    }
+  }
+
+  fn matches(&self, pattern: &str) -> bool {
+    if let Some(description) = pattern.strip_prefix("desc:") {
+      let pattern = format!("{}*", description.trim());
+      return self
+        .description()
+        .is_some_and(|description| glob_match(&pattern, &description));
+    }
+
+    self
+      .name
+      .as_deref()
+      .is_some_and(|name| glob_match(pattern, name))
+  }
+}
+
+impl From<&data::Monitor> for OutputInfo {
+  fn from(monitor: &data::Monitor) -> Self {
+    Self {
+      name: Some(monitor.name.clone()),
+      description: Some(monitor.description.clone()),
+      make: monitor.make.clone(),
+      model: monitor.model.clone(),
+    }
+  }
+}
+
+impl From<&smithay_client_toolkit::output::OutputInfo> for OutputInfo {
+  fn from(info: &smithay_client_toolkit::output::OutputInfo) -> Self {
+    Self {
+      name: info.name.clone(),
+      description: info.description.clone(),
+      make: Some(info.make.clone()),
+      model: Some(info.model.clone()),
+    }
+  }
+}
+
+/// Returns the index of the primary output, or `None` if there are no outputs.
+pub fn resolve_primary(patterns: &[String], outputs: &[OutputInfo]) -> Option<usize> {
+  if outputs.is_empty() {
+    return None;
+  }
+
+  let lowest_name = |indices: &mut dyn Iterator<Item = usize>| {
+    // Outputs without a name come last
+    indices.min_by_key(|index| {
+      let name = outputs[*index].name.as_deref();
+      (name.is_none(), name)
+    })
+  };
+
+  patterns
+    .iter()
+    .find_map(|pattern| {
+      lowest_name(&mut (0..outputs.len()).filter(|index| outputs[*index].matches(pattern)))
+    })
+    .or_else(|| lowest_name(&mut (0..outputs.len())))
+}
+
+/// Matches `text` against a pattern, where `*` matches any number of
+/// characters and `?` matches exactly one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+  let pattern = pattern.chars().collect::<Vec<_>>();
+  let text = text.chars().collect::<Vec<_>>();
+
+  let (mut p, mut t) = (0, 0);
+  let mut backtrack: Option<(usize, usize)> = None;
+
+  while t < text.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        backtrack = Some((p, t));
+        p += 1;
+      }
+      Some(c) if *c == '?' || *c == text[t] => {
+        p += 1;
+        t += 1;
+      }
+      _ => match backtrack {
+        // Let the last star consume one more character and try again
+        Some((star_p, star_t)) => {
+          backtrack = Some((star_p, star_t + 1));
+          p = star_p + 1;
+          t = star_t + 1;
+        }
+        None => return false,
+      },
+    }
+  }
+
+  pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn output(name: &str, description: Option<&str>) -> OutputInfo {
+    OutputInfo {
+      name: Some(name.to_string()),
+      description: description.map(str::to_string),
+      ..Default::default()
+    }
+  }
+
+  fn outputs() -> Vec<OutputInfo> {
+    vec![
+      output("eDP-1", Some("BOE 0x0BCA")),
+      output("DP-3", Some("Dell Inc. DELL U2720Q 8LQ2NR2")),
+      output("HDMI-A-1", Some("LG Electronics LG HDR 4K 0x0001A2B3")),
+    ]
+  }
+
+  fn patterns(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|pattern| pattern.to_string()).collect()
+  }
+
+  #[test]
+  fn glob_patterns() {
+    assert!(glob_match("DP-1", "DP-1"));
+    assert!(!glob_match("DP-1", "DP-10"));
+    assert!(!glob_match("DP-10", "DP-1"));
+    assert!(glob_match("DP-*", "DP-10"));
+    assert!(glob_match("DP-*", "DP-"));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("DP-?", "DP-3"));
+    assert!(!glob_match("DP-?", "DP-"));
+    assert!(!glob_match("DP-?", "DP-10"));
+    assert!(glob_match("*-A-*", "HDMI-A-1"));
+    assert!(glob_match("*1", "HDMI-A-1"));
+    assert!(!glob_match("*2", "HDMI-A-1"));
+    // The first star has to give back what the rest of the pattern needs
+    assert!(glob_match("*A*1", "A-A-1"));
+    assert!(glob_match("**?", "x"));
+    assert!(glob_match("caf?", "café"));
+  }
+
+  #[test]
+  fn matches_connector_names() {
+    let outputs = outputs();
+
+    assert_eq!(resolve_primary(&patterns(&["eDP-1"]), &outputs), Some(0));
+    assert_eq!(resolve_primary(&patterns(&["HDMI-*"]), &outputs), Some(2));
+    // Names never match descriptions
+    assert_eq!(resolve_primary(&patterns(&["BOE*"]), &outputs), Some(1));
+  }
+
+  #[test]
+  fn matches_description_prefixes() {
+    let outputs = outputs();
+
+    assert_eq!(
+      resolve_primary(&patterns(&["desc:Dell Inc. DELL U2720Q"]), &outputs),
+      Some(1)
+    );
+    assert_eq!(
+      resolve_primary(&patterns(&["desc: LG Electronics "]), &outputs),
+      Some(2)
+    );
+    assert_eq!(
+      resolve_primary(&patterns(&["desc:* HDR 4K"]), &outputs),
+      Some(2)
+    );
+    assert_eq!(resolve_primary(&patterns(&["desc:BOE"]), &outputs), Some(0));
+    // Only the start of the description
+    assert_eq!(
+      resolve_primary(&patterns(&["desc:HDR 4K"]), &outputs),
+      Some(1)
+    );
+  }
+
+  #[test]
+  fn describes_outputs_by_make_and_model() {
+    let outputs = vec![
+      output("eDP-1", None),
+      OutputInfo {
+        name: Some("DP-1".to_string()),
+        make: Some("Dell Inc.".to_string()),
+        model: Some("DELL U2720Q".to_string()),
+        ..Default::default()
+      },
+    ];
+
+    assert_eq!(
+      resolve_primary(&patterns(&["desc:Dell Inc. DELL"]), &outputs),
+      Some(1)
+    );
+  }
+
+  #[test]
+  fn first_matching_pattern_wins() {
+    let outputs = outputs();
+
+    assert_eq!(
+      resolve_primary(&patterns(&["DP-1", "HDMI-A-1", "DP-3"]), &outputs),
+      Some(2)
+    );
+    assert_eq!(
+      resolve_primary(&patterns(&["desc:Dell*", "eDP-1"]), &outputs),
+      Some(1)
+    );
+    // A pattern matching several outputs picks the lowest name of them
+    assert_eq!(resolve_primary(&patterns(&["*-1"]), &outputs), Some(2));
+  }
+
+  #[test]
+  fn falls_back_to_the_lowest_name() {
+    assert_eq!(resolve_primary(&patterns(&["DP-9"]), &outputs()), Some(1));
+    assert_eq!(resolve_primary(&[], &outputs()), Some(1));
+    assert_eq!(
+      resolve_primary(
+        &patterns(&["DP-1"]),
+        &[OutputInfo::default(), output("eDP-1", None)]
+      ),
+      Some(1)
+    );
+    assert_eq!(
+      resolve_primary(&patterns(&["DP-1"]), &[OutputInfo::default()]),
+      Some(0)
+    );
+  }
+
+  #[test]
+  fn ignores_output_order() {
+    let mut outputs = outputs();
+    let name = |outputs: &[OutputInfo], index: Option<usize>| {
+      index.and_then(|index| outputs[index].name.clone())
+    };
+
+    for patterns in [patterns(&[]), patterns(&["*-1"]), patterns(&["DP-9"])] {
+      let primary = name(&outputs, resolve_primary(&patterns, &outputs));
+      outputs.reverse();
+      assert_eq!(
+        name(&outputs, resolve_primary(&patterns, &outputs)),
+        primary
+      );
+    }
+  }
+
+  #[test]
+  fn no_outputs() {
+    assert_eq!(resolve_primary(&patterns(&["DP-1"]), &[]), None);
+  }
+}
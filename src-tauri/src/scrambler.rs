@@ -1,4 +1,9 @@
-use std::{default::Default, ffi::CString};
+use std::{
+  default::Default,
+  ffi::CString,
+  ptr,
+  sync::atomic::{compiler_fence, Ordering},
+};
 
 /// Scrambling overwrites a buffers content with the default value. Useful to
 /// avoid leaving behind a heap littered with old secrets.
@@ -8,33 +13,77 @@ pub trait Scrambler {
 
 impl<T: Default> Scrambler for Vec<T> {
   fn scramble(&mut self) {
-    let cap = self.capacity();
-    self.truncate(0);
-    for _ in 0..cap {
-      self.push(Default::default())
+    // Volatile writes keep the compiler from optimizing away stores
+    // to memory that is about to be freed
+    for item in self.iter_mut() {
+      unsafe {
+        ptr::drop_in_place(item);
+        ptr::write_volatile(item, T::default());
+      }
+    }
+
+    // Old contents might still linger past the length after truncating
+    for slot in self.spare_capacity_mut() {
+      unsafe { ptr::write_volatile(slot.as_mut_ptr(), T::default()) };
     }
-    self.truncate(0);
+
+    compiler_fence(Ordering::SeqCst);
+    self.clear();
   }
 }
 
 impl Scrambler for String {
   fn scramble(&mut self) {
-    let v = unsafe { self.as_mut_vec() };
-    (0..v.len()).for_each(|idx| v[idx] = 0);
-    self.truncate(0);
+    unsafe { self.as_mut_vec() }.scramble();
   }
 }
 
 impl Scrambler for CString {
   fn scramble(&mut self) {
-    unsafe {
-      let ptr = self.as_ptr();
-      let mut offset = 0;
+    // Converting back into bytes reuses the allocation, which
+    // gives us mutable access to the contents
+    std::mem::take(self).into_bytes_with_nul().scramble();
+  }
+}
 
-      while *ptr.offset(offset) != 0 {
-        *(ptr.offset(offset) as *mut i8) = 0;
-        offset += 1;
-      }
-    }
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The whole allocation, which stays around after scrambling
+  fn allocation(vec: &Vec<u8>) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(vec.as_ptr(), vec.capacity()) }
+  }
+
+  #[test]
+  fn scrambles_vecs() {
+    let mut vec = b"hunter2".to_vec();
+    vec.scramble();
+
+    assert!(vec.is_empty());
+    assert!(allocation(&vec).iter().all(|byte| *byte == 0));
+  }
+
+  #[test]
+  fn scrambles_truncated_contents() {
+    let mut vec = Vec::with_capacity(32);
+    vec.extend_from_slice(b"hunter2, but longer");
+    vec.truncate(6);
+    vec.scramble();
+
+    assert_eq!(vec.capacity(), 32);
+    assert!(allocation(&vec).iter().all(|byte| *byte == 0));
+  }
+
+  #[test]
+  fn scrambles_strings() {
+    let mut string = String::with_capacity(16);
+    string.push_str("hunter2 ☕");
+    string.pop();
+    string.scramble();
+
+    assert!(string.is_empty());
+    let vec = unsafe { string.as_mut_vec() };
+    assert!(allocation(vec).iter().all(|byte| *byte == 0));
   }
 }
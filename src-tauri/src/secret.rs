@@ -0,0 +1,154 @@
+use std::{
+  alloc::{handle_alloc_error, Layout},
+  fmt, ptr,
+  sync::atomic::{compiler_fence, Ordering},
+};
+
+use serde::{Deserialize, Deserializer};
+use tracing::debug;
+
+use crate::scrambler::Scrambler;
+
+/// A password or other secret entered by the user. The contents are copied
+/// into pages of their own, which are locked into memory where possible so
+/// they aren't swapped out, and scrambled when dropped. Secrets can't be
+/// cloned or printed.
+pub struct Secret {
+  ptr: ptr::NonNull<u8>,
+  len: usize,
+  /// Size of the mapping, a multiple of the page size
+  size: usize,
+}
+
+// The mapping is owned by the secret and only read through shared references
+unsafe impl Send for Secret {}
+unsafe impl Sync for Secret {}
+
+impl Secret {
+  /// Takes over the value, scrambling the string it came in
+  pub fn new(mut value: String) -> Self {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let size = value.len().div_ceil(page_size).max(1) * page_size;
+
+    // mlock doesn't nest, so unlocking pages shared with other allocations
+    // would unlock those as well. A mapping of our own has no neighbours.
+    let addr = unsafe {
+      libc::mmap(
+        ptr::null_mut(),
+        size,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+        -1,
+        0,
+      )
+    };
+
+    let Some(ptr) = ptr::NonNull::new(addr as *mut u8).filter(|_| addr != libc::MAP_FAILED) else {
+      handle_alloc_error(Layout::from_size_align(size, page_size).unwrap());
+    };
+
+    // This fails if RLIMIT_MEMLOCK is too low, which is fine
+    if unsafe { libc::mlock(addr, size) } != 0 {
+      debug!("failed to lock secret into memory");
+    }
+
+    unsafe { ptr::copy_nonoverlapping(value.as_ptr(), ptr.as_ptr(), value.len()) };
+    let len = value.len();
+    value.scramble();
+
+    Self { ptr, len, size }
+  }
+
+  pub fn expose(&self) -> &str {
+    // Copied from a string, and only ever truncated to nothing
+    unsafe {
+      std::str::from_utf8_unchecked(std::slice::from_raw_parts(self.ptr.as_ptr(), self.len))
+    }
+  }
+
+  /// Overwrites the whole mapping, leaving an empty secret
+  fn scramble(&mut self) {
+    for offset in 0..self.size {
+      unsafe { ptr::write_volatile(self.ptr.as_ptr().add(offset), 0) };
+    }
+
+    compiler_fence(Ordering::SeqCst);
+    self.len = 0;
+  }
+}
+
+impl From<String> for Secret {
+  fn from(value: String) -> Self {
+    Self::new(value)
+  }
+}
+
+impl Drop for Secret {
+  fn drop(&mut self) {
+    self.scramble();
+
+    let addr = self.ptr.as_ptr() as *mut libc::c_void;
+    unsafe {
+      libc::munlock(addr, self.size);
+      libc::munmap(addr, self.size);
+    }
+  }
+}
+
+impl fmt::Debug for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Secret([redacted])")
+  }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer).map(Self::new)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mapping(secret: &Secret) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(secret.ptr.as_ptr(), secret.size) }
+  }
+
+  #[test]
+  fn exposes_the_value() {
+    let secret = Secret::new("hunter2 ☕".to_string());
+    assert_eq!(secret.expose(), "hunter2 ☕");
+
+    assert_eq!(Secret::new(String::new()).expose(), "");
+  }
+
+  #[test]
+  fn uses_pages_of_its_own() {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let small = Secret::new("hunter2".to_string());
+    let large = Secret::new("x".repeat(page_size + 1));
+
+    assert_eq!(small.ptr.as_ptr() as usize % page_size, 0);
+    assert_eq!(small.size, page_size);
+    assert_eq!(large.size, 2 * page_size);
+    assert_eq!(large.expose().len(), page_size + 1);
+  }
+
+  #[test]
+  fn scrambles_the_mapping() {
+    let mut secret = Secret::new("hunter2".to_string());
+    secret.scramble();
+
+    assert_eq!(secret.expose(), "");
+    assert!(mapping(&secret).iter().all(|byte| *byte == 0));
+  }
+
+  #[test]
+  fn deserializes_into_a_secret() {
+    let secret: Secret = serde_json::from_str("\"hunter2\"").unwrap();
+
+    assert_eq!(secret.expose(), "hunter2");
+    assert_eq!(format!("{secret:?}"), "Secret([redacted])");
+  }
+}
@@ -0,0 +1,338 @@
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use anyhow::Result;
+use smithay_client_toolkit::{
+  reexports::calloop::channel::Sender,
+  seat::keyboard::{KeyEvent, Keysym},
+  session_lock::SessionLockSurface,
+  shm::{slot::SlotPool, Shm},
+};
+use tracing::error;
+use wayland_client::protocol::{wl_output, wl_shm};
+
+use crate::{
+  config::Config,
+  pam::{self, session::PamSession},
+  scrambler::Scrambler,
+  secret::Secret,
+  util::get_current_username,
+};
+
+use super::throttle::Throttle;
+
+/// Longest password that fits the preallocated buffer, so typing never
+/// leaves copies behind in reallocated memory
+const MAX_PASSWORD_LEN: usize = 256;
+const MAX_DOTS: usize = 32;
+const DOT_SIZE: u32 = 12;
+const DOT_GAP: u32 = 8;
+
+const BACKGROUND: u32 = 0xff1c1917;
+const DOT: u32 = 0xffe7e5e4;
+const DOT_CHECKING: u32 = 0xff78716c;
+const DOT_ERROR: u32 = 0xffef4444;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+  Idle,
+  Checking,
+  Failed,
+}
+
+struct LockSurface {
+  surface: SessionLockSurface,
+  output: wl_output::WlOutput,
+  size: (u32, u32),
+}
+
+/// Minimal native lock screen, drawn into shm buffers on the surfaces of
+/// the session lock. Shows one dot per typed character, and turns them red
+/// if the password was wrong. Takes over when the webview lock screen can't
+/// start or keeps failing.
+pub struct Fallback {
+  pool: SlotPool,
+  surfaces: Vec<LockSurface>,
+  password: String,
+  status: Status,
+  /// Receives the result of every authentication
+  auth_tx: Sender<bool>,
+  pam_service: String,
+  username: String,
+  throttle: Arc<Mutex<Throttle>>,
+}
+
+impl Fallback {
+  pub fn new(config: &Config, shm: &Shm, auth_tx: Sender<bool>) -> Result<Self> {
+    let pam_service = pam::resolve_service(
+      &config.lock.pam.service,
+      config.lock.pam.fallback_service.as_deref(),
+    )?;
+    let Some(username) = get_current_username() else {
+      anyhow::bail!("username not available");
+    };
+
+    Ok(Self {
+      pool: SlotPool::new(1, shm)?,
+      surfaces: Vec::new(),
+      password: String::with_capacity(MAX_PASSWORD_LEN),
+      status: Status::Idle,
+      auth_tx,
+      pam_service,
+      username,
+      throttle: Arc::new(Mutex::new(Throttle::load(&config.lock.throttle))),
+    })
+  }
+
+  pub fn has_output(&self, output: &wl_output::WlOutput) -> bool {
+    self.surfaces.iter().any(|s| s.output == *output)
+  }
+
+  /// Takes the lock surface for an output, drawn once it is configured
+  pub fn add_surface(&mut self, surface: SessionLockSurface, output: &wl_output::WlOutput) {
+    self.surfaces.push(LockSurface {
+      surface,
+      output: output.clone(),
+      size: (0, 0),
+    });
+  }
+
+  pub fn remove_output(&mut self, output: &wl_output::WlOutput) {
+    self.surfaces.retain(|s| s.output != *output);
+  }
+
+  /// Draws the surface in its new size. Returns whether it is one of ours.
+  pub fn configure(&mut self, surface: &SessionLockSurface, size: (u32, u32)) -> bool {
+    let Some(found) = self
+      .surfaces
+      .iter_mut()
+      .find(|s| s.surface.wl_surface() == surface.wl_surface())
+    else {
+      return false;
+    };
+
+    found.size = size;
+    self.redraw();
+    true
+  }
+
+  pub fn press_key(&mut self, event: KeyEvent) {
+    if self.status == Status::Checking {
+      return;
+    }
+
+    self.status = Status::Idle;
+
+    match event.keysym {
+      Keysym::Return | Keysym::KP_Enter => self.submit(),
+      Keysym::BackSpace => {
+        self.password.pop();
+      }
+      Keysym::Escape => self.password.scramble(),
+      _ => {
+        if let Some(mut text) = event.utf8 {
+          if self.password.len() + text.len() <= MAX_PASSWORD_LEN {
+            self.password.push_str(&text);
+          }
+
+          text.scramble();
+        }
+      }
+    }
+
+    self.redraw();
+  }
+
+  /// Shows that the password was wrong, a correct one unlocks instead
+  pub fn fail(&mut self) {
+    self.status = Status::Failed;
+    self.redraw();
+  }
+
+  fn redraw(&mut self) {
+    let dots = self.password.chars().count().min(MAX_DOTS);
+    let color = match self.status {
+      Status::Idle => DOT,
+      Status::Checking => DOT_CHECKING,
+      Status::Failed => DOT_ERROR,
+    };
+
+    for lock_surface in &self.surfaces {
+      draw(&mut self.pool, lock_surface, dots, color).unwrap_or_else(|err| {
+        error!("failed to draw lock surface: {err}");
+      });
+    }
+  }
+
+  fn submit(&mut self) {
+    if self.password.is_empty() {
+      return;
+    }
+
+    let password = Secret::new(std::mem::replace(
+      &mut self.password,
+      String::with_capacity(MAX_PASSWORD_LEN),
+    ));
+
+    match self.throttle.lock() {
+      Ok(throttle) if throttle.is_locked_out() => {
+        self.status = Status::Failed;
+        return;
+      }
+      Ok(_) => {}
+      Err(_) => error!("failed to lock throttle"),
+    }
+
+    self.status = Status::Checking;
+
+    let pam_service = self.pam_service.clone();
+    let username = self.username.clone();
+    let throttle = self.throttle.clone();
+    let auth_tx = self.auth_tx.clone();
+    std::thread::spawn(move || {
+      let result = authenticate(&pam_service, &username, password);
+
+      let delay = match (&result, throttle.lock()) {
+        (Ok(()), Ok(mut throttle)) => {
+          throttle.record_success(&username);
+          Duration::ZERO
+        }
+        (Err(reason), Ok(mut throttle)) => throttle.record_failure(&username, reason),
+        (_, Err(_)) => {
+          error!("failed to lock throttle");
+          Duration::ZERO
+        }
+      };
+
+      std::thread::sleep(delay);
+      auth_tx.send(result.is_ok()).unwrap_or_else(|err| {
+        error!("failed to send auth result: {err}");
+      });
+    });
+  }
+}
+
+/// Authenticates without a conversation, so expired passwords
+/// have to be changed through the regular lock screen
+fn authenticate(pam_service: &str, username: &str, password: Secret) -> Result<(), String> {
+  let conv = Box::pin(pam::PasswordConv::new(password));
+  let mut pam = PamSession::start(pam_service, username, conv)
+    .map_err(|_| "failed to start pam session".to_string())?;
+
+  pam
+    .authenticate(pam_sys::PamFlag::NONE)
+    .map_err(|err| err.to_string())?;
+  pam
+    .acct_mgmt(pam_sys::PamFlag::NONE)
+    .map_err(|err| err.to_string())?;
+  pam
+    .setcred(pam_sys::PamFlag::REFRESH_CRED)
+    .map_err(|err| err.to_string())
+}
+
+fn draw(pool: &mut SlotPool, lock_surface: &LockSurface, dots: usize, color: u32) -> Result<()> {
+  let (width, height) = lock_surface.size;
+  if width == 0 || height == 0 {
+    return Ok(());
+  }
+
+  let (buffer, canvas) = pool.create_buffer(
+    width as i32,
+    height as i32,
+    width as i32 * 4,
+    wl_shm::Format::Argb8888,
+  )?;
+
+  fill_rect(canvas, (width, height), (0, 0), (width, height), BACKGROUND);
+
+  // Without any input, a bar shows where the dots will appear
+  let y = height / 2;
+  if dots == 0 {
+    let bar_width = DOT_SIZE * 4;
+    fill_rect(
+      canvas,
+      (width, height),
+      ((width - bar_width.min(width)) / 2, y),
+      (bar_width, 2),
+      color,
+    );
+  } else {
+    // Narrow outputs show fewer dots rather than cutting the row off
+    let fitting = (width + DOT_GAP) / (DOT_SIZE + DOT_GAP);
+    let dots = (dots as u32).min(fitting).max(1);
+    let row_width = dots * DOT_SIZE + (dots - 1) * DOT_GAP;
+    let x = (width - row_width.min(width)) / 2;
+    for dot in 0..dots {
+      fill_rect(
+        canvas,
+        (width, height),
+        (x + dot * (DOT_SIZE + DOT_GAP), y - DOT_SIZE.min(y) / 2),
+        (DOT_SIZE, DOT_SIZE),
+        color,
+      );
+    }
+  }
+
+  let wl_surface = lock_surface.surface.wl_surface();
+  wl_surface.damage_buffer(0, 0, width as i32, height as i32);
+  buffer.attach_to(wl_surface)?;
+  wl_surface.commit();
+
+  Ok(())
+}
+
+/// Fills a rectangle of ARGB8888 pixels, clipped to the canvas
+fn fill_rect(
+  canvas: &mut [u8],
+  (width, height): (u32, u32),
+  (x, y): (u32, u32),
+  (w, h): (u32, u32),
+  color: u32,
+) {
+  let color = color.to_le_bytes();
+  let (x, y) = (x.min(width), y.min(height));
+  let (x_end, y_end) = ((x + w).min(width), (y + h).min(height));
+
+  for row in y..y_end {
+    let start = ((row * width + x) * 4) as usize;
+    let end = ((row * width + x_end) * 4) as usize;
+    for pixel in canvas[start..end].chunks_exact_mut(4) {
+      pixel.copy_from_slice(&color);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fill_rect_clips_to_the_canvas() {
+    let (width, height) = (4, 2);
+    let mut canvas = vec![0; (width * height * 4) as usize];
+
+    fill_rect(&mut canvas, (width, height), (2, 1), (8, 8), DOT);
+    let filled = canvas
+      .chunks_exact(4)
+      .map(|pixel| pixel == DOT.to_le_bytes())
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      filled,
+      [false, false, false, false, false, false, true, true]
+    );
+  }
+
+  #[test]
+  fn fill_rect_ignores_rectangles_outside_the_canvas() {
+    let (width, height) = (4, 2);
+    let mut canvas = vec![0; (width * height * 4) as usize];
+
+    fill_rect(&mut canvas, (width, height), (10, 0), (2, 2), DOT);
+    fill_rect(&mut canvas, (width, height), (0, 5), (2, 2), DOT);
+
+    assert!(canvas.iter().all(|&byte| byte == 0));
+  }
+}
@@ -0,0 +1,165 @@
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use futures::StreamExt;
+use tracing::info;
+
+use crate::logind::ManagerProxy;
+
+/// Time after an idle lock in which any input unlocks without a password.
+/// It's measured on `CLOCK_BOOTTIME`, which keeps counting while the system
+/// sleeps, and ends for good once the system goes to sleep.
+pub struct Grace {
+  /// Boot time at which the grace period ends
+  until: Mutex<Option<Duration>>,
+}
+
+impl Grace {
+  pub fn new(seconds: u64) -> Self {
+    Self::starting_at(boottime(), seconds)
+  }
+
+  fn starting_at(now: Duration, seconds: u64) -> Self {
+    Self {
+      until: Mutex::new((seconds > 0).then(|| now + Duration::from_secs(seconds))),
+    }
+  }
+
+  pub fn remaining(&self) -> Duration {
+    self.remaining_at(boottime())
+  }
+
+  fn remaining_at(&self, now: Duration) -> Duration {
+    let until = match self.until.lock() {
+      Ok(until) => *until,
+      Err(poisoned) => *poisoned.into_inner(),
+    };
+
+    until.map_or(Duration::ZERO, |until| until.saturating_sub(now))
+  }
+
+  pub fn end(&self) {
+    match self.until.lock() {
+      Ok(mut until) => *until = None,
+      Err(poisoned) => *poisoned.into_inner() = None,
+    }
+  }
+
+  /// Ends the grace period when the system prepares to sleep
+  pub async fn end_on_sleep(&self, zbus_conn: &zbus::Connection) -> Result<()> {
+    let manager = ManagerProxy::new(zbus_conn).await?;
+    let mut sleep = manager.receive_prepare_for_sleep().await?;
+
+    while let Some(signal) = sleep.next().await {
+      if *signal.args()?.start() {
+        info!("ending grace period before sleep");
+        self.end();
+        break;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn boottime() -> Duration {
+  let mut time = libc::timespec {
+    tv_sec: 0,
+    tv_nsec: 0,
+  };
+
+  // Can't fail with a valid clock and pointer
+  unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut time) };
+  Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use zbus::{dbus_interface, SignalContext};
+
+  use super::*;
+  use crate::testing::PrivateBus;
+
+  const START: Duration = Duration::from_secs(1000);
+
+  #[test]
+  fn counts_down() {
+    let grace = Grace::starting_at(START, 5);
+
+    assert_eq!(grace.remaining_at(START), Duration::from_secs(5));
+    assert_eq!(
+      grace.remaining_at(START + Duration::from_secs(2)),
+      Duration::from_secs(3)
+    );
+    assert_eq!(
+      grace.remaining_at(START + Duration::from_secs(5)),
+      Duration::ZERO
+    );
+  }
+
+  #[test]
+  fn is_off_without_seconds() {
+    assert_eq!(
+      Grace::starting_at(START, 0).remaining_at(START),
+      Duration::ZERO
+    );
+  }
+
+  #[test]
+  fn stays_ended() {
+    let grace = Grace::starting_at(START, 5);
+    grace.end();
+
+    assert_eq!(grace.remaining_at(START), Duration::ZERO);
+  }
+
+  #[test]
+  fn counts_on_boottime() {
+    let grace = Grace::new(5);
+    let remaining = grace.remaining();
+
+    assert!(remaining > Duration::from_secs(4) && remaining <= Duration::from_secs(5));
+  }
+
+  struct FakeManager;
+
+  #[dbus_interface(name = "org.freedesktop.login1.Manager")]
+  impl FakeManager {
+    #[dbus_interface(signal)]
+    async fn prepare_for_sleep(ctxt: &SignalContext<'_>, start: bool) -> zbus::Result<()>;
+  }
+
+  #[tokio::test]
+  async fn ends_before_sleep() {
+    let bus = PrivateBus::start();
+    let logind = bus
+      .connection()
+      .name("org.freedesktop.login1")
+      .unwrap()
+      .serve_at("/org/freedesktop/login1", FakeManager)
+      .unwrap()
+      .build()
+      .await
+      .unwrap();
+
+    let grace = Arc::new(Grace::new(60));
+    let watcher_conn = bus.connect().await;
+    let watcher = tokio::spawn({
+      let grace = grace.clone();
+      async move { grace.end_on_sleep(&watcher_conn).await }
+    });
+
+    let ctxt = SignalContext::new(&logind, "/org/freedesktop/login1").unwrap();
+    // The watcher may not be subscribed yet, so keep asking until it is
+    while !watcher.is_finished() {
+      FakeManager::prepare_for_sleep(&ctxt, false).await.unwrap();
+      FakeManager::prepare_for_sleep(&ctxt, true).await.unwrap();
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    watcher.await.unwrap().unwrap();
+    assert_eq!(grace.remaining(), Duration::ZERO);
+  }
+}
@@ -1,18 +1,36 @@
-use std::time::Duration;
+use std::{
+  os::fd::OwnedFd,
+  sync::{mpsc, Arc, Mutex},
+  time::Duration,
+};
 
 use anyhow::Result;
+use futures::StreamExt;
 use smithay_client_toolkit::reexports::calloop::channel::{channel, Sender};
 use tauri::{Emitter, Manager};
-use tracing::error;
+use tracing::{error, info};
 
 use crate::{
+  background,
   battery::{BatteryState, BatterySubscription},
-  config::Config,
-  pam,
+  config::{self, Config},
+  fingerprint::Fingerprint,
+  logind,
+  pam::{self, PamError},
   power::Power,
-  util::get_current_username,
+  secret::Secret,
+  util::{self, get_current_username},
+};
+
+use self::{
+  grace::Grace,
+  throttle::{AttemptState, Throttle},
 };
 
+mod fallback;
+mod grace;
+mod supervisor;
+mod throttle;
 mod wayland;
 
 struct TauriState<'a> {
@@ -21,35 +39,88 @@ struct TauriState<'a> {
   window_ready_tx: Sender<()>,
   battery: BatterySubscription<'a>,
   power: Power,
+  /// Responses for the PAM conversation that is currently running
+  pam_responses: Mutex<Option<mpsc::Sender<Option<Secret>>>>,
+  pam_service: String,
+  throttle: Mutex<Throttle>,
+  /// Any input unlocks without a password for a while after an idle lock
+  grace: Arc<Grace>,
 }
 
-pub fn run(config: Config, should_suspend: bool) -> Result<()> {
+/// Locks the session. The sleep inhibitor, if any, is released once the
+/// compositor reports the session as locked. The session stays locked if
+/// the lock screen fails, the native lock screen takes over instead.
+pub fn run(
+  config: Config,
+  should_suspend: bool,
+  grace: u64,
+  inhibitor: Option<OwnedFd>,
+) -> Result<()> {
+  // Without a usable service the session could never be unlocked
+  let pam_service = pam::resolve_service(
+    &config.lock.pam.service,
+    config.lock.pam.fallback_service.as_deref(),
+  )?;
+
+  let (ui_failed_tx, ui_failed_rx) = channel();
+  supervisor::watch_panics(ui_failed_tx.clone());
+
+  let grace = Arc::new(Grace::new(grace));
+
   let rt = tokio::runtime::Runtime::new()?;
   rt.block_on(async {
     tauri::async_runtime::set(tokio::runtime::Handle::current());
 
+    let zbus_conn = zbus::Connection::system().await?;
+
+    let (locked_tx, locked_rx) = tokio::sync::oneshot::channel();
+    let suspend_power = should_suspend.then(|| Power::new(zbus_conn.clone()));
+    tokio::spawn(after_locked(locked_rx, inhibitor, suspend_power));
+
+    let background = config.lock.appearance.background.clone();
     let app = tauri::Builder::default()
+      .register_uri_scheme_protocol(background::SCHEME, move |_, _| {
+        background::respond(background.as_deref())
+      })
       .invoke_handler(tauri::generate_handler![
+        get_config,
         poweroff,
         submit_password,
+        respond_auth_message,
+        get_attempts,
+        get_unlock_users,
+        get_grace_remaining,
+        grace_unlock,
         suspend,
         get_battery_state,
         window_ready,
       ])
-      .build(tauri::generate_context!())?;
+      .build(tauri::generate_context!());
+
+    let app = match app {
+      Ok(app) => app,
+      Err(err) => {
+        error!("failed to start the lock screen, using the native one: {err}");
+        return tokio::task::spawn_blocking(move || wayland::lock_native(config, locked_tx))
+          .await?;
+      }
+    };
 
-    let zbus_conn = zbus::Connection::system().await?;
     let battery = BatterySubscription::new(app.handle(), &zbus_conn).await?;
-    let power = Power::new(zbus_conn);
+    let power = Power::new(zbus_conn.clone());
 
     let (unlock_tx, unlock_rx) = channel();
     let (window_ready_tx, window_ready_rx) = channel();
     app.manage(TauriState {
       config: config.clone(),
       window_ready_tx: window_ready_tx.clone(),
-      unlock_tx,
+      unlock_tx: unlock_tx.clone(),
       battery,
       power,
+      pam_responses: Mutex::new(None),
+      pam_service,
+      throttle: Mutex::new(Throttle::load(&config.lock.throttle)),
+      grace: grace.clone(),
     });
 
     let lock_handle = wayland::lock_session(
@@ -58,18 +129,40 @@ pub fn run(config: Config, should_suspend: bool) -> Result<()> {
       unlock_rx,
       window_ready_tx,
       window_ready_rx,
+      ui_failed_tx,
+      ui_failed_rx,
+      locked_tx,
     )?;
 
-    if should_suspend {
-      let handle = app.handle().clone();
-      tokio::spawn(async move {
-        let state = handle.state::<TauriState>();
-        tokio::time::sleep(Duration::from_millis(500)).await;
-        state.power.suspend().await.unwrap_or_else(|err| {
-          error!("failed to suspend: {err}");
-        });
+    // Waking up must not unlock the session without a password
+    let sleep_conn = zbus_conn.clone();
+    tokio::spawn(async move {
+      grace.end_on_sleep(&sleep_conn).await.unwrap_or_else(|err| {
+        error!("failed to listen for sleep: {err}");
+        grace.end();
       });
-    }
+    });
+
+    let logind_conn = zbus_conn.clone();
+    let logind_unlock_tx = unlock_tx.clone();
+    tokio::spawn(async move {
+      unlock_on_request(&logind_conn, logind_unlock_tx)
+        .await
+        .unwrap_or_else(|err| {
+          error!("failed to listen for unlock requests: {err}");
+        });
+    });
+
+    // Fingerprint verification runs alongside password entry,
+    // whichever succeeds first unlocks the session
+    let fingerprint_handle = app.handle().clone();
+    tokio::spawn(async move {
+      verify_fingerprint(&fingerprint_handle, &zbus_conn, unlock_tx)
+        .await
+        .unwrap_or_else(|err| {
+          error!("failed to verify fingerprint: {err}");
+        });
+    });
 
     app.run(|_, _| {});
     lock_handle
@@ -80,6 +173,72 @@ pub fn run(config: Config, should_suspend: bool) -> Result<()> {
   })
 }
 
+/// Releases the sleep inhibitor once the session is locked, and suspends
+/// if asked to. Sleeping before the session is locked would briefly show
+/// its contents on wake up.
+async fn after_locked(
+  locked_rx: tokio::sync::oneshot::Receiver<()>,
+  inhibitor: Option<OwnedFd>,
+  suspend: Option<Power>,
+) {
+  if locked_rx.await.is_err() {
+    return;
+  }
+
+  drop(inhibitor);
+
+  if let Some(power) = suspend {
+    power.suspend().await.unwrap_or_else(|err| {
+      error!("failed to suspend: {err}");
+    });
+  }
+}
+
+/// Unlocks when logind asks for it, e.g. through `loginctl unlock-session`
+async fn unlock_on_request(zbus_conn: &zbus::Connection, unlock_tx: Sender<()>) -> Result<()> {
+  let session = logind::current_session(zbus_conn).await?;
+  let mut unlock = session.receive_unlock().await?;
+
+  if unlock.next().await.is_some() {
+    info!("unlocking session on request of logind");
+    unlock_tx.send(())?;
+  }
+
+  Ok(())
+}
+
+async fn verify_fingerprint(
+  app: &tauri::AppHandle,
+  zbus_conn: &zbus::Connection,
+  unlock_tx: Sender<()>,
+) -> Result<()> {
+  let Some(username) = get_current_username() else {
+    anyhow::bail!("username not available");
+  };
+
+  let Some(fingerprint) = Fingerprint::new(zbus_conn).await? else {
+    return Ok(());
+  };
+
+  if !fingerprint.verify(&username, |event| event.emit(app)).await? {
+    return Ok(());
+  }
+
+  let state = app.state::<TauriState>();
+  let mut throttle = state
+    .throttle
+    .lock()
+    .map_err(|_| anyhow::anyhow!("failed to lock throttle"))?;
+
+  if throttle.is_locked_out() {
+    anyhow::bail!("too many failed attempts, ignoring fingerprint match");
+  }
+
+  throttle.record_success(&username);
+  unlock_tx.send(())?;
+  Ok(())
+}
+
 #[tauri::command]
 async fn window_ready(app: tauri::AppHandle) {
   let state = app.state::<TauriState>();
@@ -118,55 +277,294 @@ async fn get_battery_state(app: tauri::AppHandle) -> Option<BatteryState> {
   state.battery.get_state().await.unwrap_or(None)
 }
 
-#[tauri::command]
-async fn submit_password(app: tauri::AppHandle, window: tauri::WebviewWindow, value: String) {
+/// Checks the PAM setup for the lock screen without locking.
+pub fn check_pam(config: Config) -> Result<()> {
+  let service = pam::resolve_service(
+    &config.lock.pam.service,
+    config.lock.pam.fallback_service.as_deref(),
+  )?;
+  println!("pam service: {service}");
+
   let Some(username) = get_current_username() else {
+    anyhow::bail!("username not available");
+  };
+
+  for admin in &config.lock.pam.admin_users {
+    if !util::user_exists(admin) {
+      anyhow::bail!("admin user {admin} does not exist");
+    }
+  }
+
+  println!("admin users: {}", config.lock.pam.admin_users.join(", "));
+
+  // Prompts can't be answered here, so this only checks that the
+  // service loads and the account is in good standing
+  let conv = Box::pin(pam::PasswordConv::new(Secret::new(String::new())));
+  let mut session = pam::session::PamSession::start(&service, &username, conv)?;
+  println!("pam session started for {username}");
+
+  match session.acct_mgmt(pam_sys::PamFlag::NONE) {
+    Ok(()) => println!("account of {username} is valid"),
+    Err(PamError::NewAuthTokRequired(_)) => {
+      println!("password of {username} expired and must be changed on unlock")
+    }
+    Err(err) => anyhow::bail!("account check for {username} failed: {err}"),
+  }
+
+  for admin in &config.lock.pam.admin_users {
+    check_admin_unlock(&service, admin)?;
+  }
+
+  Ok(())
+}
+
+/// Authenticates an admin user with the password typed in. pam_unix checks
+/// passwords through unix_chkpwd when not running as root, which only
+/// accepts the password of the calling user, so a service that unlocks as
+/// other users needs a module that can check their passwords.
+fn check_admin_unlock(service: &str, admin: &str) -> Result<()> {
+  let password = util::read_password(&format!("password of {admin} (empty to skip): "))?;
+  if password.expose().is_empty() {
+    println!("skipped checking that {admin} can unlock, admin unlock may not work");
+    return Ok(());
+  }
+
+  let conv = Box::pin(pam::PasswordConv::new(password));
+  let mut session = pam::session::PamSession::start(service, admin, conv)?;
+
+  match session.authenticate(pam_sys::PamFlag::NONE) {
+    Ok(()) => println!("{admin} can unlock the session"),
+    Err(PamError::AuthError(err)) if unsafe { libc::geteuid() } != 0 => anyhow::bail!(
+      "{admin} failed to authenticate: {err}. If the password is right, {service} \
+       can't check passwords of other users, e.g. because it uses pam_unix"
+    ),
+    Err(err) => anyhow::bail!("{admin} failed to authenticate: {err}"),
+  }
+
+  Ok(())
+}
+
+/// Users that can unlock the session, starting with the locked user
+#[tauri::command]
+async fn get_unlock_users(app: tauri::AppHandle) -> Vec<String> {
+  let state = app.state::<TauriState>();
+  get_current_username()
+    .into_iter()
+    .chain(state.config.lock.pam.admin_users.iter().cloned())
+    .collect()
+}
+
+/// Milliseconds left in which any input unlocks the session
+#[tauri::command]
+async fn get_grace_remaining(app: tauri::AppHandle) -> u64 {
+  let state = app.state::<TauriState>();
+  state.grace.remaining().as_millis() as u64
+}
+
+/// Unlocks without a password if still within the grace period
+/// after an idle lock. Returns whether the session was unlocked.
+#[tauri::command]
+async fn grace_unlock(app: tauri::AppHandle) -> bool {
+  let state = app.state::<TauriState>();
+  if state.grace.remaining().is_zero() {
+    return false;
+  }
+
+  state.unlock_tx.send(()).unwrap_or_else(|err| {
+    error!("failed to send unlock signal: {err}");
+  });
+
+  true
+}
+
+#[tauri::command]
+async fn get_config(app: tauri::AppHandle) -> config::Lock {
+  let state = app.state::<TauriState>();
+  state.config.lock.clone()
+}
+
+#[tauri::command]
+async fn get_attempts(app: tauri::AppHandle) -> Option<AttemptState> {
+  let state = app.state::<TauriState>();
+  let throttle = state.throttle.lock().ok()?;
+  Some(throttle.state())
+}
+
+#[tauri::command]
+async fn submit_password(
+  app: tauri::AppHandle,
+  window: tauri::WebviewWindow,
+  value: Secret,
+  user: Option<String>,
+) {
+  let Some(current_username) = get_current_username() else {
     window
       .emit("password-error", "username not available")
       .unwrap_or_else(|err| error!("failed to emit: {err}"));
     return;
   };
 
-  let authenticated = tokio::task::spawn_blocking(move || {
-    let conv = Box::pin(pam::PasswordConv::new(value));
-    let Ok(mut pam) = pam::session::PamSession::start("dash2", &username, conv) else {
-      window
-        .emit("password-error", "failed to start pam session")
-        .unwrap_or_else(|err| error!("failed to emit: {err}"));
-      return false;
+  // Admin users can unlock in place of the locked user
+  let username = match user {
+    Some(user) if user != current_username => {
+      let state = app.state::<TauriState>();
+      if !state.config.lock.pam.admin_users.contains(&user) {
+        window
+          .emit(
+            "password-error",
+            format!("{user} can't unlock this session"),
+          )
+          .unwrap_or_else(|err| error!("failed to emit: {err}"));
+        return;
+      }
+
+      user
+    }
+    _ => current_username,
+  };
+
+  let (conv, mut messages, responses) = pam::channel::ChannelConv::new(value);
+
+  {
+    let state = app.state::<TauriState>();
+    let Ok(mut pam_responses) = state.pam_responses.lock() else {
+      error!("failed to lock pam responses");
+      return;
     };
 
-    if let Err(err) = pam.authenticate(pam_sys::PamFlag::NONE) {
+    // The UI waits for a reply to every submission
+    if pam_responses.is_some() {
+      error!("authentication is already in progress");
       window
-        .emit("password-error", err.to_string())
+        .emit("password-error", "authentication is already in progress")
         .unwrap_or_else(|err| error!("failed to emit: {err}"));
-      return false;
+      return;
+    }
+
+    let Ok(throttle) = state.throttle.lock() else {
+      error!("failed to lock throttle");
+      return;
     };
 
-    if let Err(err) = pam.setcred(pam_sys::PamFlag::REFRESH_CRED) {
+    if throttle.is_locked_out() {
+      emit_attempts(&window, throttle.state());
       window
-        .emit("password-error", err.to_string())
+        .emit(
+          "password-error",
+          "too many failed attempts, reboot to unlock",
+        )
         .unwrap_or_else(|err| error!("failed to emit: {err}"));
-      return false;
-    };
+      return;
+    }
+
+    *pam_responses = Some(responses);
+  }
 
-    true
+  let message_window = window.clone();
+  tokio::spawn(async move {
+    while let Some(message) = messages.recv().await {
+      message_window
+        .emit("auth-message", message)
+        .unwrap_or_else(|err| error!("failed to emit: {err}"));
+    }
+  });
+
+  let pam_username = username.clone();
+  let pam_service = app.state::<TauriState>().pam_service.clone();
+  let result = tokio::task::spawn_blocking(move || {
+    let conv = Box::pin(conv);
+    let mut pam = pam::session::PamSession::start(&pam_service, &pam_username, conv)
+      .map_err(|_| "failed to start pam session".to_string())?;
+
+    pam
+      .authenticate(pam_sys::PamFlag::NONE)
+      .map_err(|err| err.to_string())?;
+
+    // Expired or locked accounts pass authentication, but not this.
+    // An expired password has to be changed before unlocking, and the
+    // conversation prompts for the new one.
+    match pam.acct_mgmt(pam_sys::PamFlag::NONE) {
+      Ok(()) => {}
+      Err(PamError::NewAuthTokRequired(_)) => pam
+        .chauthtok(pam_sys::PamFlag::CHANGE_EXPIRED_AUTHTOK)
+        .map_err(|err| err.to_string())?,
+      Err(err) => return Err(err.to_string()),
+    }
+
+    pam
+      .setcred(pam_sys::PamFlag::REFRESH_CRED)
+      .map_err(|err| err.to_string())
   })
   .await
-  .unwrap_or_else(|err| {
-    error!("failed to authenticate: {err}");
-    false
-  });
+  .unwrap_or_else(|err| Err(err.to_string()));
+
+  let state = app.state::<TauriState>();
+  if let Err(reason) = result {
+    let (delay, attempts) = match state.throttle.lock() {
+      Ok(mut throttle) => (
+        throttle.record_failure(&username, &reason),
+        throttle.state(),
+      ),
+      Err(_) => {
+        error!("failed to lock throttle");
+        (Duration::ZERO, AttemptState::default())
+      }
+    };
+
+    // The conversation slot stays taken while waiting, so no
+    // new attempt can be started before the delay is over
+    tokio::time::sleep(delay).await;
+
+    emit_attempts(&window, attempts);
+    window
+      .emit("password-error", reason)
+      .unwrap_or_else(|err| error!("failed to emit: {err}"));
+
+    if attempts.max.is_some_and(|max| attempts.failed >= max) {
+      window
+        .emit("lockout-alert", attempts)
+        .unwrap_or_else(|err| error!("failed to emit: {err}"));
+    }
+
+    if let Ok(mut pam_responses) = state.pam_responses.lock() {
+      *pam_responses = None;
+    }
 
-  if !authenticated {
     return;
   }
 
-  app
-    .state::<TauriState>()
-    .unlock_tx
-    .send(())
-    .unwrap_or_else(|err| {
-      error!("failed to send unlock signal: {err}");
-    })
+  if let Ok(mut pam_responses) = state.pam_responses.lock() {
+    *pam_responses = None;
+  }
+
+  if let Ok(mut throttle) = state.throttle.lock() {
+    throttle.record_success(&username);
+  }
+
+  state.unlock_tx.send(()).unwrap_or_else(|err| {
+    error!("failed to send unlock signal: {err}");
+  })
+}
+
+fn emit_attempts(window: &tauri::WebviewWindow, attempts: AttemptState) {
+  window
+    .emit("attempts", attempts)
+    .unwrap_or_else(|err| error!("failed to emit: {err}"));
+}
+
+/// Answers the prompt that was last sent as an `auth-message` event.
+/// Responding with `None` aborts the conversation.
+#[tauri::command]
+async fn respond_auth_message(app: tauri::AppHandle, value: Option<Secret>) -> Result<(), String> {
+  let state = app.state::<TauriState>();
+  let pam_responses = state
+    .pam_responses
+    .lock()
+    .map_err(|_| "failed to lock pam responses".to_string())?;
+
+  let Some(responses) = pam_responses.as_ref() else {
+    return Err("no authentication in progress".to_string());
+  };
+
+  responses.send(value).map_err(|err| err.to_string())
 }
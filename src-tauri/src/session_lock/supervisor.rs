@@ -0,0 +1,47 @@
+use std::{sync::Mutex, thread, time::Duration};
+
+use smithay_client_toolkit::reexports::calloop::channel::Sender;
+use tracing::error;
+
+/// Name of the thread that holds the session lock
+pub const LOCK_THREAD: &str = "session-lock";
+
+/// Web process crashes that are recovered from by reloading the webview.
+/// After that the native lock screen takes over.
+pub const MAX_WEBVIEW_CRASHES: u32 = 3;
+
+/// How long the webview lock screen may take to load before the native
+/// lock screen takes over
+pub const UI_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reports panics in any thread but the one holding the session lock as a
+/// failed lock screen, so the native lock screen takes over the lock.
+///
+/// A panic on the main thread would abort inside a GTK callback and take
+/// the lock with it, so that thread is parked instead.
+pub fn watch_panics(ui_failed_tx: Sender<()>) {
+  let ui_failed_tx = Mutex::new(ui_failed_tx);
+  let default_hook = std::panic::take_hook();
+
+  std::panic::set_hook(Box::new(move |info| {
+    default_hook(info);
+
+    let current = thread::current();
+    if current.name() == Some(LOCK_THREAD) {
+      return;
+    }
+
+    match ui_failed_tx.lock() {
+      Ok(ui_failed_tx) => ui_failed_tx.send(()).unwrap_or_else(|err| {
+        error!("failed to report failed lock screen: {err}");
+      }),
+      Err(_) => error!("failed to lock ui failure sender"),
+    }
+
+    if current.name() == Some("main") {
+      loop {
+        thread::park();
+      }
+    }
+  }));
+}
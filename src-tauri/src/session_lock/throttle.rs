@@ -0,0 +1,258 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::config::{LockoutAction, Throttle as ThrottleConfig};
+
+/// Target for audit log entries, so they can be filtered separately
+const AUDIT_TARGET: &str = "dash2::audit";
+
+/// Failed attempts are kept in the runtime dir, so restarting the lock
+/// screen doesn't reset them but rebooting does.
+const ATTEMPTS_FILE: &str = "dash2/lock-attempts";
+
+/// Attempt counts shown in the login window
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct AttemptState {
+  pub failed: u32,
+  pub max: Option<u32>,
+  pub locked_out: bool,
+}
+
+pub struct Throttle {
+  delays: Vec<Duration>,
+  max_attempts: Option<u32>,
+  on_max_attempts: LockoutAction,
+  failed: u32,
+  /// Where failed attempts are persisted
+  path: Option<PathBuf>,
+}
+
+impl Throttle {
+  pub fn load(config: &ThrottleConfig) -> Self {
+    Self::load_from(config, attempts_path())
+  }
+
+  fn load_from(config: &ThrottleConfig, path: Option<PathBuf>) -> Self {
+    let failed = path
+      .as_ref()
+      .and_then(|path| std::fs::read_to_string(path).ok())
+      .and_then(|raw| raw.trim().parse().ok())
+      .unwrap_or(0);
+
+    Self {
+      delays: config
+        .delays
+        .iter()
+        .copied()
+        .map(Duration::from_secs)
+        .collect(),
+      max_attempts: (config.max_attempts > 0).then_some(config.max_attempts),
+      on_max_attempts: config.on_max_attempts,
+      failed,
+      path,
+    }
+  }
+
+  pub fn state(&self) -> AttemptState {
+    AttemptState {
+      failed: self.failed,
+      max: self.max_attempts,
+      locked_out: self.is_locked_out(),
+    }
+  }
+
+  /// Whether no more attempts are accepted until the next reboot.
+  pub fn is_locked_out(&self) -> bool {
+    self.on_max_attempts == LockoutAction::RequireReboot && self.max_reached()
+  }
+
+  /// Records a failed attempt and returns how long to wait before
+  /// accepting the next one.
+  pub fn record_failure(&mut self, user: &str, reason: &str) -> Duration {
+    self.failed += 1;
+    self.persist();
+
+    warn!(
+      target: AUDIT_TARGET,
+      "failed unlock attempt {} for {user}: {reason}", self.failed
+    );
+
+    if self.max_attempts == Some(self.failed) {
+      error!(
+        target: AUDIT_TARGET,
+        "{user} reached the maximum of {} failed unlock attempts", self.failed
+      );
+    }
+
+    let index = (self.failed as usize - 1).min(self.delays.len().saturating_sub(1));
+    self.delays.get(index).copied().unwrap_or_default()
+  }
+
+  pub fn record_success(&mut self, user: &str) {
+    if self.failed > 0 {
+      warn!(
+        target: AUDIT_TARGET,
+        "{user} unlocked after {} failed attempts", self.failed
+      );
+    }
+
+    self.failed = 0;
+    self.persist();
+  }
+
+  pub fn max_reached(&self) -> bool {
+    self.max_attempts.is_some_and(|max| self.failed >= max)
+  }
+
+  fn persist(&self) {
+    let Some(path) = &self.path else {
+      return;
+    };
+
+    let result = path
+      .parent()
+      .map(std::fs::create_dir_all)
+      .unwrap_or(Ok(()))
+      .and_then(|_| std::fs::write(path, self.failed.to_string()));
+
+    result.unwrap_or_else(|err| {
+      error!("failed to persist unlock attempts: {err}");
+    });
+  }
+}
+
+fn attempts_path() -> Option<PathBuf> {
+  dirs::runtime_dir().map(|dir| dir.join(ATTEMPTS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn config(delays: &[u64], max_attempts: u32, on_max_attempts: LockoutAction) -> ThrottleConfig {
+    ThrottleConfig {
+      delays: delays.to_vec(),
+      max_attempts,
+      on_max_attempts,
+    }
+  }
+
+  /// A throttle persisting into a directory of its own
+  fn throttle(dir: &TempDir, config: &ThrottleConfig) -> Throttle {
+    Throttle::load_from(config, Some(dir.path().join(ATTEMPTS_FILE)))
+  }
+
+  #[test]
+  fn delays_progressively_up_to_the_last_entry() {
+    let dir = TempDir::new("throttle-delays");
+    let mut throttle = throttle(&dir, &config(&[0, 1, 5], 0, LockoutAction::Alert));
+
+    let delays = (0..5)
+      .map(|_| throttle.record_failure("alice", "wrong password").as_secs())
+      .collect::<Vec<_>>();
+
+    assert_eq!(delays, [0, 1, 5, 5, 5]);
+  }
+
+  #[test]
+  fn never_delays_without_delays() {
+    let dir = TempDir::new("throttle-no-delays");
+    let mut throttle = throttle(&dir, &config(&[], 0, LockoutAction::Alert));
+
+    for _ in 0..3 {
+      assert_eq!(
+        throttle.record_failure("alice", "wrong password"),
+        Duration::ZERO
+      );
+    }
+  }
+
+  #[test]
+  fn alerts_without_locking_out() {
+    let dir = TempDir::new("throttle-alert");
+    let mut throttle = throttle(&dir, &config(&[0], 2, LockoutAction::Alert));
+
+    throttle.record_failure("alice", "wrong password");
+    assert!(!throttle.max_reached());
+
+    throttle.record_failure("alice", "wrong password");
+    assert!(throttle.max_reached());
+    assert!(!throttle.is_locked_out());
+
+    let state = throttle.state();
+    assert_eq!(
+      (state.failed, state.max, state.locked_out),
+      (2, Some(2), false)
+    );
+  }
+
+  #[test]
+  fn locks_out_until_reboot() {
+    let dir = TempDir::new("throttle-reboot");
+    let mut throttle = throttle(&dir, &config(&[0], 2, LockoutAction::RequireReboot));
+
+    throttle.record_failure("alice", "wrong password");
+    assert!(!throttle.is_locked_out());
+
+    throttle.record_failure("alice", "wrong password");
+    assert!(throttle.is_locked_out());
+    assert!(throttle.state().locked_out);
+  }
+
+  #[test]
+  fn has_no_maximum_when_disabled() {
+    let dir = TempDir::new("throttle-unlimited");
+    let mut throttle = throttle(&dir, &config(&[0], 0, LockoutAction::RequireReboot));
+
+    for _ in 0..10 {
+      throttle.record_failure("alice", "wrong password");
+    }
+
+    assert!(!throttle.max_reached());
+    assert!(!throttle.is_locked_out());
+    assert_eq!(throttle.state().max, None);
+  }
+
+  #[test]
+  fn resets_on_success() {
+    let dir = TempDir::new("throttle-success");
+    let config = config(&[0, 1, 5], 2, LockoutAction::Alert);
+    let mut throttle = throttle(&dir, &config);
+
+    throttle.record_failure("alice", "wrong password");
+    throttle.record_failure("alice", "wrong password");
+    throttle.record_success("alice");
+
+    assert_eq!(throttle.state().failed, 0);
+    assert!(!throttle.max_reached());
+    assert_eq!(
+      throttle.record_failure("alice", "wrong password"),
+      Duration::ZERO
+    );
+  }
+
+  /// The only test reading the attempts file from `$XDG_RUNTIME_DIR`
+  #[test]
+  fn keeps_attempts_in_the_runtime_dir() {
+    let dir = TempDir::new("throttle-runtime-dir");
+    std::env::set_var("XDG_RUNTIME_DIR", dir.path());
+
+    let config = config(&[0], 3, LockoutAction::RequireReboot);
+    let mut throttle = Throttle::load(&config);
+    throttle.record_failure("alice", "wrong password");
+    throttle.record_failure("alice", "wrong password");
+
+    let reloaded = Throttle::load(&config);
+    assert_eq!(reloaded.state().failed, 2);
+    assert_eq!(
+      std::fs::read_to_string(dir.path().join(ATTEMPTS_FILE)).unwrap(),
+      "2"
+    );
+
+    throttle.record_success("alice");
+    assert_eq!(Throttle::load(&config).state().failed, 0);
+  }
+}
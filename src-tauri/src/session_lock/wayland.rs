@@ -1,5 +1,8 @@
 use std::{
-  sync::{Arc, Mutex},
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex, PoisonError,
+  },
   thread::JoinHandle,
   time::Duration,
 };
@@ -8,37 +11,51 @@ use anyhow::Result;
 use gdk::{glib::translate::ToGlibPtr, prelude::*};
 use gtk::prelude::*;
 use smithay_client_toolkit::{
+  compositor::{CompositorHandler, CompositorState},
   output::{OutputHandler, OutputState},
   reexports::{
     calloop::{
-      channel::{Channel, Sender},
-      EventLoop, LoopHandle,
+      channel::{self, channel, Channel, Sender},
+      timer::{TimeoutAction, Timer},
+      EventLoop,
     },
     calloop_wayland_source::WaylandSource,
   },
   registry::{ProvidesRegistryState, RegistryState},
   registry_handlers,
+  seat::{
+    keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
+    Capability, SeatHandler, SeatState,
+  },
   session_lock::{
     SessionLock, SessionLockHandler, SessionLockState, SessionLockSurface,
     SessionLockSurfaceConfigure,
   },
+  shm::{Shm, ShmHandler},
 };
 use tauri::{Emitter, Listener};
-use tracing::error;
+use tracing::{error, info};
 use wayland_backend::client::Backend;
 use wayland_client::{
-  globals::registry_queue_init,
+  globals::{registry_queue_init, GlobalList},
   protocol::{
-    wl_buffer,
+    wl_buffer, wl_keyboard,
     wl_output::{self, WlOutput},
+    wl_seat, wl_surface,
   },
-  Connection, QueueHandle,
+  Connection, EventQueue, QueueHandle,
 };
 use webkit2gtk::WebViewExt;
 
 use crate::{
   config::Config,
-  util::{get_output_window_label, get_wl_surface, get_wl_window, rand_string},
+  primary::{resolve_primary, OutputInfo},
+  util::{get_output_window_label, get_wl_surface, get_wl_window},
+};
+
+use super::{
+  fallback::Fallback,
+  supervisor::{LOCK_THREAD, MAX_WEBVIEW_CRASHES, UI_START_TIMEOUT},
 };
 
 #[derive(Clone)]
@@ -46,30 +63,62 @@ struct TauriLockSurface {
   surface: SessionLockSurface,
   window: tauri::WebviewWindow,
   output: WlOutput,
-  output_name: String,
+  output_info: OutputInfo,
   is_active: bool,
 }
 
+/// Lock surfaces of the webview lock screen. `None` once the native
+/// fallback took over, so windows that are still being created don't add
+/// surfaces for outputs that already have one.
+type TauriLockSurfaces = Arc<Mutex<Option<Vec<TauriLockSurface>>>>;
+
+/// The webview lock screen, shown on the surfaces of the session lock
+struct Webview {
+  app: tauri::AppHandle,
+  surfaces: TauriLockSurfaces,
+  window_ready_tx: Sender<()>,
+  ui_failed_tx: Sender<()>,
+  crashes: Arc<AtomicU32>,
+  /// Whether the lock screen loaded on any output
+  ready: bool,
+}
+
+/// Holds the session lock until it is unlocked, whatever happens to the
+/// lock screen drawn on it. If the webview fails, the native fallback takes
+/// over the same lock.
 struct State {
   config: Config,
   running: bool,
-  loop_handle: LoopHandle<'static, Self>,
   conn: Connection,
-  session_lock_state: SessionLockState,
-  session_lock: Option<SessionLock>,
-  tauri_app: tauri::AppHandle,
+  qh: QueueHandle<Self>,
+  compositor_state: CompositorState,
   registry_state: RegistryState,
   output_state: OutputState,
-  lock_surfaces: Arc<Mutex<Vec<TauriLockSurface>>>,
-  window_ready_tx: Sender<()>,
+  seat_state: SeatState,
+  shm: Shm,
+  session_lock_state: SessionLockState,
+  session_lock: Option<SessionLock>,
+  webview: Option<Webview>,
+  fallback: Option<Fallback>,
+  keyboard: Option<wl_keyboard::WlKeyboard>,
+  auth_tx: Sender<bool>,
+  locked_tx: Option<tokio::sync::oneshot::Sender<()>>,
 }
 
+/// Locks the session with the webview lock screen, on the connection GTK
+/// uses so the webview windows can serve as lock surfaces. The lock is held
+/// on a thread of its own, which falls back to the native lock screen when
+/// `ui_failed_rx` reports the webview as failed, or the webview doesn't load
+/// in time.
 pub fn lock_session(
   config: Config,
   app_handle: &tauri::AppHandle,
   unlock_rx: Channel<()>,
   window_ready_tx: Sender<()>,
   window_ready_rx: Channel<()>,
+  ui_failed_tx: Sender<()>,
+  ui_failed_rx: Channel<()>,
+  locked_tx: tokio::sync::oneshot::Sender<()>,
 ) -> Result<JoinHandle<()>> {
   let display = gdk::Display::default().ok_or(anyhow::anyhow!("failed to get default display"))?;
   let wl_display = display
@@ -81,110 +130,207 @@ pub fn lock_session(
   let wl_conn = Connection::from_backend(wl_backend);
   let (globals, event_queue) = registry_queue_init(&wl_conn)?;
 
-  let qh: QueueHandle<State> = event_queue.handle();
+  let webview = Webview {
+    app: app_handle.clone(),
+    surfaces: Arc::new(Mutex::new(Some(Vec::new()))),
+    window_ready_tx,
+    ui_failed_tx,
+    crashes: Arc::new(AtomicU32::new(0)),
+    ready: false,
+  };
 
   let app_handle = app_handle.clone();
-  let thread_handle = std::thread::spawn(move || {
-    let mut event_loop: EventLoop<State> = match EventLoop::try_new() {
-      Ok(event_loop) => event_loop,
-      Err(err) => {
-        error!("Failed to create event loop: {err}");
-        app_handle.exit(1);
-        return;
+  let thread_handle = std::thread::Builder::new()
+    .name(LOCK_THREAD.to_string())
+    .spawn(move || {
+      let result = run(
+        config,
+        wl_conn,
+        globals,
+        event_queue,
+        Some(webview),
+        locked_tx,
+        |event_loop| {
+          let loop_handle = event_loop.handle();
+
+          loop_handle
+            .insert_source(unlock_rx, |event, _, state| {
+              // Only an explicit unlock, not the UI going away
+              if let channel::Event::Msg(()) = event {
+                state.unlock();
+              }
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert unlock source: {err}"))?;
+
+          loop_handle
+            .insert_source(window_ready_rx, |_, _, state| {
+              if let Some(webview) = &mut state.webview {
+                webview.ready = true;
+              }
+
+              state.assign_primary().unwrap_or_else(|err| {
+                error!("failed to assign primary: {err}");
+              })
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert window ready source: {err}"))?;
+
+          loop_handle
+            .insert_source(ui_failed_rx, |event, _, state| {
+              if let channel::Event::Msg(()) = event {
+                state.use_fallback();
+              }
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert ui failure source: {err}"))?;
+
+          loop_handle
+            .insert_source(Timer::from_duration(UI_START_TIMEOUT), |_, _, state| {
+              if state.webview.as_ref().is_some_and(|webview| !webview.ready) {
+                error!("lock screen didn't load in time");
+                state.use_fallback();
+              }
+
+              TimeoutAction::Drop
+            })
+            .map_err(|err| anyhow::anyhow!("failed to insert ui timeout source: {err}"))?;
+
+          Ok(())
+        },
+      );
+
+      match result {
+        Ok(true) => app_handle.exit(0),
+        // The main thread might be gone, it can't be relied on to exit
+        Ok(false) => std::process::exit(0),
+        Err(err) => {
+          error!("failed to lock session: {err}");
+          app_handle.exit(1);
+        }
       }
-    };
+    })?;
 
-    let loop_handle = event_loop.handle();
+  Ok(thread_handle)
+}
 
-    if let Err(err) = loop_handle.insert_source(unlock_rx, |_, _, app_data| app_data.unlock()) {
-      error!("failed to insert unlock source: {err}");
-      app_handle.exit(1);
-      return;
-    }
+/// Locks the session with only the native lock screen, for when the webview
+/// can't be created at all. Returns once the session is unlocked.
+pub fn lock_native(config: Config, locked_tx: tokio::sync::oneshot::Sender<()>) -> Result<()> {
+  let conn = Connection::connect_to_env()?;
+  let (globals, event_queue) = registry_queue_init(&conn)?;
 
-    if let Err(err) = loop_handle.insert_source(window_ready_rx, |_, _, app_data| {
-      app_data.assign_primary().unwrap_or_else(|err| {
-        error!("failed to assign primary: {err}");
-      })
-    }) {
-      error!("failed to insert window ready source: {err}");
-      app_handle.exit(1);
-      return;
-    }
+  run(config, conn, globals, event_queue, None, locked_tx, |_| {
+    Ok(())
+  })?;
 
-    let mut wl_state = State {
-      config,
-      running: true,
-      tauri_app: app_handle.clone(),
-      output_state: OutputState::new(&globals, &qh),
-      registry_state: RegistryState::new(&globals),
-      loop_handle,
-      conn: wl_conn.clone(),
-      session_lock_state: SessionLockState::new(&globals, &qh),
-      session_lock: None,
-      lock_surfaces: Arc::new(Mutex::new(Vec::new())),
-      window_ready_tx,
-    };
+  Ok(())
+}
 
-    let session_lock = match wl_state.session_lock_state.lock(&qh) {
-      Ok(session_lock) => session_lock,
-      Err(err) => {
-        error!("Compositor does not support ext_session_lock_v1: {err}");
-        app_handle.exit(1);
-        return;
+/// Locks the session and dispatches events until it is unlocked. Returns
+/// whether the webview lock screen was still in use.
+fn run(
+  config: Config,
+  conn: Connection,
+  globals: GlobalList,
+  event_queue: EventQueue<State>,
+  webview: Option<Webview>,
+  locked_tx: tokio::sync::oneshot::Sender<()>,
+  insert_sources: impl FnOnce(&EventLoop<'static, State>) -> Result<()>,
+) -> Result<bool> {
+  let qh = event_queue.handle();
+  let mut event_loop: EventLoop<State> = EventLoop::try_new()?;
+
+  let (auth_tx, auth_rx) = channel();
+  event_loop
+    .handle()
+    .insert_source(auth_rx, |event, _, state| {
+      if let channel::Event::Msg(success) = event {
+        state.finish_auth(success);
       }
-    };
+    })
+    .map_err(|err| anyhow::anyhow!("failed to insert auth source: {err}"))?;
+
+  insert_sources(&event_loop)?;
+
+  let mut state = State {
+    config,
+    running: true,
+    conn: conn.clone(),
+    qh: qh.clone(),
+    compositor_state: CompositorState::bind(&globals, &qh)?,
+    registry_state: RegistryState::new(&globals),
+    output_state: OutputState::new(&globals, &qh),
+    seat_state: SeatState::new(&globals, &qh),
+    shm: Shm::bind(&globals, &qh)?,
+    session_lock_state: SessionLockState::new(&globals, &qh),
+    session_lock: None,
+    webview,
+    fallback: None,
+    keyboard: None,
+    auth_tx,
+    locked_tx: Some(locked_tx),
+  };
+
+  // Check that the native lock screen can start before locking, so it
+  // works if the webview fails later
+  let fallback = Fallback::new(&state.config, &state.shm, state.auth_tx.clone())?;
+  if state.webview.is_none() {
+    state.fallback = Some(fallback);
+  }
 
-    if let Err(err) = WaylandSource::new(wl_conn.clone(), event_queue).insert(event_loop.handle()) {
-      error!("failed to insert wayland source: {err}");
-      app_handle.exit(1);
-      return;
-    }
+  state.session_lock = Some(
+    state
+      .session_lock_state
+      .lock(&qh)
+      .map_err(|err| anyhow::anyhow!("compositor does not support ext_session_lock_v1: {err}"))?,
+  );
 
-    wl_state.session_lock = Some(session_lock);
-    for output in wl_state.output_state.outputs() {
-      wl_state
-        .create_lock_surface(&qh, &output)
-        .unwrap_or_else(|err| {
-          error!("failed to create lock surface: {err}");
-        });
-    }
+  WaylandSource::new(conn, event_queue)
+    .insert(event_loop.handle())
+    .map_err(|err| anyhow::anyhow!("failed to insert wayland source: {err}"))?;
 
-    while wl_state.running {
-      event_loop
-        .dispatch(Duration::from_millis(16), &mut wl_state)
-        .unwrap_or_else(|err| {
-          error!("failed to dispatch event loop: {err}");
-        });
-    }
+  for output in state.output_state.outputs() {
+    state.create_lock_surface(&qh, &output);
+  }
 
-    app_handle.exit(0);
-  });
+  while state.running {
+    event_loop
+      .dispatch(Duration::from_millis(16), &mut state)
+      .unwrap_or_else(|err| {
+        error!("failed to dispatch event loop: {err}");
+      });
+  }
 
-  Ok(thread_handle)
+  Ok(state.webview.is_some())
 }
 
 impl State {
+  fn surfaces(&self) -> Option<&TauriLockSurfaces> {
+    self.webview.as_ref().map(|webview| &webview.surfaces)
+  }
+
   fn assign_primary(&mut self) -> Result<()> {
-    let surfaces = self
-      .lock_surfaces
+    let Some(surfaces) = self.surfaces() else {
+      return Ok(());
+    };
+
+    let surfaces = surfaces
       .lock()
       .map_err(|_| anyhow::anyhow!("failed to lock"))?;
 
-    if surfaces.is_empty() {
+    let Some(surfaces) = surfaces.as_ref().filter(|s| !s.is_empty()) else {
       return Ok(());
-    }
+    };
 
-    let primary = self
-      .config
-      .primary_display
+    let active = surfaces.iter().filter(|s| s.is_active).collect::<Vec<_>>();
+    let outputs = active
       .iter()
-      .find_map(|name| {
-        surfaces
-          .iter()
-          .find(|s| s.is_active && &s.output_name == name)
-      })
-      .unwrap_or(&surfaces[0]);
+      .map(|s| s.output_info.clone())
+      .collect::<Vec<_>>();
+
+    let Some(primary_index) = resolve_primary(&self.config.lock.primary_display, &outputs) else {
+      return Ok(());
+    };
+
+    let primary = active[primary_index];
 
     surfaces
       .iter()
@@ -209,13 +355,12 @@ impl State {
     Ok(())
   }
 
-  fn get_output_name(&mut self, output: &WlOutput) -> Result<String> {
-    let Some(info) = self.output_state().info(output) else {
-      return Ok(rand_string());
-    };
-
-    let result = info.name.unwrap_or_else(|| format!("{}", info.id));
-    Ok(result)
+  fn get_output_info(&mut self, output: &WlOutput) -> OutputInfo {
+    self
+      .output_state()
+      .info(output)
+      .map(|info| OutputInfo::from(&info))
+      .unwrap_or_default()
   }
 
   fn unlock(&mut self) {
@@ -235,51 +380,161 @@ impl State {
     self.running = false;
   }
 
-  /// Attempts to update the name for the given output in the lock surface list.
+  fn finish_auth(&mut self, success: bool) {
+    if success {
+      self.unlock();
+      return;
+    }
+
+    if let Some(fallback) = &mut self.fallback {
+      fallback.fail();
+    }
+  }
+
+  /// Replaces the webview lock screen with the native one, without ever
+  /// letting go of the lock. The webview surfaces are destroyed, which
+  /// leaves the outputs blank until the native surfaces are configured.
+  fn use_fallback(&mut self) {
+    if self.fallback.is_some() {
+      return;
+    }
+
+    let fallback = match Fallback::new(&self.config, &self.shm, self.auth_tx.clone()) {
+      Ok(fallback) => fallback,
+      Err(err) => {
+        error!("failed to start the native lock screen: {err}");
+        return;
+      }
+    };
+
+    info!("switching to the native lock screen");
+    self.fallback = Some(fallback);
+
+    if let Some(surfaces) = self.surfaces() {
+      let surfaces = surfaces
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+        .unwrap_or_default();
+
+      for surface in surfaces {
+        surface.window.close().unwrap_or_else(|err| {
+          error!("failed to close lock screen window: {err}");
+        });
+      }
+    }
+
+    let qh = self.qh.clone();
+    for output in self.output_state.outputs() {
+      self.create_lock_surface(&qh, &output);
+    }
+
+    for seat in self.seat_state.seats() {
+      self.get_keyboard(&qh, &seat);
+    }
+  }
+
+  /// Attempts to update the info for the given output in the lock surface list.
   /// Returns true if the output was present or anything went wrong, false otherwise.
-  fn refresh_output_name(&mut self, output: &wl_output::WlOutput) -> bool {
-    let surfaces = self.lock_surfaces.clone();
+  fn refresh_output_info(&mut self, output: &wl_output::WlOutput) -> bool {
+    let Some(surfaces) = self.surfaces().cloned() else {
+      return false;
+    };
+
     let Ok(mut surfaces) = surfaces.lock() else {
       error!("failed to lock surfaces for new output");
       return true;
     };
 
-    if let Some(found) = surfaces.iter_mut().find(|s| s.output == *output) {
-      let output_name = match self.get_output_name(output) {
-        Ok(output_name) => output_name,
-        Err(err) => {
-          error!("failed to get output name: {err}");
-          return true;
-        }
-      };
+    let Some(surfaces) = surfaces.as_mut() else {
+      return false;
+    };
 
-      found.output_name = output_name;
+    if let Some(found) = surfaces.iter_mut().find(|s| s.output == *output) {
+      found.output_info = self.get_output_info(output);
       return true;
     }
 
     false
   }
 
-  fn create_lock_surface(&mut self, qh: &QueueHandle<Self>, output: &WlOutput) -> Result<()> {
-    let output_name = self.get_output_name(output)?;
+  fn create_lock_surface(&mut self, qh: &QueueHandle<Self>, output: &WlOutput) {
+    let result = match self.fallback.is_some() {
+      true => self.create_native_lock_surface(qh, output),
+      false => self.create_webview_lock_surface(qh, output),
+    };
+
+    result.unwrap_or_else(|err| {
+      error!("failed to create lock surface: {err}");
+    });
+  }
+
+  fn create_native_lock_surface(
+    &mut self,
+    qh: &QueueHandle<Self>,
+    output: &WlOutput,
+  ) -> Result<()> {
+    let (Some(session_lock), Some(fallback)) = (self.session_lock.as_ref(), self.fallback.as_mut())
+    else {
+      anyhow::bail!("session lock not initialized");
+    };
+
+    if fallback.has_output(output) {
+      return Ok(());
+    }
+
+    let surface = self.compositor_state.create_surface(qh);
+    fallback.add_surface(
+      session_lock.create_lock_surface(surface, output, qh),
+      output,
+    );
+
+    Ok(())
+  }
+
+  fn create_webview_lock_surface(
+    &mut self,
+    qh: &QueueHandle<Self>,
+    output: &WlOutput,
+  ) -> Result<()> {
+    let output_info = self.get_output_info(output);
 
     let Some(session_lock) = self.session_lock.as_ref() else {
       anyhow::bail!("session lock not initialized");
     };
 
+    let Some(webview) = self.webview.as_ref() else {
+      anyhow::bail!("webview lock screen not initialized");
+    };
+
     let window_label = get_output_window_label(output);
     let window = tauri::WebviewWindow::builder(
-      &self.tauri_app,
+      &webview.app,
       window_label,
       tauri::WebviewUrl::App("src/login/index.html".into()),
     )
     .visible(false)
     .build()?;
 
-    window.with_webview(|webview| {
+    let crashes = webview.crashes.clone();
+    let ui_failed_tx = webview.ui_failed_tx.clone();
+    window.with_webview(move |webview| {
       let webview = webview.inner();
       webview.connect_context_menu(|_, _, _, _| true);
       webview.set_background_color(&gdk::RGBA::new(0., 0., 0., 0.));
+
+      webview.connect_web_process_terminated(move |webview, reason| {
+        error!("lock screen web process terminated: {reason:?}");
+
+        if crashes.fetch_add(1, Ordering::SeqCst) >= MAX_WEBVIEW_CRASHES {
+          ui_failed_tx.send(()).unwrap_or_else(|err| {
+            error!("failed to report failed lock screen: {err}");
+          });
+          return;
+        }
+
+        webview.reload();
+      });
     })?;
 
     let qh = qh.clone();
@@ -301,7 +556,7 @@ impl State {
     });
 
     let ev_window = window.clone();
-    let window_ready_tx = self.window_ready_tx.clone();
+    let window_ready_tx = webview.window_ready_tx.clone();
     window.listen("ready", move |ev| {
       // ev_window.open_devtools();
       ev_window.unlisten(ev.id());
@@ -311,7 +566,7 @@ impl State {
     });
 
     let conn = self.conn.clone();
-    let surfaces = self.lock_surfaces.clone();
+    let surfaces = webview.surfaces.clone();
     let output = output.clone();
     gtk_window.connect_realize(move |gtk_window| {
       let Ok(wl_window) = get_wl_window(gtk_window) else {
@@ -326,18 +581,22 @@ impl State {
         return;
       };
 
-      let lock_surface = session_lock.create_lock_surface(surface, &output, &qh);
-
       {
         let Ok(mut surfaces) = surfaces.lock() else {
           return;
         };
 
+        // The native lock screen took over in the meantime
+        let Some(surfaces) = surfaces.as_mut() else {
+          return;
+        };
+
+        let lock_surface = session_lock.create_lock_surface(surface, &output, &qh);
         surfaces.push(TauriLockSurface {
           surface: lock_surface,
           window: window.clone(),
           output: output.clone(),
-          output_name: output_name.clone(),
+          output_info: output_info.clone(),
           is_active: true,
         });
       }
@@ -349,13 +608,24 @@ impl State {
 
     Ok(())
   }
+
+  fn get_keyboard(&mut self, qh: &QueueHandle<Self>, seat: &wl_seat::WlSeat) {
+    if self.keyboard.is_some() {
+      return;
+    }
+
+    match self.seat_state.get_keyboard(qh, seat, None) {
+      Ok(keyboard) => self.keyboard = Some(keyboard),
+      Err(err) => error!("failed to get keyboard: {err}"),
+    }
+  }
 }
 
 impl ProvidesRegistryState for State {
   fn registry(&mut self) -> &mut RegistryState {
     &mut self.registry_state
   }
-  registry_handlers![OutputState,];
+  registry_handlers![OutputState, SeatState,];
 }
 
 impl OutputHandler for State {
@@ -369,16 +639,14 @@ impl OutputHandler for State {
     qh: &QueueHandle<Self>,
     output: wl_output::WlOutput,
   ) {
-    if self.refresh_output_name(&output) {
+    if self.refresh_output_info(&output) {
       self.assign_primary().unwrap_or_else(|err| {
         error!("failed to assign primary: {err}");
       });
       return;
     }
 
-    self.create_lock_surface(qh, &output).unwrap_or_else(|err| {
-      error!("failed to create lock surface: {err}");
-    })
+    self.create_lock_surface(qh, &output);
   }
 
   fn update_output(
@@ -387,7 +655,7 @@ impl OutputHandler for State {
     _qh: &QueueHandle<Self>,
     output: wl_output::WlOutput,
   ) {
-    self.refresh_output_name(&output);
+    self.refresh_output_info(&output);
 
     self.assign_primary().unwrap_or_else(|err| {
       error!("failed to assign primary: {err}");
@@ -400,13 +668,18 @@ impl OutputHandler for State {
     _qh: &QueueHandle<Self>,
     output: wl_output::WlOutput,
   ) {
-    {
-      let Ok(mut surfaces) = self.lock_surfaces.lock() else {
+    if let Some(fallback) = &mut self.fallback {
+      fallback.remove_output(&output);
+      return;
+    }
+
+    if let Some(surfaces) = self.surfaces() {
+      let Ok(mut surfaces) = surfaces.lock() else {
         error!("failed to lock surfaces for destroyed output");
         return;
       };
 
-      let Some(found) = surfaces.iter_mut().find(|s| s.output == output) else {
+      let Some(found) = surfaces.iter_mut().flatten().find(|s| s.output == output) else {
         error!("no surface found for destroyed output");
         return;
       };
@@ -427,6 +700,14 @@ impl OutputHandler for State {
 
 impl SessionLockHandler for State {
   fn locked(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _session_lock: SessionLock) {
+    info!("session locked");
+
+    if let Some(locked_tx) = self.locked_tx.take() {
+      locked_tx.send(()).unwrap_or_else(|_| {
+        error!("failed to send locked signal");
+      });
+    }
+
     self.assign_primary().unwrap_or_else(|err| {
       error!("failed to assign primary: {err}");
     })
@@ -444,14 +725,20 @@ impl SessionLockHandler for State {
     configure: SessionLockSurfaceConfigure,
     _serial: u32,
   ) {
-    {
-      let Ok(surfaces) = self.lock_surfaces.lock() else {
+    if let Some(fallback) = &mut self.fallback {
+      fallback.configure(&session_lock_surface, configure.new_size);
+      return;
+    }
+
+    if let Some(surfaces) = self.surfaces() {
+      let Ok(surfaces) = surfaces.lock() else {
         error!("failed to lock surfaces for configure");
         return;
       };
 
       let found = surfaces
         .iter()
+        .flatten()
         .find(|s| s.surface.wl_surface() == session_lock_surface.wl_surface());
 
       if let Some(found) = found {
@@ -472,7 +759,162 @@ impl SessionLockHandler for State {
   }
 }
 
+/// Keyboard input only goes to the native lock screen, the webview gets
+/// its input through GTK
+impl KeyboardHandler for State {
+  fn enter(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &wl_keyboard::WlKeyboard,
+    _: &wl_surface::WlSurface,
+    _: u32,
+    _: &[u32],
+    _: &[Keysym],
+  ) {
+  }
+
+  fn leave(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &wl_keyboard::WlKeyboard,
+    _: &wl_surface::WlSurface,
+    _: u32,
+  ) {
+  }
+
+  fn press_key(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _: &wl_keyboard::WlKeyboard,
+    _: u32,
+    event: KeyEvent,
+  ) {
+    if let Some(fallback) = &mut self.fallback {
+      fallback.press_key(event);
+    }
+  }
+
+  fn release_key(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &wl_keyboard::WlKeyboard,
+    _: u32,
+    _: KeyEvent,
+  ) {
+  }
+
+  fn update_modifiers(
+    &mut self,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+    _: &wl_keyboard::WlKeyboard,
+    _serial: u32,
+    _: Modifiers,
+    _layout: u32,
+  ) {
+  }
+}
+
+impl SeatHandler for State {
+  fn seat_state(&mut self) -> &mut SeatState {
+    &mut self.seat_state
+  }
+
+  fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+
+  fn new_capability(
+    &mut self,
+    _conn: &Connection,
+    qh: &QueueHandle<Self>,
+    seat: wl_seat::WlSeat,
+    capability: Capability,
+  ) {
+    if capability == Capability::Keyboard && self.fallback.is_some() {
+      self.get_keyboard(qh, &seat);
+    }
+  }
+
+  fn remove_capability(
+    &mut self,
+    _conn: &Connection,
+    _: &QueueHandle<Self>,
+    _: wl_seat::WlSeat,
+    capability: Capability,
+  ) {
+    if capability != Capability::Keyboard {
+      return;
+    }
+
+    if let Some(keyboard) = self.keyboard.take() {
+      keyboard.release();
+    }
+  }
+
+  fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+}
+
+impl CompositorHandler for State {
+  fn scale_factor_changed(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _surface: &wl_surface::WlSurface,
+    _new_factor: i32,
+  ) {
+  }
+
+  fn transform_changed(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _surface: &wl_surface::WlSurface,
+    _new_transform: wl_output::Transform,
+  ) {
+  }
+
+  fn frame(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _surface: &wl_surface::WlSurface,
+    _time: u32,
+  ) {
+  }
+
+  fn surface_enter(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _surface: &wl_surface::WlSurface,
+    _output: &wl_output::WlOutput,
+  ) {
+  }
+
+  fn surface_leave(
+    &mut self,
+    _conn: &Connection,
+    _qh: &QueueHandle<Self>,
+    _surface: &wl_surface::WlSurface,
+    _output: &wl_output::WlOutput,
+  ) {
+  }
+}
+
+impl ShmHandler for State {
+  fn shm_state(&mut self) -> &mut Shm {
+    &mut self.shm
+  }
+}
+
+smithay_client_toolkit::delegate_compositor!(State);
 smithay_client_toolkit::delegate_output!(State);
+smithay_client_toolkit::delegate_shm!(State);
+smithay_client_toolkit::delegate_seat!(State);
+smithay_client_toolkit::delegate_keyboard!(State);
 smithay_client_toolkit::delegate_session_lock!(State);
 smithay_client_toolkit::delegate_registry!(State);
 wayland_client::delegate_noop!(State: ignore wl_buffer::WlBuffer);
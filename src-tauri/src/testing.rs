@@ -0,0 +1,79 @@
+//! Helpers for tests that need more than the code under test
+
+use std::{
+  io::{BufRead, BufReader},
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+};
+
+/// A D-Bus daemon of its own for a test, so fake services can own the
+/// names of the real ones. Stopped when dropped.
+pub struct PrivateBus {
+  daemon: Child,
+  address: String,
+}
+
+impl PrivateBus {
+  pub fn start() -> Self {
+    let mut daemon = Command::new("dbus-daemon")
+      .args(["--session", "--nofork", "--print-address"])
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("failed to start dbus-daemon");
+
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.as_mut().unwrap())
+      .read_line(&mut address)
+      .unwrap();
+
+    Self {
+      daemon,
+      address: address.trim().to_string(),
+    }
+  }
+
+  pub fn connection(&self) -> zbus::ConnectionBuilder<'static> {
+    zbus::ConnectionBuilder::address(self.address.as_str()).unwrap()
+  }
+
+  pub async fn connect(&self) -> zbus::Connection {
+    self.connection().build().await.unwrap()
+  }
+}
+
+impl Drop for PrivateBus {
+  fn drop(&mut self) {
+    let _ = self.daemon.kill();
+    let _ = self.daemon.wait();
+  }
+}
+
+/// A directory of its own for a test under `$TMPDIR`. Removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  pub fn new(name: &str) -> Self {
+    let dir = std::env::temp_dir().join(format!("dash2-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    Self(dir)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.0
+  }
+
+  /// Writes a file, creating the directories leading to it
+  pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+    let path = self.0.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, contents).unwrap();
+    path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
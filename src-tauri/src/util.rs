@@ -9,6 +9,8 @@ use wayland_client::{
   Connection, Proxy,
 };
 
+use crate::{hyprland::data, secret::Secret};
+
 pub fn get_current_username() -> Option<String> {
   let uid = unsafe { libc::getuid() };
   let mut passwd = unsafe { std::mem::zeroed::<libc::passwd>() };
@@ -38,6 +40,67 @@ pub fn get_current_username() -> Option<String> {
   Some(String::from(raw.to_string_lossy()))
 }
 
+pub fn user_exists(name: &str) -> bool {
+  let Ok(name) = std::ffi::CString::new(name) else {
+    return false;
+  };
+
+  let mut passwd = unsafe { std::mem::zeroed::<libc::passwd>() };
+  let mut buf = vec![0; 2048];
+  let mut result = std::ptr::null_mut::<libc::passwd>();
+
+  loop {
+    let r = unsafe {
+      libc::getpwnam_r(
+        name.as_ptr(),
+        &mut passwd,
+        buf.as_mut_ptr(),
+        buf.len(),
+        &mut result,
+      )
+    };
+
+    if r != libc::ERANGE {
+      break;
+    }
+
+    let Some(newsize) = buf.len().checked_mul(2) else {
+      return false;
+    };
+    buf.resize(newsize, 0);
+  }
+
+  !result.is_null()
+}
+
+/// Reads a line from stdin without echoing it, if stdin is a terminal
+pub fn read_password(prompt: &str) -> std::io::Result<Secret> {
+  use std::io::{BufRead, Write};
+
+  eprint!("{prompt}");
+  std::io::stderr().flush()?;
+
+  let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+  let is_tty = unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } == 0;
+  if is_tty {
+    let mut silent = termios;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) };
+  }
+
+  let mut line = String::new();
+  let result = std::io::stdin().lock().read_line(&mut line);
+
+  if is_tty {
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) };
+  }
+
+  result?;
+  line.truncate(line.trim_end_matches(['\r', '\n']).len());
+  Ok(Secret::new(line))
+}
+
 pub fn rand_string() -> String {
   rand::thread_rng()
     .sample_iter(&Alphanumeric)
@@ -95,3 +158,15 @@ pub fn get_output_window_label(output: &WlOutput) -> String {
 
   format!("lock-{}", sanitized)
 }
+
+/// Finds the hyprland monitor for a gdk monitor by comparing their positions,
+/// since gdk does not expose the output's connector name.
+pub fn get_hyprland_monitor<'a>(
+  monitor: &gdk::Monitor,
+  hyprland_monitors: &'a [data::Monitor],
+) -> Option<&'a data::Monitor> {
+  let geometry = monitor.geometry();
+  hyprland_monitors
+    .iter()
+    .find(|m| m.x == geometry.x() && m.y == geometry.y())
+}
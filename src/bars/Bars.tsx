@@ -2,8 +2,9 @@ import { createResizeObserver } from "@solid-primitives/resize-observer"
 import { invoke } from "@tauri-apps/api/core"
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow"
 import clsx from "clsx"
-import { createSignal } from "solid-js"
+import { createSignal, Show } from "solid-js"
 import { makeEventListener } from "@solid-primitives/event-listener"
+import { createConfigSignal, DesktopConfig, Widget } from "../config"
 
 const current = getCurrentWebviewWindow()
 
@@ -11,6 +12,10 @@ export const Bars = () => {
   let timer: number | null = null
   const [isVisible, setIsVisible] = createSignal(false)
 
+  const config = createConfigSignal<DesktopConfig>()
+  const isEnabled = (widget: Widget) =>
+    config()?.widgets.includes(widget) ?? true
+
   current.listen("enter", async () => {
     await invoke("request_height", { height: 150 })
     setIsVisible(true)
@@ -40,12 +45,16 @@ export const Bars = () => {
         !isVisible() && "translate-y-[40px] opacity-0"
       )}
     >
-      <Bar
-        icon="icon-[ph--sun-dim-fill] text-2xl translate-x-[-2px]"
-        color="bg-yellow-400"
-      />
-
-      <Bar icon="icon-[ph--speaker-high-fill] text-xl" color="bg-amber-600" />
+      <Show when={isEnabled("brightness")}>
+        <Bar
+          icon="icon-[ph--sun-dim-fill] text-2xl translate-x-[-2px]"
+          color="bg-yellow-400"
+        />
+      </Show>
+
+      <Show when={isEnabled("volume")}>
+        <Bar icon="icon-[ph--speaker-high-fill] text-xl" color="bg-amber-600" />
+      </Show>
     </div>
   )
 }
@@ -1,11 +1,22 @@
 import { format } from "date-fns"
 import { createSignal, onCleanup } from "solid-js"
 
-const getTime = () => format(new Date(), "p")
+const DEFAULT_FORMAT = "p"
 
-export const createClockSignal = () => {
-  const [time, setTime] = createSignal(getTime())
-  const interval = setInterval(() => setTime(getTime()), 1000)
+// Falls back to the default for patterns date-fns can't handle
+const getTime = (pattern: string) => {
+  try {
+    return format(new Date(), pattern)
+  } catch {
+    return format(new Date(), DEFAULT_FORMAT)
+  }
+}
+
+export const createClockSignal = (
+  pattern: () => string = () => DEFAULT_FORMAT
+) => {
+  const [time, setTime] = createSignal(getTime(pattern()))
+  const interval = setInterval(() => setTime(getTime(pattern())), 1000)
   onCleanup(() => clearInterval(interval))
 
   return time
@@ -0,0 +1,57 @@
+import { invoke } from "@tauri-apps/api/core"
+import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow"
+import { createSignal, JSX, onCleanup, onMount } from "solid-js"
+
+export type Appearance = {
+  background: string | null
+  clock_format: string
+}
+
+export type Widget =
+  | "clock"
+  | "calendar"
+  | "wifi"
+  | "bluetooth"
+  | "audio"
+  | "power"
+  | "brightness"
+  | "volume"
+
+export type DesktopConfig = {
+  widgets: Widget[]
+  clock_format: string
+}
+
+// The section of the config for the current mode, kept up to date
+// when the desktop reloads its config
+export const createConfigSignal = <T>() => {
+  const [config, setConfig] = createSignal<T | null>(null)
+
+  let unlisten: VoidFunction | null = null
+  onMount(async () => {
+    unlisten = await getCurrentWebviewWindow().listen<T>(
+      "config-changed",
+      ev => setConfig(() => ev.payload)
+    )
+
+    const initial = await invoke<T>("get_config")
+    setConfig(() => initial)
+  })
+
+  onCleanup(() => unlisten?.())
+
+  return config
+}
+
+// The background is served by the backend, webviews can't load
+// arbitrary files from disk
+export const backgroundStyle = (
+  appearance: Appearance | null
+): JSX.CSSProperties =>
+  appearance?.background != null
+    ? {
+        "background-image": "url(background://localhost/)",
+        "background-size": "cover",
+        "background-position": "center",
+      }
+    : {}
@@ -1,8 +1,9 @@
 import { invoke } from "@tauri-apps/api/core"
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow"
 import clsx from "clsx"
-import { createSignal, For, JSX, onCleanup, onMount } from "solid-js"
+import { createSignal, For, JSX, onCleanup, onMount, Show } from "solid-js"
 import { createClockSignal } from "../clock"
+import { createConfigSignal, DesktopConfig, Widget } from "../config"
 import { createResizeObserver } from "@solid-primitives/resize-observer"
 import { createEventListener } from "@solid-primitives/event-listener"
 
@@ -21,6 +22,10 @@ export const Control = () => {
   const [isVisible, setIsVisible] = createSignal(false)
   const [openedMenu, setOpenedMenu] = createSignal<string | null>()
 
+  const config = createConfigSignal<DesktopConfig>()
+  const isEnabled = (widget: Widget) =>
+    config()?.widgets.includes(widget) ?? true
+
   const close = async () => {
     if (timer() != null) return
     if (openedMenu() != null) return
@@ -87,17 +92,21 @@ export const Control = () => {
           if (ev.clientY < rect.top) close()
         }}
       >
-        <WifiMenu
-          close={close}
-          setMenuOpen={open => setOpenedMenu(open ? "wifi" : null)}
-          menuOpen={openedMenu() === "wifi"}
-        />
-
-        <BluetoothMenu
-          close={close}
-          setMenuOpen={open => setOpenedMenu(open ? "bluetooth" : null)}
-          menuOpen={openedMenu() === "bluetooth"}
-        />
+        <Show when={isEnabled("wifi")}>
+          <WifiMenu
+            close={close}
+            setMenuOpen={open => setOpenedMenu(open ? "wifi" : null)}
+            menuOpen={openedMenu() === "wifi"}
+          />
+        </Show>
+
+        <Show when={isEnabled("bluetooth")}>
+          <BluetoothMenu
+            close={close}
+            setMenuOpen={open => setOpenedMenu(open ? "bluetooth" : null)}
+            menuOpen={openedMenu() === "bluetooth"}
+          />
+        </Show>
 
         <div
           ref={container}
@@ -106,38 +115,52 @@ export const Control = () => {
             !isVisible() && "translate-y-[40px] opacity-0"
           )}
         >
-          <Clock />
+          <Show when={isEnabled("clock")}>
+            <Clock format={config()?.clock_format} />
+          </Show>
 
           <div class="flex gap-2 text-2xl items-center justify-center">
-            <MenuButton
-              text="19. June"
-              icon="icon-[mdi--calendar]"
-              menuOpen={false}
-              setMenuOpen={() => {}}
-            />
-
-            <MenuButton
-              text="Sacred Heart"
-              icon="icon-[ic--round-wifi]"
-              setMenuOpen={open => setOpenedMenu(open ? "wifi" : null)}
-              menuOpen={openedMenu() === "wifi"}
-            />
-
-            <MenuButton
-              text="Pixel Buds Pro 2"
-              icon="icon-[ph--bluetooth-bold]"
-              setMenuOpen={open => setOpenedMenu(open ? "bluetooth" : null)}
-              menuOpen={openedMenu() === "bluetooth"}
-            />
-
-            <MenuButton
-              text="Headphones 2"
-              icon="icon-[ic--baseline-speaker]"
-              menuOpen={false}
-              setMenuOpen={() => {}}
-            />
-
-            <SimpleIcon icon="icon-[mingcute--power-fill] translate-y-[-1px]" />
+            <Show when={isEnabled("calendar")}>
+              <MenuButton
+                text="19. June"
+                icon="icon-[mdi--calendar]"
+                menuOpen={false}
+                setMenuOpen={() => {}}
+              />
+            </Show>
+
+            <Show when={isEnabled("wifi")}>
+              <MenuButton
+                text="Sacred Heart"
+                icon="icon-[ic--round-wifi]"
+                setMenuOpen={open => setOpenedMenu(open ? "wifi" : null)}
+                menuOpen={openedMenu() === "wifi"}
+              />
+            </Show>
+
+            <Show when={isEnabled("bluetooth")}>
+              <MenuButton
+                text="Pixel Buds Pro 2"
+                icon="icon-[ph--bluetooth-bold]"
+                setMenuOpen={open => setOpenedMenu(open ? "bluetooth" : null)}
+                menuOpen={openedMenu() === "bluetooth"}
+              />
+            </Show>
+
+            <Show when={isEnabled("audio")}>
+              <MenuButton
+                text="Headphones 2"
+                icon="icon-[ic--baseline-speaker]"
+                menuOpen={false}
+                setMenuOpen={() => {}}
+              />
+            </Show>
+
+            <Show when={isEnabled("power")}>
+              <SimpleIcon
+                icon="icon-[mingcute--power-fill] translate-y-[-1px]"
+              />
+            </Show>
           </div>
         </div>
       </div>
@@ -276,8 +299,10 @@ const MenuButton = (props: TextIconProps) => (
   </div>
 )
 
-const Clock = () => {
-  const time = createClockSignal()
+type ClockProps = { format?: string }
+
+const Clock = (props: ClockProps) => {
+  const time = createClockSignal(() => props.format ?? "p")
   return (
     <div class="text-4xl text-gray-200 font-bold select-none">{time()}</div>
   )
@@ -1,8 +1,9 @@
 import { invoke } from "@tauri-apps/api/core"
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow"
 import clsx from "clsx"
-import { createMemo, createSignal, onMount, Show } from "solid-js"
+import { createMemo, createSignal, For, onMount, Show } from "solid-js"
 import { createClockSignal } from "../clock"
+import { Appearance, backgroundStyle, createConfigSignal } from "../config"
 import { twMerge } from "tailwind-merge"
 
 const current = getCurrentWebviewWindow()
@@ -12,21 +13,107 @@ type BatteryState = {
   psu_connected: boolean
 }
 
+type User = {
+  name: string
+  uid: number
+  display_name: string
+}
+
+type Session = {
+  id: string
+  name: string
+  kind: "wayland" | "x11" | "config"
+}
+
+type AuthMessage = {
+  kind: "visible" | "secret" | "info" | "error"
+  message: string
+}
+
+type AttemptState = {
+  failed: number
+  max: number | null
+  locked_out: boolean
+}
+
+type Selection = {
+  user: string | null
+  session: string | null
+}
+
 export const Login = () => {
+  const config = createConfigSignal<Appearance>()
+
   const [isPrimary, setIsPrimary] = createSignal(false)
   const [fadeIn, setFadeIn] = createSignal(false)
   const [hasBattery, setHasBattery] = createSignal(false)
   const [psuConnected, setPsuConnected] = createSignal(false)
   const [batteryPercentage, setBatteryPercentage] = createSignal(0)
 
+  const [isGreeter, setIsGreeter] = createSignal(false)
+  const [users, setUsers] = createSignal<User[]>([])
+  const [sessions, setSessions] = createSignal<Session[]>([])
+  const [selectedUser, setSelectedUser] = createSignal<string | null>(null)
+  const [selectedSession, setSelectedSession] = createSignal<string | null>(
+    null
+  )
+
   const [isCheckingPassword, setIsCheckingPassword] = createSignal(false)
   const [isCheckingFingerprint, setIsCheckingFingerprint] = createSignal(false)
   const [hasPasswordError, setHasPasswordError] = createSignal(false)
   const [hasFingerprintError, setHasFingerprintError] = createSignal(false)
 
+  // Follow-up prompts and messages from the PAM conversation
+  const [prompt, setPrompt] = createSignal<AuthMessage | null>(null)
+  const [authInfo, setAuthInfo] = createSignal<AuthMessage | null>(null)
+
+  const [attempts, setAttempts] = createSignal<AttemptState | null>(null)
+
   onMount(async () => {
     await invoke("window_ready")
 
+    // Right after an idle lock, any input unlocks again without a password
+    const graceRemaining = await invoke<number>("get_grace_remaining").catch(
+      () => 0
+    )
+    if (graceRemaining > 0) {
+      const unlock = (ev: Event) => {
+        // Entering the window reports a pointer move without any movement
+        if (ev instanceof PointerEvent && ev.type === "pointermove") {
+          if (ev.movementX === 0 && ev.movementY === 0) return
+        }
+
+        invoke("grace_unlock")
+      }
+
+      const events = ["keydown", "pointerdown", "pointermove", "wheel"]
+      events.forEach(event => window.addEventListener(event, unlock))
+      setTimeout(
+        () => events.forEach(event => window.removeEventListener(event, unlock)),
+        graceRemaining
+      )
+    }
+
+    // Attempts are only tracked on the lock screen
+    setAttempts(
+      await invoke<AttemptState | null>("get_attempts").catch(() => null)
+    )
+
+    // User and session selection is only available in the greeter
+    const selection = await invoke<Selection>("get_selection").catch(() => null)
+    if (selection != null) {
+      setIsGreeter(true)
+      setUsers(await invoke<User[]>("get_users"))
+      setSessions(await invoke<Session[]>("get_sessions"))
+      setSelectedUser(selection.user)
+      setSelectedSession(selection.session)
+    } else {
+      // On the lock screen, admin users can unlock in place of the locked user
+      const names = await invoke<string[]>("get_unlock_users")
+      setUsers(names.map(name => ({ name, uid: -1, display_name: name })))
+      setSelectedUser(names[0] ?? null)
+    }
+
     const state = await invoke<BatteryState | null>("get_battery_state")
     if (state != null) {
       setHasBattery(true)
@@ -40,7 +127,23 @@ export const Login = () => {
     setBatteryPercentage(ev.payload)
   )
 
-  current.listen<string>("password-error", () => {
+  current.listen<AttemptState>("attempts", ev => setAttempts(ev.payload))
+
+  current.listen<AuthMessage>("auth-message", ev => {
+    if (ev.payload.kind === "info" || ev.payload.kind === "error") {
+      setAuthInfo(ev.payload)
+      return
+    }
+
+    setPrompt(ev.payload)
+    setIsCheckingPassword(false)
+    passwordField.value = ""
+    passwordField.focus()
+  })
+
+  current.listen<string>("password-error", ev => {
+    setPrompt(null)
+    setAuthInfo({ kind: "error", message: ev.payload })
     setHasPasswordError(true)
     setIsCheckingPassword(false)
     passwordField.focus()
@@ -56,9 +159,37 @@ export const Login = () => {
     setIsCheckingFingerprint(false)
   })
 
-  const isLoading = createMemo(
-    () => isCheckingPassword() || isCheckingFingerprint()
-  )
+  // A bad scan, the reader keeps waiting for the finger
+  current.listen<string>("fingerprint-retry", () => {
+    setHasFingerprintError(true)
+  })
+
+  current.listen("fingerprint-match", () => {
+    setHasFingerprintError(false)
+    setIsCheckingFingerprint(false)
+  })
+
+  // Fingerprint scanning runs in the background, so
+  // it doesn't block entering a password
+  const isLoading = createMemo(() => isCheckingPassword())
+  const isLockedOut = createMemo(() => attempts()?.locked_out ?? false)
+
+  const displayName = createMemo(() => {
+    const user = users().find(u => u.name === selectedUser())
+    return user?.display_name ?? selectedUser() ?? ""
+  })
+
+  const selectUser = async (name: string) => {
+    if (isGreeter()) await invoke("select_user", { name })
+    setSelectedUser(name)
+    passwordField.focus()
+  }
+
+  const selectSession = async (id: string) => {
+    await invoke("select_session", { id })
+    setSelectedSession(id)
+    passwordField.focus()
+  }
 
   let fadeInTimer: number | null
   current.listen<boolean>("is-primary", ev => {
@@ -77,11 +208,18 @@ export const Login = () => {
   })
 
   const submit = async (value: string) => {
-    if (isLoading()) return
+    if (isLoading() || isLockedOut()) return
     if (passwordField.value.length === 0) return
 
     setIsCheckingPassword(true)
-    await invoke("submit_password", { value })
+    setAuthInfo(null)
+
+    if (prompt() != null) {
+      setPrompt(null)
+      await invoke("respond_auth_message", { value })
+    } else {
+      await invoke("submit_password", { value, user: selectedUser() })
+    }
   }
 
   // Before suspending, focus the password field so we
@@ -96,6 +234,7 @@ export const Login = () => {
   return (
     <Show when={isPrimary()}>
       <div
+        style={backgroundStyle(config())}
         class={twMerge(
           "w-full h-screen flex items-center justify-center cursor-default select-none transition-opacity duration-500 opacity-0",
           fadeIn() && "opacity-1"
@@ -104,7 +243,23 @@ export const Login = () => {
         <div class="flex flex-col items-center justify-center gap-4">
           <img src="/profile.webp" class="rounded-full h-[100px]" />
 
-          <h1 class="text-stone-200 text-lg font-bold">happens</h1>
+          <Show
+            when={users().length > 1}
+            fallback={
+              <h1 class="text-stone-200 text-lg font-bold">{displayName()}</h1>
+            }
+          >
+            <select
+              value={selectedUser() ?? ""}
+              onChange={ev => selectUser(ev.currentTarget.value)}
+              disabled={isLoading()}
+              class="text-stone-200 text-lg font-bold bg-transparent text-center focus:outline-none cursor-pointer"
+            >
+              <For each={users()}>
+                {user => <option value={user.name}>{user.display_name}</option>}
+              </For>
+            </select>
+          </Show>
 
           <div class="flex flex-col gap-2">
             <div class="flex gap-2 items-center relative">
@@ -116,8 +271,9 @@ export const Login = () => {
                   if (ev.key === "Enter") submit(passwordField.value)
                 }}
                 autofocus
-                disabled={isLoading()}
-                type="password"
+                disabled={isLoading() || isLockedOut()}
+                placeholder={prompt()?.message}
+                type={prompt()?.kind === "visible" ? "text" : "password"}
                 class={clsx(
                   "focus:outline-none transition w-[200px] rounded-full px-4 py-1 text-stone-200 bg-stone-700 hover:bg-stone-600 focus:bg-stone-600 border disabled:opacity-50 disabled:pointer-events-none",
                   !hasPasswordError() &&
@@ -142,15 +298,52 @@ export const Login = () => {
                     "text-stone-200",
                     isCheckingPassword() &&
                       "icon-[ph--circle-notch] animate-spin text-2xl",
-                    isCheckingFingerprint() &&
+                    !isCheckingPassword() &&
+                      isCheckingFingerprint() &&
                       "icon-[ph--fingerprint] animate-pulse text-2xl",
-                    !isLoading() && "icon-[ph--arrow-right-bold] text-xl"
+                    !isLoading() &&
+                      !isCheckingFingerprint() &&
+                      "icon-[ph--arrow-right-bold] text-xl"
                   )}
                 />
               </button>
             </div>
           </div>
 
+          <Show when={authInfo()}>
+            {info => (
+              <span
+                class={clsx(
+                  "text-sm max-w-[300px] text-center",
+                  info().kind === "error" ? "text-red-400" : "text-stone-400"
+                )}
+              >
+                {info().message}
+              </span>
+            )}
+          </Show>
+
+          <Show when={(attempts()?.failed ?? 0) > 0}>
+            <span class="text-sm text-stone-400">
+              {attempts()!.failed}
+              {attempts()!.max != null && ` / ${attempts()!.max}`} failed
+              attempts
+            </span>
+          </Show>
+
+          <Show when={sessions().length > 1}>
+            <select
+              value={selectedSession() ?? ""}
+              onChange={ev => selectSession(ev.currentTarget.value)}
+              disabled={isLoading()}
+              class="text-stone-400 text-sm bg-transparent text-center focus:outline-none cursor-pointer"
+            >
+              <For each={sessions()}>
+                {session => <option value={session.id}>{session.name}</option>}
+              </For>
+            </select>
+          </Show>
+
           <div
             class={clsx(
               "text-stone-400 flex items-center gap-2 relative",
@@ -184,7 +377,7 @@ export const Login = () => {
             disabled={isLoading()}
             onBeforeSuspend={onBeforeSuspend}
           />
-          <Clock />
+          <Clock format={config()?.clock_format} />
         </div>
       </div>
     </Show>
@@ -246,8 +439,10 @@ const PowerControls = (props: PowerControlsProps) => (
   </div>
 )
 
-const Clock = () => {
-  const time = createClockSignal()
+type ClockProps = { format?: string }
+
+const Clock = (props: ClockProps) => {
+  const time = createClockSignal(() => props.format ?? "p")
 
   return (
     <div class="text-stone-200 text-3xl text-center font-bold select-none">
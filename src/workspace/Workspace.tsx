@@ -2,15 +2,26 @@ import { createResizeObserver } from "@solid-primitives/resize-observer"
 import { invoke } from "@tauri-apps/api/core"
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow"
 import clsx from "clsx"
-import { createSignal, For } from "solid-js"
+import { createSignal, For, onMount } from "solid-js"
 
 const current = getCurrentWebviewWindow()
 
+type MonitorWorkspaces = {
+  workspaces: Array<number>
+  active: number | null
+}
+
 export const Workspace = () => {
   let timer: number | null = null
   const [isVisible, setIsVisible] = createSignal(false)
   const [active, setActive] = createSignal(0)
-  const [workspaces, setWorkspaces] = createSignal([0, 1, 2])
+  const [workspaces, setWorkspaces] = createSignal<Array<number>>([])
+
+  onMount(async () => {
+    const state = await invoke<MonitorWorkspaces>("get_workspaces")
+    setWorkspaces(state.workspaces)
+    if (state.active != null) setActive(state.active)
+  })
 
   current.listen("enter", async () => {
     await invoke("request_height", { height: 80 })
@@ -12,6 +12,9 @@ export default defineConfig(async () => ({
       input: {
         login: resolve(__dirname, "./src/login/index.html"),
         control: resolve(__dirname, "./src/control/index.html"),
+        bars: resolve(__dirname, "./src/bars/index.html"),
+        workspace: resolve(__dirname, "./src/workspace/index.html"),
+        desktop: resolve(__dirname, "./src/desktop/index.html"),
       },
     },
   },